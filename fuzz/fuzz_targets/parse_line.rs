@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/protocol.rs"]
+#[allow(dead_code)]
+mod protocol;
+
+fuzz_target!(|data: &[u8]| {
+    // The serial reader only hands over valid UTF-8 lines
+    if let Ok(line) = std::str::from_utf8(data) {
+        for name in protocol::PROTOCOL_NAMES {
+            if let Some(mut parser) = protocol::create_protocol(name) {
+                let _ = parser.parse_line(line);
+            }
+        }
+    }
+});
@@ -0,0 +1,154 @@
+use super::{AudioBackend, AudioDevice, AudioSession, DataFlow, Endpoint};
+use anyhow::Result;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeCall {
+    Session { process_name: String, volume: f32 },
+    Endpoint { endpoint: Endpoint, volume: f32 },
+    DefaultDevice { flow: DataFlow, name: String },
+    SessionMute { process_name: String, muted: bool },
+    EndpointMute { endpoint: Endpoint, muted: bool },
+}
+
+/// One line of an expected trace for replays. Volumes are rounded to three decimals so traces
+/// don't break over float noise.
+impl fmt::Display for VolumeCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoint_name = |endpoint: &Endpoint| match endpoint {
+            Endpoint::DefaultOutput => "output".to_string(),
+            Endpoint::Capture(name) => format!("capture {}", name),
+        };
+        match self {
+            VolumeCall::Session { process_name, volume } => write!(f, "volume {} {:.3}", process_name, volume),
+            VolumeCall::Endpoint { endpoint, volume } => write!(f, "volume {} {:.3}", endpoint_name(endpoint), volume),
+            VolumeCall::DefaultDevice { flow, name } => write!(f, "default {:?} {}", flow, name),
+            VolumeCall::SessionMute { process_name, muted } => write!(f, "mute {} {}", process_name, muted),
+            VolumeCall::EndpointMute { endpoint, muted } => write!(f, "mute {} {}", endpoint_name(endpoint), muted),
+        }
+    }
+}
+
+/// In-memory backend that records every call instead of touching a real mixer.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    pub sessions: Vec<AudioSession>,
+    pub playback_devices: Vec<AudioDevice>,
+    pub capture_devices: Vec<AudioDevice>,
+    pub calls: Vec<VolumeCall>,
+    /// Ids of muted sessions.
+    pub muted_sessions: Vec<u64>,
+    pub muted_endpoints: Vec<Endpoint>,
+    /// Last volume set per session id. Sessions start at full volume.
+    pub session_volumes: Vec<(u64, f32)>,
+    pub endpoint_volumes: Vec<(Endpoint, f32)>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_session(self, pid: u32, process_name: &str) -> Self {
+        self.with_app(pid, process_name, "", "")
+    }
+
+    /// A session with the executable's path and the session's display name filled in.
+    pub fn with_app(mut self, pid: u32, process_name: &str, exe_path: &str, display_name: &str) -> Self {
+        let id = self.sessions.len() as u64;
+        self.sessions.push(AudioSession {
+            id,
+            pid,
+            process_name: process_name.to_string(),
+            exe_path: exe_path.to_string(),
+            display_name: display_name.to_string(),
+        });
+        self
+    }
+
+    pub fn with_device(mut self, flow: DataFlow, name: &str) -> Self {
+        let device = AudioDevice { name: name.to_string(), id: format!("mock:{}", name) };
+        match flow {
+            DataFlow::Render => self.playback_devices.push(device),
+            DataFlow::Capture => self.capture_devices.push(device),
+        }
+        self
+    }
+
+    fn check_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+        if let Endpoint::Capture(name) = endpoint {
+            if !self.capture_devices.iter().any(|d| d.name.to_lowercase() == name.to_lowercase()) {
+                return Err(anyhow::anyhow!("Microphone not found"));
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, call: VolumeCall) {
+        self.calls.push(call);
+    }
+}
+
+impl AudioBackend for MockBackend {
+    fn list_sessions(&mut self) -> Result<Vec<AudioSession>> {
+        Ok(self.sessions.clone())
+    }
+
+    fn session_volume(&mut self, session: &AudioSession) -> Result<f32> {
+        Ok(self.session_volumes.iter().find(|(id, _)| *id == session.id).map(|(_, v)| *v).unwrap_or(1.0))
+    }
+
+    fn set_session_volume(&mut self, session: &AudioSession, volume: f32) -> Result<()> {
+        self.session_volumes.retain(|(id, _)| *id != session.id);
+        self.session_volumes.push((session.id, volume));
+        self.record(VolumeCall::Session { process_name: session.process_name.clone(), volume });
+        Ok(())
+    }
+
+    fn endpoint_volume(&mut self, endpoint: &Endpoint) -> Result<f32> {
+        self.check_endpoint(endpoint)?;
+        Ok(self.endpoint_volumes.iter().find(|(e, _)| e == endpoint).map(|(_, v)| *v).unwrap_or(1.0))
+    }
+
+    fn set_endpoint_volume(&mut self, endpoint: &Endpoint, volume: f32) -> Result<()> {
+        self.check_endpoint(endpoint)?;
+        self.endpoint_volumes.retain(|(e, _)| e != endpoint);
+        self.endpoint_volumes.push((endpoint.clone(), volume));
+        self.record(VolumeCall::Endpoint { endpoint: endpoint.clone(), volume });
+        Ok(())
+    }
+
+    fn list_devices(&mut self, flow: DataFlow) -> Result<Vec<AudioDevice>> {
+        Ok(match flow {
+            DataFlow::Render => self.playback_devices.clone(),
+            DataFlow::Capture => self.capture_devices.clone(),
+        })
+    }
+
+    fn set_default_device(&mut self, flow: DataFlow, device: &AudioDevice) -> Result<()> {
+        self.record(VolumeCall::DefaultDevice { flow, name: device.name.clone() });
+        Ok(())
+    }
+
+    fn session_muted(&mut self, session: &AudioSession) -> Result<bool> {
+        Ok(self.muted_sessions.contains(&session.id))
+    }
+
+    fn set_session_mute(&mut self, session: &AudioSession, muted: bool) -> Result<()> {
+        self.muted_sessions.retain(|id| *id != session.id);
+        if muted { self.muted_sessions.push(session.id); }
+        self.record(VolumeCall::SessionMute { process_name: session.process_name.clone(), muted });
+        Ok(())
+    }
+
+    fn endpoint_muted(&mut self, endpoint: &Endpoint) -> Result<bool> {
+        self.check_endpoint(endpoint)?;
+        Ok(self.muted_endpoints.contains(endpoint))
+    }
+
+    fn set_endpoint_mute(&mut self, endpoint: &Endpoint, muted: bool) -> Result<()> {
+        self.check_endpoint(endpoint)?;
+        self.muted_endpoints.retain(|e| e != endpoint);
+        if muted { self.muted_endpoints.push(endpoint.clone()); }
+        self.record(VolumeCall::EndpointMute { endpoint: endpoint.clone(), muted });
+        Ok(())
+    }
+}
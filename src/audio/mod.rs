@@ -0,0 +1,111 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+mod mock;
+#[cfg(target_os = "linux")]
+mod pulse;
+#[cfg(windows)]
+mod wasapi;
+
+pub use mock::MockBackend;
+#[cfg(target_os = "linux")]
+pub use pulse::PulseBackend;
+#[cfg(windows)]
+pub use wasapi::WasapiBackend;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFlow { Render, Capture }
+
+/// One audio session as seen by the backend. `id` is only valid until the next `list_sessions` call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSession {
+    pub id: u64,
+    pub pid: u32,
+    pub process_name: String,
+    /// Full path of the executable, empty when the OS doesn't tell.
+    pub exe_path: String,
+    /// Name the app gave its session, often empty.
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDevice {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    DefaultOutput,
+    Capture(String),
+}
+
+/// The operations the volume engine needs from the OS mixer.
+pub trait AudioBackend {
+    fn list_sessions(&mut self) -> Result<Vec<AudioSession>>;
+    fn session_volume(&mut self, session: &AudioSession) -> Result<f32>;
+    fn set_session_volume(&mut self, session: &AudioSession, volume: f32) -> Result<()>;
+    fn endpoint_volume(&mut self, endpoint: &Endpoint) -> Result<f32>;
+    fn set_endpoint_volume(&mut self, endpoint: &Endpoint, volume: f32) -> Result<()>;
+    fn list_devices(&mut self, flow: DataFlow) -> Result<Vec<AudioDevice>>;
+    fn set_default_device(&mut self, flow: DataFlow, device: &AudioDevice) -> Result<()>;
+    fn session_muted(&mut self, session: &AudioSession) -> Result<bool>;
+    fn set_session_mute(&mut self, session: &AudioSession, muted: bool) -> Result<()>;
+    fn endpoint_muted(&mut self, endpoint: &Endpoint) -> Result<bool>;
+    fn set_endpoint_mute(&mut self, endpoint: &Endpoint, muted: bool) -> Result<()>;
+
+    /// Drops any cached lookups (pid names, endpoint handles, stream lists). Called every few
+    /// seconds by the engine.
+    fn invalidate_caches(&mut self) {}
+}
+
+/// Picks the native backend for this platform. `RVCI_AUDIO_BACKEND=mock` forces the in-memory one.
+pub fn create_backend() -> Box<dyn AudioBackend> {
+    if std::env::var("RVCI_AUDIO_BACKEND").map(|v| v == "mock").unwrap_or(false) {
+        // A few made-up apps and devices so the settings window has something to offer
+        return Box::new(MockBackend::new()
+            .with_session(1, "browser")
+            .with_session(2, "player")
+            .with_device(DataFlow::Render, "Mock Speakers")
+            .with_device(DataFlow::Render, "Mock Headphones")
+            .with_device(DataFlow::Capture, "Mock Microphone"));
+    }
+    native_backend()
+}
+
+#[cfg(windows)]
+fn native_backend() -> Box<dyn AudioBackend> { Box::new(WasapiBackend::new()) }
+
+#[cfg(target_os = "linux")]
+fn native_backend() -> Box<dyn AudioBackend> { Box::new(PulseBackend::new()) }
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn native_backend() -> Box<dyn AudioBackend> {
+    println!("DEBUG: No native audio backend for this platform, using mock backend.");
+    Box::new(MockBackend::new())
+}
+
+pub fn clean_process_name(name: &str) -> String {
+    let mut clean = name.to_string();
+    if clean.to_lowercase().ends_with(".exe") {
+        clean.truncate(clean.len() - 4);
+    }
+    clean
+}
+
+pub fn session_names(backend: &mut dyn AudioBackend) -> Vec<String> {
+    let names: HashSet<String> = backend.list_sessions().unwrap_or_default()
+        .into_iter()
+        .map(|s| s.process_name)
+        .filter(|n| !n.is_empty())
+        .collect();
+    let mut list: Vec<String> = names.into_iter().collect();
+    list.sort();
+    list
+}
+
+pub fn device_names(backend: &mut dyn AudioBackend, flow: DataFlow) -> Vec<String> {
+    let mut devices = backend.list_devices(flow).unwrap_or_default();
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    devices.into_iter().map(|d| d.name).collect()
+}
@@ -0,0 +1,258 @@
+use super::{clean_process_name, AudioBackend, AudioDevice, AudioSession, DataFlow, Endpoint};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::process::Command;
+
+/// PA_VOLUME_NORM, i.e. 100% on a Pulse channel volume.
+const PA_VOLUME_NORM: f32 = 65536.0;
+
+/// A stream as `pactl list sink-inputs` last reported it.
+struct SinkInput {
+    session: AudioSession,
+    volume: f32,
+    muted: bool,
+}
+
+/// PulseAudio / PipeWire (via pipewire-pulse) backend. Talks to the daemon through `pactl`, so it
+/// works the same on both servers and needs nothing beyond the client tools. `--format=json`
+/// needs pactl 16 or newer.
+///
+/// Mapping: "system" is `@DEFAULT_SINK@`, "process" dials match sink-inputs on
+/// `application.process.binary`, "microphone" dials are sources, and switching a device sets the
+/// default sink or source.
+///
+/// Every `pactl` run is a process spawn, so the streams, sources and endpoint volumes are read
+/// once and kept until `invalidate_caches`, with our own changes written into them. A command
+/// that fails drops them right away, since that usually means a stream or device went away.
+pub struct PulseBackend {
+    sink_inputs: Option<Vec<SinkInput>>,
+    sources: Option<Vec<AudioDevice>>,
+    endpoint_volumes: Vec<(Endpoint, f32)>,
+}
+
+impl PulseBackend {
+    pub fn new() -> Self { Self { sink_inputs: None, sources: None, endpoint_volumes: Vec::new() } }
+
+    fn pactl(args: &[&str]) -> Result<String> {
+        // Error messages and the odd plain-text reply are translated otherwise
+        let output = Command::new("pactl")
+            .env("LC_ALL", "C")
+            .args(args)
+            .output()
+            .context("Failed to run pactl")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "pactl {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn pactl_list(kind: &str) -> Result<Vec<Value>> {
+        let out = Self::pactl(&["--format=json", "list", kind])?;
+        let parsed: Value = serde_json::from_str(&out).context("pactl returned invalid JSON")?;
+        Ok(parsed.as_array().cloned().unwrap_or_default())
+    }
+
+    fn raw_volume(volume: f32) -> String {
+        ((volume.clamp(0.0, 1.0) * PA_VOLUME_NORM).round() as u32).to_string()
+    }
+
+    fn property<'v>(entry: &'v Value, key: &str) -> Option<&'v str> {
+        entry.get("properties").and_then(|p| p.get(key)).and_then(|v| v.as_str())
+    }
+
+    fn devices(kind: &str) -> Result<Vec<AudioDevice>> {
+        let mut devices = Vec::new();
+        for entry in Self::pactl_list(kind)? {
+            let name = entry.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+            let description = entry.get("description").and_then(|v| v.as_str()).unwrap_or(name);
+            // Monitor sources mirror a sink's output; they are not microphones.
+            if name.is_empty() || name.ends_with(".monitor") || Self::property(&entry, "device.class") == Some("monitor") {
+                continue;
+            }
+            devices.push(AudioDevice { name: description.to_string(), id: name.to_string() });
+        }
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+
+    fn source_id(&mut self, mic_name: &str) -> Result<String> {
+        if self.sources.is_none() { self.sources = Some(Self::devices("sources")?); }
+        self.sources.iter().flatten()
+            .find(|d| d.name.to_lowercase() == mic_name.to_lowercase() || d.id == mic_name)
+            .map(|d| d.id.clone())
+            .ok_or_else(|| anyhow::anyhow!("Microphone not found"))
+    }
+
+    fn sink_inputs(&mut self) -> Result<&mut Vec<SinkInput>> {
+        let inputs = match self.sink_inputs.take() {
+            Some(inputs) => inputs,
+            None => Self::load_sink_inputs()?,
+        };
+        Ok(self.sink_inputs.insert(inputs))
+    }
+
+    fn load_sink_inputs() -> Result<Vec<SinkInput>> {
+        let mut inputs = Vec::new();
+        for entry in Self::pactl_list("sink-inputs")? {
+            let index = match entry.get("index").and_then(|v| v.as_u64()) {
+                Some(i) => i,
+                None => continue,
+            };
+            let pid = Self::property(&entry, "application.process.id")
+                .and_then(|p| p.parse::<u32>().ok())
+                .unwrap_or(0);
+            let binary = Self::property(&entry, "application.process.binary").unwrap_or_default();
+            let exe_path = std::fs::read_link(format!("/proc/{}/exe", pid))
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let display_name = Self::property(&entry, "application.name").unwrap_or_default().to_string();
+            inputs.push(SinkInput {
+                session: AudioSession { id: index, pid, process_name: clean_process_name(binary), exe_path, display_name },
+                volume: Self::entry_volume(&entry).unwrap_or(1.0),
+                muted: entry.get("mute").and_then(|v| v.as_bool()).unwrap_or(false),
+            });
+        }
+        Ok(inputs)
+    }
+
+    fn sink_input(&mut self, session: &AudioSession) -> Result<&mut SinkInput> {
+        self.sink_inputs()?
+            .iter_mut()
+            .find(|input| input.session.id == session.id)
+            .ok_or_else(|| anyhow::anyhow!("Sink input {} is gone", session.id))
+    }
+
+    /// Runs a `pactl` command that changes something, dropping the caches if it fails.
+    fn change(&mut self, args: &[&str]) -> Result<()> {
+        if let Err(e) = Self::pactl(args) {
+            self.invalidate_caches();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Average of the channel volumes in a `pactl --format=json list` entry.
+    fn entry_volume(entry: &Value) -> Result<f32> {
+        let channels: Vec<f32> = entry.get("volume").and_then(|v| v.as_object())
+            .map(|chans| chans.values().filter_map(|c| c.get("value").and_then(|v| v.as_u64())).map(|v| v as f32 / PA_VOLUME_NORM).collect())
+            .unwrap_or_default();
+        if channels.is_empty() { return Err(anyhow::anyhow!("pactl reported no volume")); }
+        Ok(channels.iter().sum::<f32>() / channels.len() as f32)
+    }
+
+    fn find_entry(kind: &str, name: &str) -> Result<Value> {
+        Self::pactl_list(kind)?
+            .into_iter()
+            .find(|e| e.get("name").and_then(|v| v.as_str()) == Some(name))
+            .ok_or_else(|| anyhow::anyhow!("{} is gone", name))
+    }
+
+    /// The `list sinks` or `list sources` entry of the default sink or a microphone.
+    fn endpoint_entry(&mut self, endpoint: &Endpoint) -> Result<Value> {
+        match endpoint {
+            Endpoint::DefaultOutput => Self::find_entry("sinks", Self::pactl(&["get-default-sink"])?.trim()),
+            Endpoint::Capture(mic_name) => {
+                let source = self.source_id(mic_name)?;
+                Self::find_entry("sources", &source)
+            }
+        }
+    }
+}
+
+impl AudioBackend for PulseBackend {
+    fn list_sessions(&mut self) -> Result<Vec<AudioSession>> {
+        Ok(self.sink_inputs()?.iter().map(|input| input.session.clone()).collect())
+    }
+
+    fn session_volume(&mut self, session: &AudioSession) -> Result<f32> {
+        Ok(self.sink_input(session)?.volume)
+    }
+
+    fn set_session_volume(&mut self, session: &AudioSession, volume: f32) -> Result<()> {
+        self.change(&["set-sink-input-volume", &session.id.to_string(), &Self::raw_volume(volume)])?;
+        if let Ok(input) = self.sink_input(session) { input.volume = volume.clamp(0.0, 1.0); }
+        Ok(())
+    }
+
+    fn endpoint_volume(&mut self, endpoint: &Endpoint) -> Result<f32> {
+        if let Some((_, volume)) = self.endpoint_volumes.iter().find(|(e, _)| e == endpoint) {
+            return Ok(*volume);
+        }
+        let volume = Self::entry_volume(&self.endpoint_entry(endpoint)?)?;
+        self.endpoint_volumes.push((endpoint.clone(), volume));
+        Ok(volume)
+    }
+
+    fn set_endpoint_volume(&mut self, endpoint: &Endpoint, volume: f32) -> Result<()> {
+        match endpoint {
+            Endpoint::DefaultOutput => {
+                self.change(&["set-sink-volume", "@DEFAULT_SINK@", &Self::raw_volume(volume)])?;
+            }
+            Endpoint::Capture(mic_name) => {
+                let source = self.source_id(mic_name)?;
+                self.change(&["set-source-volume", &source, &Self::raw_volume(volume)])?;
+            }
+        }
+        self.endpoint_volumes.retain(|(e, _)| e != endpoint);
+        self.endpoint_volumes.push((endpoint.clone(), volume.clamp(0.0, 1.0)));
+        Ok(())
+    }
+
+    fn list_devices(&mut self, flow: DataFlow) -> Result<Vec<AudioDevice>> {
+        match flow {
+            DataFlow::Render => Self::devices("sinks"),
+            DataFlow::Capture => {
+                let sources = Self::devices("sources")?;
+                self.sources = Some(sources.clone());
+                Ok(sources)
+            }
+        }
+    }
+
+    fn set_default_device(&mut self, flow: DataFlow, device: &AudioDevice) -> Result<()> {
+        match flow {
+            DataFlow::Render => self.change(&["set-default-sink", &device.id])?,
+            DataFlow::Capture => self.change(&["set-default-source", &device.id])?,
+        };
+        // "System" is a different sink now
+        self.endpoint_volumes.retain(|(e, _)| *e != Endpoint::DefaultOutput);
+        Ok(())
+    }
+
+    fn session_muted(&mut self, session: &AudioSession) -> Result<bool> {
+        Ok(self.sink_input(session)?.muted)
+    }
+
+    fn set_session_mute(&mut self, session: &AudioSession, muted: bool) -> Result<()> {
+        self.change(&["set-sink-input-mute", &session.id.to_string(), if muted { "1" } else { "0" }])?;
+        if let Ok(input) = self.sink_input(session) { input.muted = muted; }
+        Ok(())
+    }
+
+    fn endpoint_muted(&mut self, endpoint: &Endpoint) -> Result<bool> {
+        let entry = self.endpoint_entry(endpoint)?;
+        entry.get("mute").and_then(|v| v.as_bool()).ok_or_else(|| anyhow::anyhow!("pactl reported no mute state"))
+    }
+
+    fn set_endpoint_mute(&mut self, endpoint: &Endpoint, muted: bool) -> Result<()> {
+        let flag = if muted { "1" } else { "0" };
+        match endpoint {
+            Endpoint::DefaultOutput => self.change(&["set-sink-mute", "@DEFAULT_SINK@", flag])?,
+            Endpoint::Capture(mic_name) => {
+                let source = self.source_id(mic_name)?;
+                self.change(&["set-source-mute", &source, flag])?
+            }
+        };
+        Ok(())
+    }
+
+    fn invalidate_caches(&mut self) {
+        self.sink_inputs = None;
+        self.sources = None;
+        self.endpoint_volumes.clear();
+    }
+}
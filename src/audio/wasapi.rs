@@ -0,0 +1,276 @@
+use super::{AudioBackend, AudioDevice, AudioSession, DataFlow, Endpoint};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use windows::core::{Interface, interface, GUID, PCWSTR, IUnknown, IUnknown_Vtbl};
+use windows::Win32::Foundation::CloseHandle;
+use windows::Win32::Media::Audio::Endpoints::IAudioEndpointVolume;
+use windows::Win32::Media::Audio::*;
+use windows::Win32::System::Com::*;
+use windows::Win32::System::ProcessStatus::{GetModuleBaseNameW, GetModuleFileNameExW};
+use windows::Win32::System::Threading::{
+    OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ,
+};
+use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+
+const CLSID_PolicyConfigClient: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
+
+#[interface("f8679f50-850a-41cf-9c72-430f290290c8")]
+pub unsafe trait IPolicyConfig: IUnknown {
+    fn GetMixFormat(&self, pszdeviceid: PCWSTR, ppformat: *mut *mut c_void) -> windows::core::HRESULT;
+    fn GetDeviceFormat(&self, pszdeviceid: PCWSTR, bdefault: i32, ppformat: *mut *mut c_void) -> windows::core::HRESULT;
+    fn ResetDeviceFormat(&self, pszdeviceid: PCWSTR) -> windows::core::HRESULT;
+    fn SetDeviceFormat(&self, pszdeviceid: PCWSTR, pformat: *const c_void, pformatext: *const c_void) -> windows::core::HRESULT;
+    fn GetProcessingPeriod(&self, pszdeviceid: PCWSTR, bdefault: i32, pdefaultperiod: *mut i64, pminimumperiod: *mut i64) -> windows::core::HRESULT;
+    fn SetProcessingPeriod(&self, pszdeviceid: PCWSTR, pdefaultperiod: *const i64) -> windows::core::HRESULT;
+    fn GetShareMode(&self, pszdeviceid: PCWSTR, pmode: *mut i32) -> windows::core::HRESULT;
+    fn SetShareMode(&self, pszdeviceid: PCWSTR, mode: i32) -> windows::core::HRESULT;
+    fn GetPropertyValue(&self, pszdeviceid: PCWSTR, bfxenable: i32, pkey: *const c_void, pv: *mut c_void) -> windows::core::HRESULT;
+    fn SetPropertyValue(&self, pszdeviceid: PCWSTR, bfxenable: i32, pkey: *const c_void, pv: *const c_void) -> windows::core::HRESULT;
+    fn SetDefaultEndpoint(&self, pszdeviceid: PCWSTR, role: ERole) -> windows::core::Result<()>;
+    fn SetEndpointVisibility(&self, pszdeviceid: PCWSTR, bvisible: i32) -> windows::core::HRESULT;
+}
+
+struct AudioController;
+impl AudioController {
+    unsafe fn get_system_volume() -> Result<IAudioEndpointVolume> {
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device: IMMDevice = enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)?;
+        Ok(device.Activate(CLSCTX_ALL, None)?)
+    }
+
+    unsafe fn get_session_manager() -> Result<IAudioSessionManager2> {
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device: IMMDevice = enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)?;
+        Ok(device.Activate(CLSCTX_ALL, None)?)
+    }
+
+    unsafe fn get_mic_volume(mic_name: &str) -> Result<IAudioEndpointVolume> {
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let collection = enumerator.EnumAudioEndpoints(eCapture, DEVICE_STATE_ACTIVE)?;
+        let count = collection.GetCount()?;
+        for i in 0..count {
+            if let Ok(item) = collection.Item(i) {
+                if let Ok(store) = item.OpenPropertyStore(STGM_READ) {
+                    if let Ok(prop) = store.GetValue(&PKEY_Device_FriendlyName) {
+                        let pwsz = prop.Anonymous.Anonymous.Anonymous.pwszVal;
+                        if !pwsz.is_null() {
+                            let name = pwsz.to_string().unwrap_or_default();
+                            if name.to_lowercase() == mic_name.to_lowercase() {
+                                return item.Activate::<IAudioEndpointVolume>(CLSCTX_ALL, None).map_err(anyhow::Error::from);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Err(anyhow::anyhow!("Microphone not found"))
+    }
+
+    /// The process's executable name without ".exe" and its full path.
+    fn get_process_name(pid: u32) -> (String, String) {
+        unsafe {
+            if let Ok(handle) = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid) {
+                let mut buffer = [0u16; 1024];
+                let len = GetModuleBaseNameW(handle, None, &mut buffer);
+                let mut path_buffer = [0u16; 1024];
+                let path_len = GetModuleFileNameExW(Some(handle), None, &mut path_buffer);
+                let _ = CloseHandle(handle);
+                if len > 0 {
+                    let mut name = String::from_utf16_lossy(&buffer[..len as usize]).to_string();
+                    if name.to_lowercase().ends_with(".exe") {
+                        name.truncate(name.len() - 4);
+                    }
+                    return (name, String::from_utf16_lossy(&path_buffer[..path_len as usize]));
+                }
+            }
+        }
+        (String::new(), String::new())
+    }
+
+    unsafe fn session_display_name(sess: &IAudioSessionControl) -> String {
+        match sess.GetDisplayName() {
+            Ok(name) if !name.is_null() => {
+                let text = name.to_string().unwrap_or_default();
+                CoTaskMemFree(Some(name.0 as *const c_void));
+                text
+            }
+            _ => String::new(),
+        }
+    }
+
+    unsafe fn get_devices_with_ids(data_flow: EDataFlow) -> Vec<AudioDevice> {
+        let mut devices = Vec::new();
+        let enumerator: Result<IMMDeviceEnumerator, _> = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL);
+        if let Ok(enumerator) = enumerator {
+            if let Ok(collection) = enumerator.EnumAudioEndpoints(data_flow, DEVICE_STATE_ACTIVE) {
+                if let Ok(count) = collection.GetCount() {
+                    for i in 0..count {
+                        if let Ok(item) = collection.Item(i) {
+                            let mut id_string = String::new();
+                            if let Ok(id_pwstr) = item.GetId() {
+                                id_string = id_pwstr.to_string().unwrap_or_default();
+                            }
+                            let mut name_string = String::new();
+                            if let Ok(store) = item.OpenPropertyStore(STGM_READ) {
+                                if let Ok(prop) = store.GetValue(&PKEY_Device_FriendlyName) {
+                                    let pwsz = prop.Anonymous.Anonymous.Anonymous.pwszVal;
+                                    if !pwsz.is_null() {
+                                        name_string = pwsz.to_string().unwrap_or_default();
+                                    }
+                                }
+                            }
+                            if !name_string.is_empty() && !id_string.is_empty() {
+                                devices.push(AudioDevice { name: name_string, id: id_string });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        devices
+    }
+}
+
+/// WASAPI sessions and endpoints, plus the undocumented `IPolicyConfig` for default device switching.
+pub struct WasapiBackend {
+    pid_name_cache: HashMap<u32, (String, String)>,
+    mic_device_cache: HashMap<String, IAudioEndpointVolume>,
+    session_volumes: Vec<ISimpleAudioVolume>,
+}
+
+impl WasapiBackend {
+    pub fn new() -> Self {
+        unsafe { let _ = CoInitializeEx(None, COINIT_MULTITHREADED); }
+        Self {
+            pid_name_cache: HashMap::new(),
+            mic_device_cache: HashMap::new(),
+            session_volumes: Vec::new(),
+        }
+    }
+
+    fn endpoint_control(&mut self, endpoint: &Endpoint) -> Result<IAudioEndpointVolume> {
+        unsafe {
+            Ok(match endpoint {
+                Endpoint::DefaultOutput => AudioController::get_system_volume()?,
+                Endpoint::Capture(name) => match self.mic_device_cache.get(name) {
+                    Some(v) => v.clone(),
+                    None => {
+                        let v = AudioController::get_mic_volume(name)?;
+                        self.mic_device_cache.insert(name.clone(), v.clone());
+                        v
+                    }
+                },
+            })
+        }
+    }
+
+    fn session_control(&self, session: &AudioSession) -> Result<&ISimpleAudioVolume> {
+        self.session_volumes.get(session.id as usize)
+            .ok_or_else(|| anyhow::anyhow!("Stale audio session {}", session.id))
+    }
+}
+
+impl AudioBackend for WasapiBackend {
+    fn list_sessions(&mut self) -> Result<Vec<AudioSession>> {
+        self.session_volumes.clear();
+        let mut sessions = Vec::new();
+        unsafe {
+            let mgr = AudioController::get_session_manager()?;
+            let enum_sess = mgr.GetSessionEnumerator()?;
+            let count = enum_sess.GetCount()?;
+            for s_idx in 0..count {
+                if let Ok(sess) = enum_sess.GetSession(s_idx) {
+                    if let Ok(s2) = Interface::cast::<IAudioSessionControl2>(&sess) {
+                        if let Ok(pid) = s2.GetProcessId() {
+                            if pid == 0 { continue; }
+                            if let Ok(simple_vol) = Interface::cast::<ISimpleAudioVolume>(&sess) {
+                                let (process_name, exe_path) = self.pid_name_cache.entry(pid).or_insert_with(|| {
+                                    AudioController::get_process_name(pid)
+                                }).clone();
+                                let display_name = AudioController::session_display_name(&sess);
+                                sessions.push(AudioSession { id: self.session_volumes.len() as u64, pid, process_name, exe_path, display_name });
+                                self.session_volumes.push(simple_vol);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn session_volume(&mut self, session: &AudioSession) -> Result<f32> {
+        let simple_vol = self.session_control(session)?;
+        Ok(unsafe { simple_vol.GetMasterVolume()? })
+    }
+
+    fn set_session_volume(&mut self, session: &AudioSession, volume: f32) -> Result<()> {
+        let simple_vol = self.session_control(session)?;
+        unsafe { simple_vol.SetMasterVolume(volume, std::ptr::null())?; }
+        Ok(())
+    }
+
+    fn endpoint_volume(&mut self, endpoint: &Endpoint) -> Result<f32> {
+        let vol = self.endpoint_control(endpoint)?;
+        Ok(unsafe { vol.GetMasterVolumeLevelScalar()? })
+    }
+
+    fn set_endpoint_volume(&mut self, endpoint: &Endpoint, volume: f32) -> Result<()> {
+        let vol = self.endpoint_control(endpoint)?;
+        unsafe { vol.SetMasterVolumeLevelScalar(volume, std::ptr::null())?; }
+        Ok(())
+    }
+
+    fn list_devices(&mut self, flow: DataFlow) -> Result<Vec<AudioDevice>> {
+        let data_flow = match flow {
+            DataFlow::Render => eRender,
+            DataFlow::Capture => eCapture,
+        };
+        Ok(unsafe { AudioController::get_devices_with_ids(data_flow) })
+    }
+
+    fn set_default_device(&mut self, _flow: DataFlow, device: &AudioDevice) -> Result<()> {
+        unsafe {
+            let policy = CoCreateInstance::<_, IPolicyConfig>(&CLSID_PolicyConfigClient, None, CLSCTX_ALL)
+                .map_err(|e| anyhow::anyhow!("Failed to instantiate IPolicyConfig COM object: {}", e))?;
+            let mut id_utf16: Vec<u16> = device.id.encode_utf16().collect();
+            id_utf16.push(0);
+            let pcwstr_id = PCWSTR(id_utf16.as_ptr());
+
+            let _ = policy.SetDefaultEndpoint(pcwstr_id, eConsole);
+            let _ = policy.SetDefaultEndpoint(pcwstr_id, eMultimedia);
+            let _ = policy.SetDefaultEndpoint(pcwstr_id, eCommunications);
+        }
+        Ok(())
+    }
+
+    fn session_muted(&mut self, session: &AudioSession) -> Result<bool> {
+        let simple_vol = self.session_control(session)?;
+        Ok(unsafe { simple_vol.GetMute()?.as_bool() })
+    }
+
+    fn set_session_mute(&mut self, session: &AudioSession, muted: bool) -> Result<()> {
+        let simple_vol = self.session_control(session)?;
+        unsafe { simple_vol.SetMute(muted, std::ptr::null())?; }
+        Ok(())
+    }
+
+    fn endpoint_muted(&mut self, endpoint: &Endpoint) -> Result<bool> {
+        let vol = self.endpoint_control(endpoint)?;
+        Ok(unsafe { vol.GetMute()?.as_bool() })
+    }
+
+    fn set_endpoint_mute(&mut self, endpoint: &Endpoint, muted: bool) -> Result<()> {
+        let vol = self.endpoint_control(endpoint)?;
+        unsafe { vol.SetMute(muted, std::ptr::null())?; }
+        Ok(())
+    }
+
+    fn invalidate_caches(&mut self) {
+        self.pid_name_cache.clear();
+        self.mic_device_cache.clear();
+    }
+}
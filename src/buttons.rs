@@ -0,0 +1,106 @@
+use crate::config::ButtonConfig;
+use std::time::{Duration, Instant};
+
+/// Held at least this long, a press becomes a long press.
+pub const LONG_PRESS: Duration = Duration::from_millis(600);
+
+/// A second press starting within this long after the first one was released is a double press.
+pub const DOUBLE_PRESS_GAP: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    Press,
+    LongPress,
+    DoublePress,
+}
+
+impl Gesture {
+    pub fn label(self) -> &'static str {
+        match self {
+            Gesture::Press => "press",
+            Gesture::LongPress => "long press",
+            Gesture::DoublePress => "double press",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ButtonState {
+    /// When the button went down, while it is held.
+    down_since: Option<Instant>,
+    /// The long press already fired for the current hold.
+    long_fired: bool,
+    /// The current hold is the second half of a double press.
+    second_press: bool,
+    /// A short press was released and may still turn into a double press.
+    released_at: Option<Instant>,
+}
+
+/// Turns raw button down/up events into gestures. Only waits for a second press on buttons that
+/// have double press actions, and only waits for a long press on buttons that have long press
+/// actions, so plain buttons fire on release without delay.
+#[derive(Debug, Default)]
+pub struct GestureDetector {
+    states: Vec<ButtonState>,
+}
+
+impl GestureDetector {
+    pub fn new() -> Self { Self::default() }
+
+    /// Forgets held and half-finished presses, e.g. after reconnecting.
+    pub fn reset(&mut self) { self.states.clear(); }
+
+    fn state(&mut self, index: usize) -> &mut ButtonState {
+        if index >= self.states.len() { self.states.resize_with(index + 1, ButtonState::default); }
+        &mut self.states[index]
+    }
+
+    /// `index` is 0-based. Returns the gesture this completes, if any.
+    pub fn button(&mut self, index: usize, pressed: bool, config: &ButtonConfig, now: Instant) -> Option<Gesture> {
+        let state = self.state(index);
+        if pressed {
+            if state.down_since.is_some() { return None; } // Repeated DOWN without an UP
+            let pending = state.released_at.take();
+            state.second_press = pending.is_some_and(|t| now.duration_since(t) <= DOUBLE_PRESS_GAP);
+            state.down_since = Some(now);
+            state.long_fired = false;
+            // Too late for a double press, but `poll` hasn't fired the first one as a press yet
+            return if pending.is_some() && !state.second_press { Some(Gesture::Press) } else { None };
+        }
+
+        state.down_since.take()?;
+        if state.long_fired { return None; }
+        if state.second_press {
+            state.second_press = false;
+            return Some(Gesture::DoublePress);
+        }
+        if config.double_press.is_empty() { return Some(Gesture::Press); }
+        state.released_at = Some(now);
+        None
+    }
+
+    /// Fires gestures that complete by time passing: long presses and single presses that are
+    /// past the double press window. Call this regularly, even when no lines arrive.
+    pub fn poll(&mut self, configs: &[ButtonConfig], now: Instant) -> Vec<(usize, Gesture)> {
+        let mut fired = Vec::new();
+        for (index, state) in self.states.iter_mut().enumerate() {
+            let config = match configs.get(index) {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some(released) = state.released_at {
+                if now.duration_since(released) > DOUBLE_PRESS_GAP {
+                    state.released_at = None;
+                    fired.push((index, Gesture::Press));
+                }
+            }
+            if let Some(down) = state.down_since {
+                if !state.long_fired && !state.second_press && !config.long_press.is_empty() && now.duration_since(down) >= LONG_PRESS {
+                    state.long_fired = true;
+                    fired.push((index, Gesture::LongPress));
+                }
+            }
+        }
+        fired
+    }
+}
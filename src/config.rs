@@ -0,0 +1,1068 @@
+use crate::protocol::PROTOCOL_NAMES;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Schema version written into `mapping.json`. Files without a `version` field are version 1.
+pub const CONFIG_VERSION: u32 = 6;
+
+/// Number of previous versions kept next to `mapping.json` as `mapping.json.bak.1` (newest) to `.bak.N`.
+pub const BACKUP_COUNT: usize = 5;
+
+/// `serial.port` value that makes RVCI probe every port for a controller.
+pub const AUTO_PORT: &str = "auto";
+
+fn default_true() -> bool { true }
+
+fn default_protocol() -> String { "deej".to_string() }
+
+/// Highest reading of the RVCI firmware's 10-bit pots.
+pub const DEFAULT_RAW_MAX: f32 = 1023.0;
+
+fn default_raw_max() -> f32 { DEFAULT_RAW_MAX }
+
+/// A measured range narrower than this is the jitter of a knob that wasn't turned.
+pub const MIN_CALIBRATED_SPAN: f32 = 32.0;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DialType {
+    #[default]
+    System,
+    Process,
+    AllOthers,
+    Microphone,
+    /// Fades from the `process_name` apps to the `fade_to` ones.
+    Crossfade,
+}
+
+impl DialType {
+    /// Same order as the type dropdown in the settings window.
+    pub const ALL: [DialType; 5] = [DialType::System, DialType::Process, DialType::AllOthers, DialType::Microphone, DialType::Crossfade];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DialType::System => "system",
+            DialType::Process => "process",
+            DialType::AllOthers => "all_others",
+            DialType::Microphone => "microphone",
+            DialType::Crossfade => "crossfade",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DialType::System => "System",
+            DialType::Process => "Process",
+            DialType::AllOthers => "Others",
+            DialType::Microphone => "Microphone",
+            DialType::Crossfade => "Crossfade",
+        }
+    }
+
+    pub fn index(self) -> i32 {
+        Self::ALL.iter().position(|t| *t == self).unwrap_or(0) as i32
+    }
+
+    pub fn from_index(idx: i32) -> Self {
+        Self::ALL.get(idx.max(0) as usize).copied().unwrap_or(DialType::System)
+    }
+
+    /// Whether the dial needs a `process_name` (a process or a capture device name).
+    pub fn has_target(self) -> bool {
+        matches!(self, DialType::Process | DialType::Microphone | DialType::Crossfade)
+    }
+
+    /// Whether the dial picks audio sessions by process name, going by its `match` rule.
+    pub fn matches_processes(self) -> bool {
+        matches!(self, DialType::Process | DialType::Crossfade)
+    }
+}
+
+impl fmt::Display for DialType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a crossfade knob splits the volume between its two sides.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeLaw {
+    /// The volumes add up to 100%, so the middle is quieter.
+    #[default]
+    Linear,
+    /// The power adds up to 100%, so the mix stays as loud all the way (both at 71% in the middle).
+    EqualPower,
+}
+
+impl FadeLaw {
+    pub const ALL: [FadeLaw; 2] = [FadeLaw::Linear, FadeLaw::EqualPower];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FadeLaw::Linear => "Linear",
+            FadeLaw::EqualPower => "Equal power",
+        }
+    }
+
+    fn is_linear(&self) -> bool { *self == FadeLaw::Linear }
+
+    /// Volumes of side A and side B with the knob at `position` (0 is all A, 1 all B).
+    pub fn volumes(self, position: f32) -> (f32, f32) {
+        let position = position.clamp(0.0, 1.0);
+        match self {
+            FadeLaw::Linear => (1.0 - position, position),
+            FadeLaw::EqualPower => {
+                let angle = position * std::f32::consts::FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+        }
+    }
+
+    /// The position that gives side A volume `a` and side B volume `b`, by their balance, or by
+    /// the one side that is playing. `None` if neither is.
+    pub fn position_of(self, a: Option<f32>, b: Option<f32>) -> Option<f32> {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let position = match (self, a, b) {
+            (_, None, None) => return None,
+            (_, Some(a), Some(b)) if a + b <= 0.0 => 0.5,
+            (FadeLaw::Linear, Some(a), Some(b)) => b / (a + b),
+            (FadeLaw::EqualPower, Some(a), Some(b)) => b.atan2(a) / quarter,
+            (FadeLaw::Linear, Some(a), None) => 1.0 - a,
+            (FadeLaw::Linear, None, Some(b)) => b,
+            (FadeLaw::EqualPower, Some(a), None) => a.clamp(0.0, 1.0).acos() / quarter,
+            (FadeLaw::EqualPower, None, Some(b)) => b.clamp(0.0, 1.0).asin() / quarter,
+        };
+        Some(position.clamp(0.0, 1.0))
+    }
+}
+
+/// How a knob's process names are compared with the audio sessions.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchRule {
+    /// The executable's name without ".exe", ignoring case.
+    #[default]
+    Name,
+    /// The name with `*` and `?` wildcards, like "discord*".
+    Glob,
+    /// A regular expression found anywhere in the name, ignoring case.
+    Regex,
+    /// The executable's full path, wildcards allowed.
+    Path,
+    /// The name the app gives its audio session, wildcards allowed.
+    DisplayName,
+}
+
+impl MatchRule {
+    /// Same order as the dropdown in a knob's settings.
+    pub const ALL: [MatchRule; 5] = [MatchRule::Name, MatchRule::Glob, MatchRule::Regex, MatchRule::Path, MatchRule::DisplayName];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MatchRule::Name => "Name",
+            MatchRule::Glob => "Wildcards",
+            MatchRule::Regex => "Regex",
+            MatchRule::Path => "Full path",
+            MatchRule::DisplayName => "Display name",
+        }
+    }
+
+    fn is_name(&self) -> bool { *self == MatchRule::Name }
+}
+
+/// Identifies the controller by its USB serial adapter, so it is found again under a new port name.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SerialConfig {
+    pub port: String,
+    pub baud: u32,
+    pub timeout: u64,
+    /// Line format spoken by the controller firmware, see `protocol::PROTOCOL_NAMES`.
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    /// When set, takes precedence over `port`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb: Option<UsbId>,
+}
+
+/// Listen address suggested when switching a controller to a network transport.
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:5000";
+
+/// Where controller lines come from. The network transports make RVCI listen on `listen`
+/// (e.g. "0.0.0.0:5000") for a controller such as an ESP32; the line format is still
+/// `serial.protocol`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TransportConfig {
+    #[default]
+    Serial,
+    Tcp {
+        listen: String,
+        /// When set, a client has to send `AUTH <token>` before anything else.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    Udp {
+        listen: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    Websocket {
+        listen: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    /// A MIDI control surface. Knobs and buttons are bound to its controls with their `midi` field.
+    Midi {
+        /// Part of the MIDI input port name. Empty creates a virtual "RVCI" port instead (not on Windows).
+        #[serde(default)]
+        port: String,
+    },
+}
+
+impl TransportConfig {
+    /// Names as shown in the settings window, in `TransportConfig::with_name` order.
+    pub const NAMES: [&'static str; 5] = ["Serial", "TCP", "UDP", "WebSocket", "MIDI"];
+
+    pub fn is_serial(&self) -> bool { *self == TransportConfig::Serial }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransportConfig::Serial => Self::NAMES[0],
+            TransportConfig::Tcp { .. } => Self::NAMES[1],
+            TransportConfig::Udp { .. } => Self::NAMES[2],
+            TransportConfig::Websocket { .. } => Self::NAMES[3],
+            TransportConfig::Midi { .. } => Self::NAMES[4],
+        }
+    }
+
+    /// Listen address and token of the network transports.
+    pub fn network(&self) -> Option<(&str, Option<&str>)> {
+        match self {
+            TransportConfig::Serial | TransportConfig::Midi { .. } => None,
+            TransportConfig::Tcp { listen, token } | TransportConfig::Udp { listen, token } | TransportConfig::Websocket { listen, token } => {
+                Some((listen, token.as_deref()))
+            }
+        }
+    }
+
+    /// The transport called `name`, keeping the listen address and token of a network one.
+    pub fn with_name(&self, name: &str) -> Self {
+        let (listen, token) = self.network()
+            .map(|(l, t)| (l.to_string(), t.map(str::to_string)))
+            .unwrap_or_else(|| (DEFAULT_LISTEN.to_string(), None));
+        match name {
+            "TCP" => TransportConfig::Tcp { listen, token },
+            "UDP" => TransportConfig::Udp { listen, token },
+            "WebSocket" => TransportConfig::Websocket { listen, token },
+            "MIDI" => match self {
+                TransportConfig::Midi { port } => TransportConfig::Midi { port: port.clone() },
+                _ => TransportConfig::Midi { port: String::new() },
+            },
+            _ => TransportConfig::Serial,
+        }
+    }
+
+    /// Same transport with a different listen address and token.
+    pub fn with_network(&self, listen: String, token: Option<String>) -> Self {
+        match self {
+            TransportConfig::Serial | TransportConfig::Midi { .. } => self.clone(),
+            TransportConfig::Tcp { .. } => TransportConfig::Tcp { listen, token },
+            TransportConfig::Udp { .. } => TransportConfig::Udp { listen, token },
+            TransportConfig::Websocket { .. } => TransportConfig::Websocket { listen, token },
+        }
+    }
+}
+
+fn default_encoder_step() -> f32 { 0.02 }
+fn default_encoder_acceleration() -> f32 { 2.0 }
+
+/// What kind of hardware a knob is.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DialInput {
+    /// A potentiometer, sending its absolute position in every frame.
+    #[default]
+    Pot,
+    /// An endless rotary encoder, sending steps that are added to the target's current volume.
+    Encoder {
+        /// Volume change per step when turning slowly.
+        #[serde(default = "default_encoder_step")]
+        step: f32,
+        /// How much bigger the steps get when turning fast. 0 turns acceleration off.
+        #[serde(default = "default_encoder_acceleration")]
+        acceleration: f32,
+    },
+}
+
+impl DialInput {
+    pub fn encoder() -> Self {
+        DialInput::Encoder { step: default_encoder_step(), acceleration: default_encoder_acceleration() }
+    }
+
+    pub fn is_encoder(self) -> bool { matches!(self, DialInput::Encoder { .. }) }
+
+    fn is_pot(&self) -> bool { *self == DialInput::Pot }
+}
+
+/// Smallest volume change a knob passes on, unless set per knob.
+pub const DEFAULT_DEADBAND: f32 = 0.005;
+
+fn default_deadband() -> f32 { DEFAULT_DEADBAND }
+
+fn is_default_deadband(deadband: &f32) -> bool { *deadband == DEFAULT_DEADBAND }
+
+fn is_zero(value: &f32) -> bool { *value == 0.0 }
+
+fn is_false(value: &bool) -> bool { !*value }
+
+fn default_one() -> f32 { 1.0 }
+
+fn is_one(value: &f32) -> bool { *value == 1.0 }
+
+/// How close a knob has to come to one of its `snap_to` volumes to land on it, unless set per knob.
+pub const DEFAULT_SNAP_ZONE: f32 = 0.02;
+
+fn default_snap_zone() -> f32 { DEFAULT_SNAP_ZONE }
+
+fn is_default_snap_zone(zone: &f32) -> bool { *zone == DEFAULT_SNAP_ZONE }
+
+/// How a pot's readings are smoothed before they set a volume. MIDI knobs are never filtered,
+/// they only send when they move.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DialFilter {
+    None,
+    /// Moves `factor` of the way to each new reading. Jumps of `jump` or more are taken at once.
+    Ema { factor: f32, jump: f32 },
+    /// Smooths hard while the knob rests and less the faster it turns. `min_cutoff` (Hz) is the
+    /// smoothing at rest, `beta` how quickly it lets go.
+    OneEuro { min_cutoff: f32, beta: f32 },
+    /// The middle of the last `samples` readings, which drops single spikes.
+    Median { samples: usize },
+}
+
+impl Default for DialFilter {
+    fn default() -> Self { DialFilter::Ema { factor: 0.35, jump: 0.08 } }
+}
+
+impl DialFilter {
+    /// Names as shown in the settings window, in `DialFilter::with_name` order.
+    pub const NAMES: [&'static str; 4] = ["EMA", "One-euro", "Median", "None"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DialFilter::Ema { .. } => Self::NAMES[0],
+            DialFilter::OneEuro { .. } => Self::NAMES[1],
+            DialFilter::Median { .. } => Self::NAMES[2],
+            DialFilter::None => Self::NAMES[3],
+        }
+    }
+
+    /// The filter called `name`, keeping this one's settings if it's the same kind.
+    pub fn with_name(&self, name: &str) -> Self {
+        if name == self.name() { return *self; }
+        match name {
+            "EMA" => DialFilter::default(),
+            "One-euro" => DialFilter::OneEuro { min_cutoff: 1.0, beta: 5.0 },
+            "Median" => DialFilter::Median { samples: 5 },
+            _ => DialFilter::None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            DialFilter::None => "No filter".to_string(),
+            DialFilter::Ema { factor, jump } => format!("EMA, factor {}, jumps from {}", factor, jump),
+            DialFilter::OneEuro { min_cutoff, beta } => format!("One-euro, min cutoff {} Hz, beta {}", min_cutoff, beta),
+            DialFilter::Median { samples } => format!("Median of {}", samples),
+        }
+    }
+
+    fn is_default(&self) -> bool { *self == DialFilter::default() }
+}
+
+/// Lowest volume of a dB curve unless set otherwise, in dB below full volume.
+pub const DEFAULT_MIN_DB: f32 = -60.0;
+
+/// How a knob's position (0 to 1) turns into a volume (0 to 1).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DialCurve {
+    #[default]
+    Linear,
+    /// The position to the power of `exponent`. 3 gives fine control over quiet volumes.
+    Power { exponent: f32 },
+    /// Evenly spaced in decibels from `min_db` (e.g. -60) to full volume. The bottom end is silent.
+    Db { min_db: f32 },
+    /// Straight lines between `[position, volume]` points, in order of position.
+    Points { points: Vec<[f32; 2]> },
+}
+
+impl DialCurve {
+    /// Names as shown in the settings window, in `DialCurve::with_name` order.
+    pub const NAMES: [&'static str; 4] = ["Linear", "Power", "dB", "Custom"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DialCurve::Linear => Self::NAMES[0],
+            DialCurve::Power { .. } => Self::NAMES[1],
+            DialCurve::Db { .. } => Self::NAMES[2],
+            DialCurve::Points { .. } => Self::NAMES[3],
+        }
+    }
+
+    /// The curve called `name`, keeping this one if it's the same kind. "Custom" starts out
+    /// with points along this curve.
+    pub fn with_name(&self, name: &str) -> Self {
+        if name == self.name() { return self.clone(); }
+        match name {
+            "Power" => DialCurve::Power { exponent: 3.0 },
+            "dB" => DialCurve::Db { min_db: DEFAULT_MIN_DB },
+            "Custom" => DialCurve::Points {
+                points: [0.0, 0.25, 0.5, 0.75, 1.0].iter().map(|p| [*p, (self.apply(*p) * 1000.0).round() / 1000.0]).collect(),
+            },
+            _ => DialCurve::Linear,
+        }
+    }
+
+    pub fn apply(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        let volume = match self {
+            DialCurve::Linear => position,
+            DialCurve::Power { exponent } => position.powf(exponent.max(0.01)),
+            DialCurve::Db { min_db } => {
+                if position <= 0.0 { 0.0 } else { 10f32.powf(min_db.min(-1.0) * (1.0 - position) / 20.0) }
+            }
+            DialCurve::Points { points } => match points.iter().position(|p| p[0] >= position) {
+                _ if points.is_empty() => position,
+                Some(0) => points[0][1],
+                Some(i) => {
+                    let ([x0, y0], [x1, y1]) = (points[i - 1], points[i]);
+                    if x1 - x0 <= f32::EPSILON { y1 } else { y0 + (y1 - y0) * (position - x0) / (x1 - x0) }
+                }
+                None => points[points.len() - 1][1],
+            },
+        };
+        volume.clamp(0.0, 1.0)
+    }
+
+    /// The position that gives `volume`, for encoders that turn from the current volume. Curves
+    /// are expected to rise, otherwise this is the first position that gets close.
+    pub fn position_of(&self, volume: f32) -> f32 {
+        if volume <= self.apply(0.0) { return 0.0; }
+        let (mut low, mut high) = (0.0f32, 1.0f32);
+        for _ in 0..24 {
+            let middle = (low + high) / 2.0;
+            if self.apply(middle) < volume { low = middle; } else { high = middle; }
+        }
+        high
+    }
+
+    fn is_linear(&self) -> bool { *self == DialCurve::Linear }
+}
+
+/// A MIDI control a knob or button is bound to. Channels are 1-16.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MidiControl {
+    Cc { channel: u8, number: u8 },
+    PitchBend { channel: u8 },
+}
+
+impl MidiControl {
+    pub fn label(self) -> String {
+        match self {
+            MidiControl::Cc { channel, number } => format!("CC {} ch {}", number, channel),
+            MidiControl::PitchBend { channel } => format!("Bend ch {}", channel),
+        }
+    }
+}
+
+/// Another process a `process` knob turns, at `gain` times the knob's volume.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProcessTarget {
+    pub process_name: String,
+    #[serde(default = "default_one", skip_serializing_if = "is_one")]
+    pub gain: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DialConfig {
+    #[serde(rename = "type")] pub dial_type: DialType,
+    pub process_name: Option<String>,
+    /// Processes turned along with `process_name`, e.g. a game together with its launcher.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<ProcessTarget>,
+    /// How `process_name`, `targets` and `fade_to` pick the sessions of a `process` or `crossfade` knob.
+    #[serde(rename = "match", default, skip_serializing_if = "MatchRule::is_name")]
+    pub match_rule: MatchRule,
+    /// Side B of a `crossfade` knob, `process_name` being side A.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_to: Option<String>,
+    #[serde(default, skip_serializing_if = "FadeLaw::is_linear")]
+    pub fade_law: FadeLaw,
+    #[serde(default)]
+    pub inverted: bool,
+    /// Readings the pot sends at its ends. Found with "Calibrate" in the settings window.
+    #[serde(default)]
+    pub raw_min: f32,
+    #[serde(default = "default_raw_max")]
+    pub raw_max: f32,
+    #[serde(default, skip_serializing_if = "DialCurve::is_linear")]
+    pub curve: DialCurve,
+    #[serde(default, skip_serializing_if = "DialFilter::is_default")]
+    pub filter: DialFilter,
+    /// Smallest volume change that is passed on.
+    #[serde(default = "default_deadband", skip_serializing_if = "is_default_deadband")]
+    pub deadband: f32,
+    /// Change needed to turn back the way the knob came, so a reading wobbling between two
+    /// values doesn't make the volume flicker.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hysteresis: f32,
+    /// Soft takeover: the knob is ignored until it reaches the volume its target is at, so it
+    /// doesn't yank a volume changed elsewhere to wherever the knob happens to be.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pickup: bool,
+    /// The knob's whole travel covers `min_output` to `max_output`, e.g. 0.05 so the master
+    /// never goes silent.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min_output: f32,
+    #[serde(default = "default_one", skip_serializing_if = "is_one")]
+    pub max_output: f32,
+    /// Volumes the knob lands on when it comes within `snap_zone` of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snap_to: Vec<f32>,
+    #[serde(default = "default_snap_zone", skip_serializing_if = "is_default_snap_zone")]
+    pub snap_zone: f32,
+    #[serde(default, skip_serializing_if = "DialInput::is_pot")]
+    pub input: DialInput,
+    /// MIDI control that moves this knob, for controllers connected over MIDI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub midi: Option<MidiControl>,
+}
+
+impl Default for DialConfig {
+    fn default() -> Self {
+        Self {
+            dial_type: DialType::default(),
+            process_name: None,
+            targets: vec![],
+            match_rule: MatchRule::Name,
+            fade_to: None,
+            fade_law: FadeLaw::Linear,
+            inverted: false,
+            raw_min: 0.0,
+            raw_max: DEFAULT_RAW_MAX,
+            curve: DialCurve::Linear,
+            filter: DialFilter::default(),
+            deadband: DEFAULT_DEADBAND,
+            hysteresis: 0.0,
+            pickup: false,
+            min_output: 0.0,
+            max_output: 1.0,
+            snap_to: vec![],
+            snap_zone: DEFAULT_SNAP_ZONE,
+            input: DialInput::default(),
+            midi: None,
+        }
+    }
+}
+
+impl DialConfig {
+    /// Where `raw` lies between `raw_min` and `raw_max`, from 0 to 1.
+    pub fn normalize(&self, raw: f32) -> f32 {
+        let span = self.raw_max - self.raw_min;
+        if span.abs() < f32::EPSILON { return 0.0; }
+        ((raw - self.raw_min) / span).clamp(0.0, 1.0)
+    }
+
+    /// Every process name with its gain, `process_name` first at full volume and a crossfade's
+    /// `fade_to` last. Unset ones are left out.
+    pub fn process_targets(&self) -> Vec<(&str, f32)> {
+        let fade_to = self.fade_to.as_deref().filter(|_| self.dial_type == DialType::Crossfade);
+        self.process_name.as_deref().map(|name| (name, 1.0))
+            .into_iter()
+            .chain(self.targets.iter().map(|t| (t.process_name.as_str(), t.gain)))
+            .chain(fade_to.map(|name| (name, 1.0)))
+            .filter(|(name, _)| !name.is_empty() && *name != "None")
+            .collect()
+    }
+
+    /// Fits a curve's volume (0 to 1) into `min_output` to `max_output`.
+    pub fn to_output(&self, volume: f32) -> f32 {
+        self.min_output + (self.max_output - self.min_output) * volume.clamp(0.0, 1.0)
+    }
+
+    /// The curve's volume that `to_output` turns into `output`.
+    pub fn output_position(&self, output: f32) -> f32 {
+        let span = self.max_output - self.min_output;
+        if span.abs() < f32::EPSILON { return 0.0; }
+        ((output - self.min_output) / span).clamp(0.0, 1.0)
+    }
+
+    /// Moves `output` onto the nearest `snap_to` volume within `snap_zone`. Snap volumes
+    /// outside the output range are ignored.
+    pub fn snap(&self, output: f32) -> f32 {
+        let (low, high) = (self.min_output.min(self.max_output), self.min_output.max(self.max_output));
+        self.snap_to.iter().copied()
+            .filter(|s| (low..=high).contains(s) && (s - output).abs() <= self.snap_zone)
+            .min_by(|a, b| (a - output).abs().total_cmp(&(b - output).abs()))
+            .unwrap_or(output)
+    }
+}
+
+/// Something a button gesture does. Written as `{"action": "toggle_mute", ...}` in mapping.json.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ButtonAction {
+    /// Mutes or unmutes a target picked the same way as a knob's, `match_rule` included.
+    ToggleMute {
+        #[serde(rename = "type")] target_type: DialType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        process_name: Option<String>,
+        #[serde(rename = "match", default, skip_serializing_if = "MatchRule::is_name")]
+        match_rule: MatchRule,
+    },
+    /// Makes the first playback device whose name contains `device` the default.
+    SwitchDevice { device: String },
+    /// Moves this controller's knobs on to the next entry of `profiles`.
+    CycleProfile,
+}
+
+/// Actions per gesture for one button. Empty lists do nothing.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ButtonConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub press: Vec<ButtonAction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub long_press: Vec<ButtonAction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub double_press: Vec<ButtonAction>,
+    /// MIDI control that acts as this button: 64 and up is down, below 64 is up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub midi: Option<MidiControl>,
+}
+
+/// Devices for one position of the selector switch.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct SelectorPosition {
+    /// Playback devices in order of preference. The first one that is connected becomes the default.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Recording device to make the default as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+}
+
+/// An alternative set of knob mappings, switched to with a `cycle_profile` button.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProfileConfig {
+    pub name: String,
+    pub dials: Vec<DialConfig>,
+}
+
+/// One physical controller box with its own connection and knobs.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ControllerConfig {
+    #[serde(default)]
+    pub name: String,
+    pub serial: SerialConfig,
+    #[serde(default, skip_serializing_if = "TransportConfig::is_serial")]
+    pub transport: TransportConfig,
+    pub dials: Vec<DialConfig>,
+    /// Button 1 is the first entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<ButtonConfig>,
+    /// Extra knob mappings after the default one in `dials`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ProfileConfig>,
+    /// Switch position 1 is the first entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selector: Vec<SelectorPosition>,
+}
+
+impl ControllerConfig {
+    /// Name shown in the settings window and the debug console.
+    pub fn label(&self, index: usize) -> String {
+        if self.name.trim().is_empty() { format!("Controller {}", index + 1) } else { self.name.clone() }
+    }
+
+    /// Whether going from `self` to `new` has to reopen the connection. Everything else, the read
+    /// timeout included, is applied to the open one.
+    pub fn needs_reconnect(&self, new: &ControllerConfig) -> bool {
+        self.serial.port != new.serial.port || self.serial.baud != new.serial.baud || self.serial.usb != new.serial.usb
+            || self.transport != new.transport
+    }
+
+    /// Writes ranges measured per knob, as (lowest, highest) reading, into the knobs of every
+    /// profile. Returns how many knobs changed.
+    pub fn apply_calibration(&mut self, ranges: &[Option<(f32, f32)>]) -> usize {
+        let mut calibrated = 0;
+        for (i, range) in ranges.iter().enumerate() {
+            if let Some((low, high)) = *range {
+                if high - low < MIN_CALIBRATED_SPAN { continue; }
+                for dial in self.dial_lists_mut().filter_map(|dials| dials.get_mut(i)) {
+                    dial.raw_min = low;
+                    dial.raw_max = high;
+                }
+                calibrated += 1;
+            }
+        }
+        calibrated
+    }
+
+    /// The default knob mappings and those of every profile.
+    pub fn dial_lists_mut(&mut self) -> impl Iterator<Item = &mut Vec<DialConfig>> {
+        std::iter::once(&mut self.dials).chain(self.profiles.iter_mut().map(|p| &mut p.dials))
+    }
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            serial: SerialConfig { port: AUTO_PORT.to_string(), baud: 115200, timeout: 50, protocol: default_protocol(), usb: None },
+            transport: TransportConfig::Serial,
+            dials: vec![],
+            buttons: vec![],
+            profiles: vec![],
+            selector: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AppConfig {
+    pub version: u32,
+    pub controllers: Vec<ControllerConfig>,
+    #[serde(default)]
+    pub debug_mode: bool,
+    #[serde(default = "default_true")]
+    pub enable_osd: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            controllers: vec![ControllerConfig::default()],
+            debug_mode: false,
+            enable_osd: true,
+        }
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("RVCI");
+    if !path.exists() { let _ = std::fs::create_dir_all(&path); }
+    path.join("mapping.json")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// No mapping.json yet, i.e. first launch.
+    Missing,
+    Io(String),
+    Invalid { message: String, line: Option<usize>, column: Option<usize> },
+}
+
+impl ConfigError {
+    fn invalid(message: impl Into<String>) -> Self {
+        ConfigError::Invalid { message: message.into(), line: None, column: None }
+    }
+
+    fn from_json(e: &serde_json::Error) -> Self {
+        // serde_json appends " at line X column Y", which we report separately
+        let full = e.to_string();
+        let message = match full.rfind(" at line ") {
+            Some(idx) => full[..idx].to_string(),
+            None => full,
+        };
+        if e.line() > 0 {
+            ConfigError::Invalid { message, line: Some(e.line()), column: Some(e.column()) }
+        } else {
+            ConfigError::invalid(message)
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Missing => f.write_str("mapping.json does not exist yet"),
+            ConfigError::Io(msg) => f.write_str(msg),
+            ConfigError::Invalid { message, line: Some(line), column: Some(column) } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            }
+            ConfigError::Invalid { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// v1 -> v2: dial types become a closed set. Normalizes case and the old deej-style aliases.
+fn migrate_v1_to_v2(root: &mut Value) -> Result<()> {
+    if let Some(dials) = root.get_mut("dials").and_then(|d| d.as_array_mut()) {
+        for dial in dials {
+            if let Some(t) = dial.get("type").and_then(|t| t.as_str()) {
+                let normalized = match t.trim().to_lowercase().as_str() {
+                    "master" => "system".to_string(),
+                    "mic" => "microphone".to_string(),
+                    "others" | "other" | "deej.unmapped" => "all_others".to_string(),
+                    other => other.to_string(),
+                };
+                dial["type"] = Value::String(normalized);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// v2 -> v3: `serial`, `value_max` and `dials` move into the first entry of `controllers`.
+fn migrate_v2_to_v3(root: &mut Value) -> Result<()> {
+    if let Some(obj) = root.as_object_mut() {
+        let mut controller = serde_json::Map::new();
+        for key in ["serial", "value_max", "dials"] {
+            if let Some(value) = obj.remove(key) { controller.insert(key.to_string(), value); }
+        }
+        obj.insert("controllers".to_string(), Value::Array(vec![Value::Object(controller)]));
+    }
+    Ok(())
+}
+
+/// v3 -> v4: `work_device_1` and `work_device_2` become positions 1 and 2 of the first
+/// controller's `selector`.
+fn migrate_v3_to_v4(root: &mut Value) -> Result<()> {
+    if let Some(obj) = root.as_object_mut() {
+        let mut positions = Vec::new();
+        for key in ["work_device_1", "work_device_2"] {
+            let device = obj.remove(key).and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+            let outputs: Vec<Value> = if device.is_empty() || device == "None" { vec![] } else { vec![Value::String(device)] };
+            positions.push(serde_json::json!({ "outputs": outputs }));
+        }
+        if positions.iter().all(|p| p["outputs"].as_array().is_some_and(|o| o.is_empty())) { return Ok(()); }
+        if let Some(first) = obj.get_mut("controllers").and_then(|c| c.as_array_mut()).and_then(|c| c.first_mut()) {
+            first["selector"] = Value::Array(positions);
+        }
+    }
+    Ok(())
+}
+
+/// v4 -> v5: each controller's `value_max` becomes the `raw_max` of all its knobs, profiles included.
+fn migrate_v4_to_v5(root: &mut Value) -> Result<()> {
+    let set_range = |dials: Option<&mut Value>, raw_max: &Value| {
+        for dial in dials.and_then(|d| d.as_array_mut()).into_iter().flatten().filter_map(|d| d.as_object_mut()) {
+            dial.insert("raw_min".to_string(), Value::from(0.0));
+            dial.insert("raw_max".to_string(), raw_max.clone());
+        }
+    };
+    let controllers = root.get_mut("controllers").and_then(|c| c.as_array_mut()).into_iter().flatten();
+    for controller in controllers.filter_map(|c| c.as_object_mut()) {
+        let raw_max = controller.remove("value_max").unwrap_or(Value::from(720.0));
+        set_range(controller.get_mut("dials"), &raw_max);
+        for profile in controller.get_mut("profiles").and_then(|p| p.as_array_mut()).into_iter().flatten() {
+            set_range(profile.get_mut("dials"), &raw_max);
+        }
+    }
+    Ok(())
+}
+
+/// v5 -> v6: `use_logarithmic_scale` becomes a power curve of 3 on every knob, profiles included.
+fn migrate_v5_to_v6(root: &mut Value) -> Result<()> {
+    let logarithmic = root.as_object_mut().and_then(|obj| obj.remove("use_logarithmic_scale")).and_then(|v| v.as_bool()).unwrap_or(false);
+    if !logarithmic { return Ok(()); }
+    let set_curve = |dials: Option<&mut Value>| {
+        for dial in dials.and_then(|d| d.as_array_mut()).into_iter().flatten().filter_map(|d| d.as_object_mut()) {
+            dial.insert("curve".to_string(), serde_json::json!({ "kind": "power", "exponent": 3.0 }));
+        }
+    };
+    let controllers = root.get_mut("controllers").and_then(|c| c.as_array_mut()).into_iter().flatten();
+    for controller in controllers {
+        set_curve(controller.get_mut("dials"));
+        for profile in controller.get_mut("profiles").and_then(|p| p.as_array_mut()).into_iter().flatten() {
+            set_curve(profile.get_mut("dials"));
+        }
+    }
+    Ok(())
+}
+
+const MIGRATIONS: &[fn(&mut Value) -> Result<()>] = &[migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4, migrate_v4_to_v5, migrate_v5_to_v6];
+
+/// Names unknown `type`s in knobs, profile knobs and button actions, which serde would only call
+/// an "unknown variant" without saying where.
+fn check_dial_types(root: &Value) -> Result<()> {
+    let controllers = root.get("controllers").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    let check = |place: String, t: &str| -> Result<()> {
+        if DialType::ALL.iter().any(|known| known.as_str() == t) { return Ok(()); }
+        let expected: Vec<&str> = DialType::ALL.iter().map(|k| k.as_str()).collect();
+        let mut place = place;
+        place[..1].make_ascii_uppercase();
+        Err(anyhow::anyhow!("{}: unknown type \"{}\" (expected one of: {})", place, t, expected.join(", ")))
+    };
+    let list = |value: &Value, key: &str| value.get(key).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    for (c, controller) in controllers.iter().enumerate() {
+        let prefix = if controllers.len() > 1 { format!("controller {}, ", c + 1) } else { String::new() };
+        let profiles = list(controller, "profiles");
+        let dial_lists = std::iter::once((prefix.clone(), list(controller, "dials")))
+            .chain(profiles.iter().enumerate().map(|(p, profile)| (format!("{}profile {}, ", prefix, p + 1), list(profile, "dials"))));
+        for (place, dials) in dial_lists {
+            for (i, dial) in dials.iter().enumerate() {
+                check(format!("{}knob {}", place, i + 1), dial.get("type").and_then(|t| t.as_str()).unwrap_or_default())?;
+            }
+        }
+        for (b, button) in list(controller, "buttons").iter().enumerate() {
+            for (key, gesture) in [("press", "press"), ("long_press", "long press"), ("double_press", "double press")] {
+                for (a, action) in list(button, key).iter().enumerate() {
+                    // Only mute actions have a type
+                    if let Some(t) = action.get("type") {
+                        check(format!("{}button {}, {} action {}", prefix, b + 1, gesture, a + 1), t.as_str().unwrap_or_default())?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses `mapping.json` contents, upgrading older schema versions in memory.
+/// Returns the config and the schema version the text was written in.
+pub fn parse_config(text: &str) -> Result<(AppConfig, u32), ConfigError> {
+    let mut root: Value = serde_json::from_str(text).map_err(|e| ConfigError::from_json(&e))?;
+    let version = root.get("version").and_then(|v| v.as_u64()).unwrap_or(1).max(1) as u32;
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::invalid(format!(
+            "mapping.json is version {}, but this build of RVCI only understands up to version {}",
+            version, CONFIG_VERSION
+        )));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(&mut root).map_err(|e| ConfigError::invalid(format!("Failed to migrate config from version {}: {}", from + 1, e)))?;
+    }
+    root["version"] = Value::from(CONFIG_VERSION);
+
+    // Current-version files are deserialized from the original text so errors keep their position
+    let parsed = if version == CONFIG_VERSION {
+        serde_json::from_str::<AppConfig>(text)
+    } else {
+        serde_json::from_value::<AppConfig>(root.clone())
+    };
+    match parsed {
+        Ok(config) => {
+            for controller in &config.controllers {
+                if !PROTOCOL_NAMES.contains(&controller.serial.protocol.as_str()) {
+                    return Err(ConfigError::invalid(format!(
+                        "serial.protocol: unknown protocol \"{}\" (expected one of: {})",
+                        controller.serial.protocol, PROTOCOL_NAMES.join(", ")
+                    )));
+                }
+            }
+            Ok((config, version))
+        }
+        Err(e) => {
+            let mut err = ConfigError::from_json(&e);
+            if let (Err(type_err), ConfigError::Invalid { message, .. }) = (check_dial_types(&root), &mut err) {
+                *message = type_err.to_string();
+            }
+            Err(err)
+        }
+    }
+}
+
+pub fn load_config(path: &Path) -> Result<AppConfig, ConfigError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(ConfigError::Missing),
+        Err(e) => return Err(ConfigError::Io(e.to_string())),
+    };
+    parse_config(&text).map(|(config, _)| config)
+}
+
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("json.bak.{}", n))
+}
+
+fn rotate_backups(path: &Path) -> Result<()> {
+    if !path.exists() { return Ok(()); }
+    let _ = std::fs::remove_file(backup_path(path, BACKUP_COUNT));
+    for n in (1..BACKUP_COUNT).rev() {
+        let from = backup_path(path, n);
+        if from.exists() { std::fs::rename(&from, backup_path(path, n + 1))?; }
+    }
+    // Copy rather than rename so mapping.json never disappears, even for a moment
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Writes a temp file next to `path`, then renames it into place. Readers either see the old file
+/// or the complete new one, never a truncated write.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    {
+        let mut f = File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+    rotate_backups(path).context("Failed to rotate config backups")?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Refuses to replace a mapping.json that doesn't parse, since that's usually someone halfway
+/// through editing it by hand. `set_aside_broken_config` moves it out of the way first.
+pub fn save_config(path: &Path, config: &AppConfig) -> Result<()> {
+    if let Some(Err(e)) = std::fs::read_to_string(path).ok().map(|text| parse_config(&text)) {
+        return Err(anyhow::anyhow!("{} has an error ({}) and was left as it is", path.display(), e));
+    }
+    let json = serde_json::to_vec_pretty(config)?;
+    write_atomic(path, &json)
+}
+
+/// Puts `mapping.json.bak.1` back in place. The current file becomes the newest backup, so
+/// restoring twice in a row undoes the restore.
+pub fn restore_previous_config(path: &Path) -> Result<()> {
+    let backup = backup_path(path, 1);
+    let text = std::fs::read_to_string(&backup).with_context(|| format!("No backup found at {}", backup.display()))?;
+    parse_config(&text).with_context(|| format!("{} is not a valid config", backup.display()))?;
+    write_atomic(path, text.as_bytes())
+}
+
+/// Upgrades an older `mapping.json` on disk to the current schema. Run once at startup, before the
+/// engine and GUI threads start reading the file.
+pub fn migrate_config_file(path: &Path) -> Result<()> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(_) => return Ok(()),
+    };
+    let (config, old_version) = parse_config(&text)?;
+    if old_version < CONFIG_VERSION {
+        println!("DEBUG: Migrating {} from config version {} to {}", path.display(), old_version, CONFIG_VERSION);
+        // Keep the original around in case the user wants to go back to an older RVCI
+        std::fs::write(path.with_extension(format!("json.v{}", old_version)), &text)?;
+        save_config(path, &config)?;
+    }
+    Ok(())
+}
+
+/// Moves an unreadable `mapping.json` out of the way so it can be replaced. Returns the new path.
+pub fn set_aside_broken_config(path: &Path) -> Result<PathBuf> {
+    let broken = path.with_extension("json.broken");
+    std::fs::rename(path, &broken)?;
+    Ok(broken)
+}
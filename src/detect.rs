@@ -0,0 +1,223 @@
+use crate::config::{self, SerialConfig, UsbId, AUTO_PORT};
+use crate::protocol::{create_protocol, ControllerEvent};
+use anyhow::{anyhow, Result};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a probed port gets to send something we recognize. Covers the Nano's reset on open.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// A simulator that hasn't refreshed its port file for this long has gone away.
+const SIMULATOR_STALE: Duration = Duration::from_secs(3);
+
+/// Serial ports held by a controller, shared by every controller thread so auto-detect for one
+/// controller never opens the port another one is already using.
+#[derive(Debug, Default)]
+pub struct PortClaims {
+    ports: Mutex<Vec<String>>,
+}
+
+impl PortClaims {
+    /// `None` if another controller holds `port`.
+    pub fn claim(self: &Arc<Self>, port: &str) -> Option<PortClaim> {
+        let mut ports = self.ports.lock().unwrap();
+        if ports.iter().any(|p| p == port) { return None; }
+        ports.push(port.to_string());
+        Some(PortClaim { claims: Arc::clone(self), port: port.to_string() })
+    }
+}
+
+/// A port one controller holds until this is dropped.
+#[derive(Debug)]
+pub struct PortClaim {
+    claims: Arc<PortClaims>,
+    port: String,
+}
+
+impl PortClaim {
+    pub fn port(&self) -> &str { &self.port }
+}
+
+impl Drop for PortClaim {
+    fn drop(&mut self) {
+        self.claims.ports.lock().unwrap().retain(|p| *p != self.port);
+    }
+}
+
+/// A serial port as listed in the settings window.
+pub struct PortEntry {
+    pub name: String,
+    pub usb: Option<UsbId>,
+    pub description: String,
+}
+
+impl PortEntry {
+    pub fn label(&self) -> String {
+        if self.description.is_empty() { self.name.clone() } else { format!("{} ({})", self.name, self.description) }
+    }
+}
+
+fn usb_id(info: &SerialPortInfo) -> Option<UsbId> {
+    match &info.port_type {
+        SerialPortType::UsbPort(usb) => Some(UsbId { vid: usb.vid, pid: usb.pid, serial_number: usb.serial_number.clone() }),
+        _ => None,
+    }
+}
+
+/// Ports a controller could be on. On Linux that's USB serial adapters and CDC ACM devices only,
+/// the kernel lists plenty of legacy ttyS ports that never have anything attached.
+fn is_candidate(info: &SerialPortInfo) -> bool {
+    if cfg!(target_os = "linux") {
+        return info.port_name.starts_with("/dev/ttyUSB") || info.port_name.starts_with("/dev/ttyACM");
+    }
+    // Opening Bluetooth serial ports can block for a long time while Windows tries to connect
+    !matches!(info.port_type, SerialPortType::BluetoothPort)
+}
+
+pub fn list_ports() -> Vec<PortEntry> {
+    let mut ports: Vec<SerialPortInfo> = serialport::available_ports().unwrap_or_default();
+    ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+    ports.iter()
+        .filter(|info| is_candidate(info))
+        .map(|info| {
+            let description = match &info.port_type {
+                SerialPortType::UsbPort(usb) => {
+                    let product = usb.product.clone().unwrap_or_else(|| "USB".to_string());
+                    format!("{} {:04x}:{:04x}", product, usb.vid, usb.pid)
+                }
+                _ => String::new(),
+            };
+            PortEntry { name: info.port_name.clone(), usb: usb_id(info), description }
+        })
+        .chain(simulator_port().map(|name| PortEntry { name, usb: None, description: "RVCI simulator".to_string() }))
+        .collect()
+}
+
+/// Where `RVCI --simulate` announces its pseudo-terminal. Those aren't listed by the system.
+fn simulator_file() -> PathBuf {
+    config::get_config_path().with_file_name("simulator-port")
+}
+
+/// Called by the simulator every second while it runs.
+pub fn publish_simulator_port(port: &str) {
+    let _ = std::fs::write(simulator_file(), port);
+}
+
+pub fn withdraw_simulator_port() {
+    let _ = std::fs::remove_file(simulator_file());
+}
+
+/// The port of a running simulator, if there is one.
+fn simulator_port() -> Option<String> {
+    let file = simulator_file();
+    let modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok()?;
+    if SystemTime::now().duration_since(modified).unwrap_or_default() > SIMULATOR_STALE { return None; }
+    let port = std::fs::read_to_string(&file).ok()?.trim().to_string();
+    if Path::new(&port).exists() { Some(port) } else { None }
+}
+
+fn usb_matches(wanted: &UsbId, found: &UsbId) -> bool {
+    wanted.vid == found.vid
+        && wanted.pid == found.pid
+        && (wanted.serial_number.is_none() || wanted.serial_number == found.serial_number)
+}
+
+/// Opens `port_name` and waits for a slider line or handshake.
+fn probe(port_name: &str, serial: &SerialConfig) -> bool {
+    let mut protocol = match create_protocol(&serial.protocol) {
+        Some(p) => p,
+        None => return false,
+    };
+    let mut port = match serialport::new(port_name, serial.baud).timeout(Duration::from_millis(100)).open() {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    if let Some(request) = protocol.handshake_request() {
+        let _ = port.write_all(request);
+    }
+
+    let started = Instant::now();
+    let mut reader = BufReader::new(port);
+    let mut line = String::new();
+    while started.elapsed() < PROBE_TIMEOUT {
+        match reader.read_line(&mut line) {
+            // The other end is gone, like a simulator that quit
+            Ok(0) => return false,
+            Ok(_) => {
+                if let Ok(Some(ControllerEvent::Hello(_) | ControllerEvent::Dials(_))) = protocol.parse_line(&line) {
+                    return true;
+                }
+                line.clear();
+            }
+            // A timeout keeps the partial line for the next read. Garbage from the boot loader
+            // isn't UTF-8, and is skipped.
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted | ErrorKind::InvalidData) => {}
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+/// Works out which port to open and claims it: by USB id if one is pinned, by probing every port
+/// no other controller holds for `"auto"`, otherwise the configured name as is.
+pub fn resolve_port(serial: &SerialConfig, claims: &Arc<PortClaims>) -> Result<PortClaim> {
+    if let Some(wanted) = &serial.usb {
+        let ports = serialport::available_ports().unwrap_or_default();
+        let mut in_use = false;
+        for info in &ports {
+            if let Some(found) = usb_id(info) {
+                if !usb_matches(wanted, &found) { continue; }
+                match claims.claim(&info.port_name) {
+                    Some(claim) => return Ok(claim),
+                    None => in_use = true,
+                }
+            }
+        }
+        if in_use {
+            return Err(anyhow!("Every controller with USB id {:04x}:{:04x} is used by another controller", wanted.vid, wanted.pid));
+        }
+        return Err(anyhow!("No controller with USB id {:04x}:{:04x} is plugged in", wanted.vid, wanted.pid));
+    }
+
+    if serial.port != AUTO_PORT {
+        return claims.claim(&serial.port).ok_or_else(|| anyhow!("{} is used by another controller", serial.port));
+    }
+
+    // USB adapters first, those are far more likely to be a controller
+    let mut candidates: Vec<SerialPortInfo> = serialport::available_ports().unwrap_or_default()
+        .into_iter()
+        .filter(is_candidate)
+        .collect();
+    candidates.sort_by_key(|info| usb_id(info).is_none());
+
+    // Claimed before probing, so two controllers looking at once can't both end up on one port
+    for info in &candidates {
+        let claim = match claims.claim(&info.port_name) {
+            Some(c) => c,
+            None => {
+                println!("DEBUG: Skipping {}, another controller uses it", info.port_name);
+                continue;
+            }
+        };
+        println!("DEBUG: Probing {} for a controller...", info.port_name);
+        if probe(&info.port_name, serial) {
+            if let Some(usb) = usb_id(info) {
+                println!("DEBUG: Found controller on {} (USB {:04x}:{:04x})", info.port_name, usb.vid, usb.pid);
+            } else {
+                println!("DEBUG: Found controller on {}", info.port_name);
+            }
+            return Ok(claim);
+        }
+    }
+    if let Some(claim) = simulator_port().and_then(|port| claims.claim(&port)) {
+        println!("DEBUG: Probing {} for the simulator...", claim.port());
+        if probe(claim.port(), serial) {
+            println!("DEBUG: Found the simulator on {}", claim.port());
+            return Ok(claim);
+        }
+    }
+    Err(anyhow!("No controller found on any serial port"))
+}
@@ -0,0 +1,790 @@
+use crate::audio::{clean_process_name, AudioBackend, AudioSession, DataFlow, Endpoint};
+use crate::buttons::{Gesture, GestureDetector};
+use crate::config::{AppConfig, ButtonAction, ButtonConfig, ControllerConfig, DialConfig, DialInput, DialType, MatchRule, MidiControl, SelectorPosition};
+use crate::matching::SessionMatcher;
+use crate::protocol::{create_protocol, ControllerEvent, ControllerInfo, DeejProtocol, LineProtocol, HANDSHAKE_VERSION};
+use crate::smoothing::Smoother;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Makes the first of `names` that is connected the default device. Names match any device whose
+/// name contains them, ignoring case.
+pub fn switch_device(backend: &mut dyn AudioBackend, flow: DataFlow, names: &[String]) {
+    let names: Vec<&String> = names.iter().filter(|n| !n.is_empty() && *n != "None").collect();
+    if names.is_empty() { return; }
+    let kind = if flow == DataFlow::Render { "playback" } else { "recording" };
+    println!("DEBUG: Attempting to switch {} device to -> {:?}", kind, names);
+
+    let all_devices = backend.list_devices(flow).unwrap_or_default();
+    let match_result = names.iter().find_map(|name| {
+        all_devices.iter().find(|d| d.name.to_lowercase().contains(&name.to_lowercase()))
+    });
+
+    if let Some(device) = match_result {
+        println!("DEBUG: Found matching device: '{}' (ID: {})", device.name, device.id);
+        match backend.set_default_device(flow, device) {
+            Ok(()) => println!("DEBUG: Successfully switched to '{}'", device.name),
+            Err(e) => println!("ERROR: {}", e),
+        }
+    } else {
+        println!("ERROR: Could not find {} device matching any of {:?}", kind, names);
+    }
+}
+
+/// Switches to the devices of one selector switch position.
+pub fn apply_selector_position(backend: &mut dyn AudioBackend, position: &SelectorPosition) {
+    switch_device(backend, DataFlow::Render, &position.outputs);
+    if let Some(input) = &position.input {
+        switch_device(backend, DataFlow::Capture, std::slice::from_ref(input));
+    }
+}
+
+/// Sent from the engine thread to the GUI.
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    /// Show the volume overlay. Without a level only the text is shown.
+    Osd { name: String, level: Option<f32> },
+    /// The controller has a different number of sliders than mapping.json has knobs.
+    DialCountMismatch { controller_index: usize, controller: String, sliders: usize, dials: usize },
+    /// A control on a MIDI controller moved, so the settings window can bind it to a knob.
+    MidiControl { controller_index: usize, control: MidiControl },
+    /// Unscaled readings as (knob index, reading), sent instead of setting volumes while the
+    /// controller is being calibrated.
+    RawReadings { controller_index: usize, readings: Vec<(usize, f32)> },
+}
+
+/// Controller whose knobs the settings window is calibrating, `NOT_CALIBRATING` if none. Its knobs
+/// are turned end to end while calibrating, so they report their readings and leave the volumes
+/// alone. Checked on every frame, so it's an atomic rather than a lock.
+static CALIBRATING: AtomicUsize = AtomicUsize::new(NOT_CALIBRATING);
+const NOT_CALIBRATING: usize = usize::MAX;
+
+pub fn calibrating_controller() -> Option<usize> {
+    match CALIBRATING.load(Ordering::Relaxed) {
+        NOT_CALIBRATING => None,
+        index => Some(index),
+    }
+}
+
+pub fn set_calibrating_controller(index: Option<usize>) {
+    CALIBRATING.store(index.unwrap_or(NOT_CALIBRATING), Ordering::Relaxed);
+}
+
+/// Frames of a different length than the mapping needed in a row before we believe that's what
+/// the hardware really sends, and not a line that got cut off while connecting.
+const FRAMES_TO_LEARN_SLIDER_COUNT: u32 = 10;
+
+/// A MIDI control that keeps moving is reported for learning at most this often.
+const MIDI_REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Encoder speed in steps per second below which steps keep their configured size, and above
+/// which they are multiplied by the full `1 + acceleration`.
+const ENCODER_SLOW: f32 = 5.0;
+const ENCODER_FAST: f32 = 40.0;
+
+/// How long the backend may keep its cached sessions and volumes before they are read again, so
+/// apps started in the meantime show up.
+const CACHE_LIFETIME: Duration = Duration::from_secs(5);
+
+/// How close a pickup knob has to come to its target's volume to take it over, and how far the
+/// volume may drift from what the knob set before the knob has to pick it up again.
+const PICKUP_TOLERANCE: f32 = 0.02;
+
+/// How long a pickup knob goes by the target volume it last read or set before asking the backend
+/// again. Longer means another app's change is noticed later.
+const PICKUP_RECHECK: Duration = Duration::from_millis(500);
+
+fn build_protocol(name: &str) -> Box<dyn LineProtocol> {
+    create_protocol(name).unwrap_or_else(|| {
+        println!("ERROR: Unknown protocol '{}', using deej.", name);
+        Box::new(DeejProtocol)
+    })
+}
+
+/// The knob mappings of `profile`, where 0 is the controller's own `dials`.
+fn profile_dials(controller: &ControllerConfig, profile: usize) -> &[DialConfig] {
+    match profile.checked_sub(1).and_then(|p| controller.profiles.get(p)) {
+        Some(p) => &p.dials,
+        None => &controller.dials,
+    }
+}
+
+/// Indices of the knobs in `dials` that are pots, i.e. the ones filled in by `Dials` frames.
+fn pot_indices(dials: &[DialConfig]) -> Vec<usize> {
+    dials.iter().enumerate().filter(|(_, d)| !d.input.is_encoder()).map(|(i, _)| i).collect()
+}
+
+/// Name of a knob's target on the OSD. "None" and "Unassigned" aren't shown.
+fn osd_name(dial_cfg: &DialConfig) -> String {
+    match dial_cfg.dial_type {
+        DialType::System => "Master Volume".to_string(),
+        DialType::AllOthers => "Other Apps".to_string(),
+        DialType::Crossfade => match (dial_cfg.process_name.as_deref(), dial_cfg.fade_to.as_deref()) {
+            (Some(a), Some(b)) => format!("{} / {}", clean_process_name(a), clean_process_name(b)),
+            (Some(side), None) | (None, Some(side)) => clean_process_name(side),
+            (None, None) => "Unassigned".to_string(),
+        },
+        _ => clean_process_name(dial_cfg.process_targets().first().map_or("Unassigned", |t| t.0)),
+    }
+}
+
+/// Every target of a knob for the log, like "steam.exe, game.exe".
+fn target_label(dial_cfg: &DialConfig) -> String {
+    let names: Vec<&str> = dial_cfg.process_targets().iter().map(|t| t.0).collect();
+    if names.is_empty() { "Unassigned".to_string() } else { names.join(", ") }
+}
+
+/// Processes mapped on any controller, so "all others" on one box skips apps owned by another.
+/// Other controllers count with their default profile, this one with its active profile.
+fn build_process_map(config: &AppConfig, index: usize, profile: usize) -> Vec<SessionMatcher> {
+    let mut process_map = Vec::new();
+    for (c, controller) in config.controllers.iter().enumerate() {
+        let dials = if c == index { profile_dials(controller, profile) } else { &controller.dials };
+        for dial in dials {
+            for (name, _) in dial.process_targets() {
+                match SessionMatcher::new(dial.match_rule, name) {
+                    Ok(matcher) => process_map.push(matcher),
+                    Err(e) => println!("ERROR: {}", e),
+                }
+            }
+        }
+    }
+    process_map
+}
+
+/// Turns raw controller lines into backend volume calls. Independent of the transport, so it can
+/// be driven from a serial port or straight from a test with a `MockBackend`. Each pipeline drives
+/// one entry of `AppConfig::controllers`.
+pub struct DialPipeline<'a> {
+    config: AppConfig,
+    index: usize,
+    backend: &'a mut dyn AudioBackend,
+    events: &'a mut dyn FnMut(EngineEvent),
+    protocol: Box<dyn LineProtocol>,
+    hardware: Option<ControllerInfo>,
+    /// Values per line the controller sends, from its handshake or learned from its frames.
+    slider_count: Option<usize>,
+    /// Length and repeat count of the current run of frames that don't match the mapping.
+    unexpected_frames: (usize, u32),
+    reported_mismatch: Option<(usize, usize)>,
+    gestures: GestureDetector,
+    /// Active entry of the controller's profiles, 0 being its default `dials`.
+    profile: usize,
+    /// Where the selector switch is, as last reported by the controller.
+    switch_position: Option<usize>,
+    smoothers: Vec<Smoother>,
+    last_applied_values: Vec<f32>,
+    /// Where each pickup knob waiting to reach its target's volume was last seen moving.
+    pickup_seen: Vec<Option<f32>>,
+    /// Target volume of each pickup knob as last set, and when it was last read from the backend.
+    pickup_volumes: Vec<Option<(f32, Instant)>>,
+    last_update: Option<Instant>,
+    /// When each encoder knob last turned, for acceleration.
+    last_turn: Vec<Option<Instant>>,
+    last_midi_report: Option<(MidiControl, Instant)>,
+    /// Stands in for the wall clock while replaying a recording.
+    clock: Option<Instant>,
+    process_map: Vec<SessionMatcher>,
+    caches_refreshed: Option<Instant>,
+}
+
+impl<'a> DialPipeline<'a> {
+    pub fn new(
+        config: AppConfig,
+        index: usize,
+        backend: &'a mut dyn AudioBackend,
+        events: &'a mut dyn FnMut(EngineEvent),
+    ) -> Self {
+        let controller = config.controllers.get(index).cloned().unwrap_or_default();
+        Self {
+            protocol: build_protocol(&controller.serial.protocol),
+            process_map: build_process_map(&config, index, 0),
+            smoothers: (0..controller.dials.len()).map(|_| Smoother::default()).collect(),
+            last_applied_values: vec![-1.0; controller.dials.len()],
+            pickup_seen: Vec::new(),
+            pickup_volumes: Vec::new(),
+            config,
+            index,
+            backend,
+            events,
+            hardware: None,
+            slider_count: None,
+            unexpected_frames: (0, 0),
+            reported_mismatch: None,
+            gestures: GestureDetector::new(),
+            profile: 0,
+            switch_position: None,
+            last_update: None,
+            last_turn: Vec::new(),
+            last_midi_report: None,
+            clock: None,
+            caches_refreshed: None,
+        }
+    }
+
+    /// This pipeline's controller. `None` once it was removed from the config.
+    pub fn controller(&self) -> Option<&ControllerConfig> { self.config.controllers.get(self.index) }
+
+    pub fn index(&self) -> usize { self.index }
+
+    /// Knob mappings of the active profile.
+    fn dials(&self) -> &[DialConfig] {
+        self.controller().map(|c| profile_dials(c, self.profile)).unwrap_or_default()
+    }
+
+    fn buttons(&self) -> &[ButtonConfig] {
+        self.controller().map(|c| c.buttons.as_slice()).unwrap_or_default()
+    }
+
+    /// Swaps in a new config without touching the connection. Dials whose mapping changed are
+    /// re-applied on the next frame so the new target picks up the knob's current position.
+    pub fn update_config(&mut self, new_config: AppConfig) {
+        let old = self.controller().cloned().unwrap_or_default();
+        let new = new_config.controllers.get(self.index).cloned().unwrap_or_default();
+
+        let old_dials = profile_dials(&old, self.profile).to_vec();
+        if self.profile > new.profiles.len() { self.profile = 0; }
+        let new_dials = profile_dials(&new, self.profile);
+        self.smoothers.resize_with(new_dials.len(), Smoother::default);
+        self.last_applied_values.resize(new_dials.len(), -1.0);
+        for (i, dial) in new_dials.iter().enumerate() {
+            if old_dials.get(i) != Some(dial) {
+                self.last_applied_values[i] = -1.0;
+                if let Some(seen) = self.pickup_seen.get_mut(i) { *seen = None; }
+                if let Some(cached) = self.pickup_volumes.get_mut(i) { *cached = None; }
+            }
+        }
+
+        if new.serial.protocol != old.serial.protocol {
+            self.protocol = build_protocol(&new.serial.protocol);
+        }
+        self.process_map = build_process_map(&new_config, self.index, self.profile);
+        let position = self.switch_position.unwrap_or_default().wrapping_sub(1);
+        let switch_changed = old.selector.get(position) != new.selector.get(position);
+        self.config = new_config;
+        self.check_dial_count();
+        if switch_changed { self.apply_switch_position(); }
+    }
+
+    /// Called after (re)opening the connection. Forgets everything the previous controller told us.
+    pub fn reset_connection(&mut self) {
+        self.hardware = None;
+        self.slider_count = None;
+        self.unexpected_frames = (0, 0);
+        self.reported_mismatch = None;
+        self.gestures.reset();
+        // The firmware reports the switch again after connecting, which re-applies its devices
+        self.switch_position = None;
+    }
+
+    pub fn handshake_request(&self) -> Option<&'static [u8]> {
+        self.protocol.handshake_request()
+    }
+
+    /// Pots in the default profile, which is what the hardware is checked against.
+    fn pot_count(&self) -> usize {
+        self.controller().map(|c| pot_indices(&c.dials).len()).unwrap_or_default()
+    }
+
+    /// Checks the hardware against the default profile. Other profiles may map fewer knobs.
+    fn check_dial_count(&mut self) {
+        if let Some(sliders) = self.slider_count {
+            let dials = self.pot_count();
+            if sliders == dials || self.reported_mismatch == Some((sliders, dials)) { return; }
+            self.reported_mismatch = Some((sliders, dials));
+
+            let controller = match (&self.hardware, self.controller()) {
+                (Some(hw), _) => hw.name.clone(),
+                (None, Some(c)) => c.label(self.index),
+                (None, None) => return,
+            };
+            println!("ERROR: {} has {} sliders, but mapping.json has {} knobs.", controller, sliders, dials);
+            (self.events)(EngineEvent::DialCountMismatch { controller_index: self.index, controller, sliders, dials });
+        }
+    }
+
+    pub fn handle_line(&mut self, raw_line: &str) {
+        match self.protocol.parse_line(raw_line) {
+            Ok(Some(event)) => self.handle_event(event),
+            Ok(None) => {}
+            Err(e) => println!("ERROR: Malformed line from controller: {}", e),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: ControllerEvent) {
+        match event {
+            ControllerEvent::Switch(position) => self.handle_switch(position),
+            ControllerEvent::Button { index, pressed } => self.handle_button(index, pressed),
+            ControllerEvent::Dials(values) => self.handle_dials(&values),
+            ControllerEvent::Encoder { index, delta } => self.handle_encoder(index, delta),
+            ControllerEvent::Hello(info) => self.handle_hello(info),
+        }
+    }
+
+    /// A MIDI control moved to `value` (0-127). Drives the knob and button bound to it, and is
+    /// reported to the settings window for learning.
+    pub fn handle_midi(&mut self, control: MidiControl, value: f32) {
+        let now = self.now();
+        let report = match self.last_midi_report {
+            Some((last, at)) => last != control || now.duration_since(at) >= MIDI_REPORT_INTERVAL,
+            None => true,
+        };
+        if report {
+            self.last_midi_report = Some((control, now));
+            (self.events)(EngineEvent::MidiControl { controller_index: self.index, control });
+        }
+
+        if let Some(i) = self.dials().iter().position(|d| d.midi == Some(control) && !d.input.is_encoder()) {
+            if self.calibrating() {
+                (self.events)(EngineEvent::RawReadings { controller_index: self.index, readings: vec![(i, value)] });
+            } else {
+                // MIDI values are clean and only sent on change, smoothing would leave them short
+                self.apply_dial(i, value, false);
+            }
+        }
+        if let Some(button) = self.buttons().iter().position(|b| b.midi == Some(control)) {
+            self.handle_button(button + 1, value >= 64.0);
+        }
+    }
+
+    fn handle_hello(&mut self, info: ControllerInfo) {
+        println!(
+            "DEBUG: Controller '{}' (handshake v{}): {} sliders, {} encoders, {} switches, {} buttons",
+            info.name, info.version, info.sliders, info.encoders, info.switches, info.buttons
+        );
+        if info.version > HANDSHAKE_VERSION {
+            println!("DEBUG: Controller uses handshake v{}, RVCI knows up to v{}. Continuing anyway.", info.version, HANDSHAKE_VERSION);
+        }
+        self.slider_count = Some(info.sliders);
+        self.hardware = Some(info);
+        self.check_dial_count();
+    }
+
+    fn handle_button(&mut self, index: usize, pressed: bool) {
+        println!("DEBUG: Button {} {}", index, if pressed { "down" } else { "up" });
+        if index == 0 { return; }
+        let config = self.buttons().get(index - 1).cloned().unwrap_or_default();
+        if let Some(gesture) = self.gestures.button(index - 1, pressed, &config, self.now()) {
+            self.run_gesture(index - 1, gesture);
+        }
+    }
+
+    fn calibrating(&self) -> bool { calibrating_controller() == Some(self.index) }
+
+    /// From now on the pipeline takes `now` as the current time instead of the wall clock, so a
+    /// replayed recording behaves the same at any speed.
+    pub fn set_clock(&mut self, now: Instant) { self.clock = Some(now); }
+
+    fn now(&self) -> Instant { self.clock.unwrap_or_else(Instant::now) }
+
+    /// Fires long presses and single presses that only complete by time passing, and lets the
+    /// backend's caches expire. The connection loop calls this between lines.
+    pub fn poll(&mut self) {
+        let now = self.now();
+        if self.caches_refreshed.is_none_or(|at| now.duration_since(at) >= CACHE_LIFETIME) {
+            self.backend.invalidate_caches();
+            self.caches_refreshed = Some(now);
+        }
+        let buttons = self.buttons().to_vec();
+        for (index, gesture) in self.gestures.poll(&buttons, now) {
+            self.run_gesture(index, gesture);
+        }
+    }
+
+    fn run_gesture(&mut self, index: usize, gesture: Gesture) {
+        let actions = match self.buttons().get(index) {
+            Some(button) => match gesture {
+                Gesture::Press => button.press.clone(),
+                Gesture::LongPress => button.long_press.clone(),
+                Gesture::DoublePress => button.double_press.clone(),
+            },
+            None => vec![],
+        };
+        if actions.is_empty() {
+            println!("DEBUG: Button {} {} has no actions", index + 1, gesture.label());
+            return;
+        }
+        println!("DEBUG: Button {} {}", index + 1, gesture.label());
+        for action in &actions {
+            self.run_action(action);
+        }
+    }
+
+    fn run_action(&mut self, action: &ButtonAction) {
+        match action {
+            ButtonAction::ToggleMute { target_type, process_name, match_rule } => {
+                self.toggle_mute(*target_type, process_name.as_deref(), *match_rule)
+            }
+            ButtonAction::SwitchDevice { device } => switch_device(&mut *self.backend, DataFlow::Render, std::slice::from_ref(device)),
+            ButtonAction::CycleProfile => self.cycle_profile(),
+        }
+    }
+
+    fn osd_text(&mut self, text: String) {
+        if self.config.enable_osd {
+            (self.events)(EngineEvent::Osd { name: text, level: None });
+        }
+    }
+
+    fn toggle_mute(&mut self, target_type: DialType, process_name: Option<&str>, match_rule: MatchRule) {
+        let (label, result) = match target_type {
+            DialType::System => ("Master Volume".to_string(), self.toggle_endpoint_mute(&Endpoint::DefaultOutput)),
+            DialType::Microphone => match process_name {
+                Some(mic) if mic != "None" => (mic.to_string(), self.toggle_endpoint_mute(&Endpoint::Capture(mic.to_string()))),
+                _ => return,
+            },
+            DialType::Process | DialType::AllOthers | DialType::Crossfade => {
+                let matching: Vec<AudioSession> = match (target_type, process_name) {
+                    (DialType::AllOthers, _) => self.other_sessions(),
+                    (_, Some(name)) => self.matched_sessions(match_rule, &[(name, 1.0)]).into_iter().map(|(sess, _)| sess).collect(),
+                    (_, None) => vec![],
+                };
+                let label = match target_type {
+                    DialType::AllOthers => "Other Apps".to_string(),
+                    _ => clean_process_name(process_name.unwrap_or("None")),
+                };
+                // All sessions follow the first one, so they never end up half muted
+                let result = match matching.first() {
+                    Some(first) => self.backend.session_muted(first).and_then(|muted| {
+                        for sess in &matching {
+                            self.backend.set_session_mute(sess, !muted)?;
+                        }
+                        Ok(!muted)
+                    }),
+                    None => Err(anyhow::anyhow!("No audio session for {}", label)),
+                };
+                (label, result)
+            }
+        };
+        match result {
+            Ok(muted) => {
+                println!("DEBUG: {} {}", label, if muted { "muted" } else { "unmuted" });
+                self.osd_text(format!("{} {}", label, if muted { "muted" } else { "unmuted" }));
+            }
+            Err(e) => println!("ERROR: Could not toggle mute for {}: {}", label, e),
+        }
+    }
+
+    fn toggle_endpoint_mute(&mut self, endpoint: &Endpoint) -> anyhow::Result<bool> {
+        let muted = !self.backend.endpoint_muted(endpoint)?;
+        self.backend.set_endpoint_mute(endpoint, muted)?;
+        Ok(muted)
+    }
+
+    fn cycle_profile(&mut self) {
+        let (next, name) = match self.controller() {
+            Some(c) if !c.profiles.is_empty() => {
+                let next = (self.profile + 1) % (c.profiles.len() + 1);
+                let name = if next == 0 { "Default".to_string() } else { c.profiles[next - 1].name.clone() };
+                (next, name)
+            }
+            _ => {
+                println!("DEBUG: No profiles configured to cycle through");
+                return;
+            }
+        };
+        self.profile = next;
+        println!("DEBUG: Switched to profile {} ({})", self.profile, name);
+
+        // Every knob takes over its new target at its current position
+        let dial_count = self.dials().len();
+        self.smoothers.resize_with(dial_count, Smoother::default);
+        self.last_applied_values = vec![-1.0; dial_count];
+        self.pickup_seen.clear();
+        self.pickup_volumes.clear();
+        self.process_map = build_process_map(&self.config, self.index, self.profile);
+        self.osd_text(format!("Profile: {}", name));
+    }
+
+    fn handle_switch(&mut self, position: usize) {
+        self.switch_position = Some(position);
+        self.apply_switch_position();
+    }
+
+    fn selector_position(&self, position: usize) -> Option<&SelectorPosition> {
+        self.controller().and_then(|c| c.selector.get(position.wrapping_sub(1)))
+    }
+
+    fn apply_switch_position(&mut self) {
+        let position = match self.switch_position {
+            Some(p) => p,
+            None => return,
+        };
+        match self.selector_position(position).cloned() {
+            Some(devices) => {
+                println!("DEBUG: Switch moved to position {}", position);
+                apply_selector_position(&mut *self.backend, &devices);
+            }
+            None => println!("DEBUG: No devices configured for switch position {}", position),
+        }
+    }
+
+    fn handle_dials(&mut self, values: &[f32]) {
+        if let Some(last) = self.last_update {
+            if self.now().duration_since(last) < Duration::from_millis(25) { return; }
+        }
+        self.last_update = Some(self.now());
+
+        let dial_count = self.pot_count();
+        if self.slider_count.is_none() && values.len() != dial_count {
+            // No handshake, so the frames themselves are all we know about the hardware
+            if self.unexpected_frames.0 == values.len() {
+                self.unexpected_frames.1 += 1;
+            } else {
+                self.unexpected_frames = (values.len(), 1);
+            }
+            if self.unexpected_frames.1 < FRAMES_TO_LEARN_SLIDER_COUNT { return; }
+            self.slider_count = Some(values.len());
+            self.check_dial_count();
+        }
+
+        let expected = self.slider_count.unwrap_or(dial_count);
+        if values.len() != expected { return; }
+
+        // Knobs the hardware doesn't have stay put, readings without a mapping are ignored
+        let pots = pot_indices(self.dials());
+        if self.calibrating() {
+            let readings = pots.into_iter().zip(values.iter().copied()).collect();
+            (self.events)(EngineEvent::RawReadings { controller_index: self.index, readings });
+            return;
+        }
+        for (i, raw_val) in pots.into_iter().zip(values) {
+            self.apply_dial(i, *raw_val, true);
+        }
+    }
+
+    /// `index` is 1-based and counts encoder knobs only.
+    fn handle_encoder(&mut self, index: usize, delta: i32) {
+        let found = self.dials().iter().enumerate()
+            .filter(|(_, d)| d.input.is_encoder())
+            .nth(index.wrapping_sub(1))
+            .map(|(i, d)| (i, d.clone()));
+        let (i, dial_cfg) = match found {
+            Some(f) => f,
+            None => {
+                println!("DEBUG: No knob is set up for encoder {}", index);
+                return;
+            }
+        };
+        let (step, acceleration) = match dial_cfg.input {
+            DialInput::Encoder { step, acceleration } => (step, acceleration),
+            DialInput::Pot => return,
+        };
+
+        let now = self.now();
+        if i >= self.last_turn.len() { self.last_turn.resize(i + 1, None); }
+        let speed = match self.last_turn[i] {
+            Some(last) => delta.unsigned_abs() as f32 / now.duration_since(last).as_secs_f32().max(0.001),
+            None => 0.0,
+        };
+        self.last_turn[i] = Some(now);
+        let boost = ((speed - ENCODER_SLOW) / (ENCODER_FAST - ENCODER_SLOW)).clamp(0.0, 1.0);
+        let mut change = delta as f32 * step * (1.0 + acceleration * boost);
+        if dial_cfg.inverted { change = -change; }
+
+        // Start from wherever the volume is now, so changes made elsewhere are kept
+        let current = match self.current_volume(&dial_cfg) {
+            Some(v) => v,
+            None => {
+                println!("DEBUG: [Knob {}] Nothing to turn for {}", i + 1, dial_cfg.dial_type);
+                return;
+            }
+        };
+        // Snapping would hold an encoder in place, it only keeps to the output range
+        let position = dial_cfg.curve.position_of(dial_cfg.output_position(current));
+        let volume = dial_cfg.to_output(dial_cfg.curve.apply(position + change));
+        if i < self.last_applied_values.len() { self.last_applied_values[i] = volume; }
+        self.set_dial_volume(i, &dial_cfg, volume);
+    }
+
+    /// Sessions no knob on any controller is mapped to, for "all others".
+    fn other_sessions(&mut self) -> Vec<AudioSession> {
+        let sessions = self.backend.list_sessions().unwrap_or_default();
+        sessions.into_iter().filter(|sess| !self.process_map.iter().any(|m| m.matches(sess))).collect()
+    }
+
+    /// Sessions a knob turns, each with the gain its volume is scaled by.
+    fn dial_sessions(&mut self, dial_cfg: &DialConfig) -> Vec<(AudioSession, f32)> {
+        if dial_cfg.dial_type == DialType::AllOthers {
+            return self.other_sessions().into_iter().map(|sess| (sess, 1.0)).collect();
+        }
+        self.matched_sessions(dial_cfg.match_rule, &dial_cfg.process_targets())
+    }
+
+    /// Sessions a crossfade knob at `position` turns, each with the volume it gets.
+    fn crossfade_sessions(&mut self, dial_cfg: &DialConfig, position: f32) -> Vec<(AudioSession, f32)> {
+        let (a, b) = dial_cfg.fade_law.volumes(position);
+        let sides: Vec<(&str, f32)> = [(dial_cfg.process_name.as_deref(), a), (dial_cfg.fade_to.as_deref(), b)].into_iter()
+            .filter_map(|(name, volume)| Some((name?, volume)))
+            .collect();
+        self.matched_sessions(dial_cfg.match_rule, &sides)
+    }
+
+    /// Sessions matching `targets` (process names with a gain each) under `rule`. A session
+    /// matched by several names goes with the first, and they come in that order.
+    fn matched_sessions(&mut self, rule: MatchRule, targets: &[(&str, f32)]) -> Vec<(AudioSession, f32)> {
+        let matchers: Vec<(SessionMatcher, f32)> = targets.iter()
+            .filter_map(|(name, gain)| SessionMatcher::new(rule, name).ok().map(|m| (m, *gain)))
+            .collect();
+        let mut sessions: Vec<(usize, AudioSession)> = self.backend.list_sessions().unwrap_or_default().into_iter()
+            .filter_map(|sess| Some((matchers.iter().position(|(m, _)| m.matches(&sess))?, sess)))
+            .collect();
+        sessions.sort_by_key(|(target, _)| *target);
+        sessions.into_iter().map(|(target, sess)| (sess, matchers[target].1)).collect()
+    }
+
+    /// The target's volume as the backend reports it, on the knob's scale. For several
+    /// sessions, the first one's.
+    fn current_volume(&mut self, dial_cfg: &DialConfig) -> Option<f32> {
+        match dial_cfg.dial_type {
+            DialType::System => self.backend.endpoint_volume(&Endpoint::DefaultOutput).ok(),
+            DialType::Microphone => match dial_cfg.process_name.as_deref() {
+                Some(mic) if mic != "None" => self.backend.endpoint_volume(&Endpoint::Capture(mic.to_string())).ok(),
+                _ => None,
+            },
+            DialType::Crossfade => {
+                let mut side = |name: Option<&str>| -> Option<f32> {
+                    let (sess, _) = self.matched_sessions(dial_cfg.match_rule, &[(name?, 1.0)]).into_iter().next()?;
+                    self.backend.session_volume(&sess).ok()
+                };
+                let (a, b) = (side(dial_cfg.process_name.as_deref()), side(dial_cfg.fade_to.as_deref()));
+                dial_cfg.fade_law.position_of(a, b)
+            }
+            DialType::Process | DialType::AllOthers => {
+                let (sess, gain) = self.dial_sessions(dial_cfg).into_iter().next()?;
+                let volume = self.backend.session_volume(&sess).ok()?;
+                Some(if gain > 0.0 { (volume / gain).min(1.0) } else { volume })
+            }
+        }
+    }
+
+    fn apply_dial(&mut self, i: usize, raw_val: f32, smooth: bool) {
+        let now = self.now();
+        let config = &self.config;
+        let controller = match config.controllers.get(self.index) {
+            Some(c) => c,
+            None => return,
+        };
+        let dial_cfg = match profile_dials(controller, self.profile).get(i) {
+            Some(d) => d,
+            None => return,
+        };
+
+        let mut normalized = dial_cfg.normalize(raw_val);
+
+        if dial_cfg.inverted {
+            normalized = 1.0 - normalized;
+        }
+
+        let volume = dial_cfg.curve.apply(normalized);
+
+        if i >= self.smoothers.len() { self.smoothers.resize_with(i + 1, Smoother::default); }
+        if i >= self.last_applied_values.len() { self.last_applied_values.resize(i + 1, -1.0); }
+
+        let smoother = &mut self.smoothers[i];
+        let smoothed = if smooth { smoother.process(&dial_cfg.filter, volume, now) } else { volume };
+        let output = dial_cfg.snap(dial_cfg.to_output(smoothed));
+        if !smoother.should_apply(dial_cfg, output, self.last_applied_values[i]) {
+            return;
+        }
+
+        let dial_cfg = dial_cfg.clone();
+        if dial_cfg.pickup && !self.picked_up(i, &dial_cfg, output) {
+            return;
+        }
+        self.last_applied_values[i] = output;
+        if let Some(Some((cached, _))) = self.pickup_volumes.get_mut(i) { *cached = output; }
+        self.set_dial_volume(i, &dial_cfg, output);
+    }
+
+    /// Whether pickup knob `i` at `volume` may set its target. It keeps the target as long as
+    /// nothing else changed the volume. After that, or after connecting or switching profiles,
+    /// it waits until it crosses the target's volume.
+    fn picked_up(&mut self, i: usize, dial_cfg: &DialConfig, volume: f32) -> bool {
+        let now = self.now();
+        if i >= self.pickup_volumes.len() { self.pickup_volumes.resize(i + 1, None); }
+        let current = match self.pickup_volumes[i] {
+            Some((cached, read_at)) if now.duration_since(read_at) < PICKUP_RECHECK => cached,
+            _ => match self.current_volume(dial_cfg) {
+                Some(v) => {
+                    self.pickup_volumes[i] = Some((v, now));
+                    v
+                }
+                None => return true,
+            },
+        };
+        if i >= self.pickup_seen.len() { self.pickup_seen.resize(i + 1, None); }
+        let last = self.last_applied_values[i];
+        if last >= 0.0 && (current - last).abs() <= PICKUP_TOLERANCE {
+            self.pickup_seen[i] = None;
+            return true;
+        }
+
+        let seen = self.pickup_seen[i];
+        let crossed = (volume - current).abs() <= PICKUP_TOLERANCE
+            || seen.is_some_and(|s| (s - current).signum() != (volume - current).signum());
+        if crossed {
+            println!("DEBUG: [Knob {}] Picked up {} at {:.3}", i + 1, osd_name(dial_cfg), current);
+            self.pickup_seen[i] = None;
+            return true;
+        }
+        match seen {
+            None => {
+                println!("DEBUG: [Knob {}] Waiting to pick up {} at {:.3}", i + 1, osd_name(dial_cfg), current);
+                self.pickup_seen[i] = Some(volume);
+            }
+            // A resting knob doesn't keep the OSD up, only turning it does
+            Some(s) if (volume - s).abs() >= PICKUP_TOLERANCE => {
+                self.pickup_seen[i] = Some(volume);
+                let name = osd_name(dial_cfg);
+                if self.config.enable_osd && name != "None" && name != "Unassigned" {
+                    (self.events)(EngineEvent::Osd { name: format!("{}: move to pick up", name), level: Some(current) });
+                }
+            }
+            Some(_) => {}
+        }
+        false
+    }
+
+    /// Logs, shows the OSD and sends `volume` to every target of knob `i`.
+    fn set_dial_volume(&mut self, i: usize, dial_cfg: &DialConfig, volume: f32) {
+        let config = &self.config;
+        let target_lbl = target_label(dial_cfg);
+        if config.controllers.len() > 1 {
+            let label = config.controllers.get(self.index).map(|c| c.label(self.index)).unwrap_or_default();
+            println!("DEBUG: [{} knob {}] {} ({}) -> {:.3}", label, i + 1, dial_cfg.dial_type, target_lbl, volume);
+        } else {
+            println!("DEBUG: [Knob {}] {} ({}) -> {:.3}", i + 1, dial_cfg.dial_type, target_lbl, volume);
+        }
+
+        if config.enable_osd {
+            let display_name = osd_name(dial_cfg);
+            if display_name != "None" && display_name != "Unassigned" {
+                (self.events)(EngineEvent::Osd { name: display_name, level: Some(volume) });
+            }
+        }
+
+        match dial_cfg.dial_type {
+            DialType::System => {
+                let _ = self.backend.set_endpoint_volume(&Endpoint::DefaultOutput, volume);
+            },
+            DialType::Microphone => {
+                if let Some(target) = &dial_cfg.process_name {
+                    if target != "None" {
+                        let _ = self.backend.set_endpoint_volume(&Endpoint::Capture(target.clone()), volume);
+                    }
+                }
+            },
+            DialType::Process | DialType::AllOthers => {
+                for (sess, gain) in self.dial_sessions(dial_cfg) {
+                    let _ = self.backend.set_session_volume(&sess, (volume * gain).clamp(0.0, 1.0));
+                }
+            },
+            DialType::Crossfade => {
+                for (sess, side_volume) in self.crossfade_sessions(dial_cfg, volume) {
+                    let _ = self.backend.set_session_volume(&sess, side_volume.clamp(0.0, 1.0));
+                }
+            },
+        }
+    }
+}
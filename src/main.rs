@@ -1,181 +1,75 @@
 #![windows_subsystem = "windows"]
 
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
-use std::ffi::c_void;
+
+mod audio;
+mod buttons;
+mod config;
+mod detect;
+mod engine;
+mod matching;
+mod protocol;
+mod replay;
+mod simulator;
+mod smoothing;
+mod transport;
+mod watch;
+
+use audio::DataFlow;
+use config::{AppConfig, ConfigError, ControllerConfig, DialConfig, DialCurve, DialFilter, DialInput, DialType, FadeLaw, MatchRule, MidiControl, ProcessTarget, SelectorPosition, SerialConfig, TransportConfig, MIN_CALIBRATED_SPAN};
+use detect::PortClaims;
+use engine::{DialPipeline, EngineEvent};
+use transport::{Recorder, TransportEvent};
+use watch::ConfigWatcher;
 
 //fltk imports
 use fltk::{
     app,
     button::{Button, CheckButton},
-    enums::{Color, FrameType, Font},
+    dialog,
+    draw::{self, LineStyle},
+    enums::{Color, Event, FrameType, Font},
     frame::Frame,
     group::{Flex, Pack, Scroll},
     menu::Choice,
     misc::Progress,
-    input::{Input, IntInput}, 
+    input::{FloatInput, Input},
     prelude::*,
     window::Window,
     image::RgbImage, 
 };
 
 //winreg bs
+#[cfg(windows)]
 use winreg::enums::*;
+#[cfg(windows)]
 use winreg::RegKey;
 
 //tray icons
 use tray_icon::{
     menu::{Menu, MenuItem, MenuEvent},
-    TrayIconBuilder, Icon, TrayIconEvent, MouseButton,
+    TrayIcon, TrayIconBuilder, Icon, TrayIconEvent, MouseButton,
 };
 
 //WAPI imports
-use windows::core::{Interface, interface, GUID, PCWSTR, PCSTR, IUnknown, IUnknown_Vtbl}; 
-use windows::Win32::Foundation::CloseHandle;
-use windows::Win32::Media::Audio::Endpoints::IAudioEndpointVolume;
-use windows::Win32::Media::Audio::*; 
-use windows::Win32::System::Com::*; 
-use windows::Win32::System::ProcessStatus::GetModuleBaseNameW;
-use windows::Win32::System::Threading::{
-    OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ,
-};
-use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
-use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+#[cfg(windows)]
 use windows::Win32::System::Console::AllocConsole;
 
-// ==========================================
-// DIRECT WINDOWS API HOOKS
-// ==========================================
-
-#[link(name = "dwmapi")]
-extern "system" {
-    fn DwmSetWindowAttribute(
-        hwnd: *mut c_void,
-        dwAttribute: u32,
-        pvAttribute: *const c_void,
-        cbAttribute: u32,
-    ) -> i32;
-}
-
-const DWMWA_USE_IMMERSIVE_DARK_MODE_WIN11: u32 = 20;
-const DWMWA_USE_IMMERSIVE_DARK_MODE_WIN10: u32 = 19;
-const DWMWA_WINDOW_CORNER_PREFERENCE: u32 = 33;
-const DWMWCP_ROUND: u32 = 2; 
-
-#[link(name = "user32")]
-extern "system" {
-    fn SetWindowPos(hWnd: *mut c_void, hWndInsertAfter: *mut c_void, X: i32, Y: i32, cx: i32, cy: i32, uFlags: u32) -> i32;
-    fn SetLayeredWindowAttributes(hwnd: *mut c_void, crKey: u32, bAlpha: u8, dwFlags: u32) -> i32;
-
-    #[cfg(target_arch = "x86_64")]
-    fn GetWindowLongPtrW(hWnd: *mut c_void, nIndex: i32) -> isize;
-    #[cfg(target_arch = "x86_64")]
-    fn SetWindowLongPtrW(hWnd: *mut c_void, nIndex: i32, dwNewLong: isize) -> isize;
-
-    #[cfg(target_arch = "x86")]
-    fn GetWindowLongW(hWnd: *mut c_void, nIndex: i32) -> isize;
-    #[cfg(target_arch = "x86")]
-    fn SetWindowLongW(hWnd: *mut c_void, nIndex: i32, dwNewLong: isize) -> isize;
-}
-
-#[cfg(target_arch = "x86_64")]
-unsafe fn get_win_long(hwnd: *mut c_void, idx: i32) -> isize { GetWindowLongPtrW(hwnd, idx) }
-#[cfg(target_arch = "x86_64")]
-unsafe fn set_win_long(hwnd: *mut c_void, idx: i32, val: isize) -> isize { SetWindowLongPtrW(hwnd, idx, val) }
-
-#[cfg(target_arch = "x86")]
-unsafe fn get_win_long(hwnd: *mut c_void, idx: i32) -> isize { GetWindowLongW(hwnd, idx) }
-#[cfg(target_arch = "x86")]
-unsafe fn set_win_long(hwnd: *mut c_void, idx: i32, val: i32) -> isize { SetWindowLongW(hwnd, idx, val as isize) }
-
-const HWND_TOPMOST: isize = -1;
-const SWP_NOSIZE: u32 = 0x0001;
-const SWP_NOMOVE: u32 = 0x0002;
-const GWL_EXSTYLE: i32 = -20;
-const WS_EX_LAYERED: isize = 0x00080000;
-const WS_EX_TRANSPARENT: isize = 0x00000020;
-const LWA_COLORKEY: u32 = 0x00000001;
-const LWA_ALPHA: u32 = 0x00000002;
-
-const CLSID_PolicyConfigClient: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
-
-#[interface("f8679f50-850a-41cf-9c72-430f290290c8")]
-pub unsafe trait IPolicyConfig: IUnknown {
-    fn GetMixFormat(&self, pszdeviceid: PCWSTR, ppformat: *mut *mut c_void) -> windows::core::HRESULT;
-    fn GetDeviceFormat(&self, pszdeviceid: PCWSTR, bdefault: i32, ppformat: *mut *mut c_void) -> windows::core::HRESULT;
-    fn ResetDeviceFormat(&self, pszdeviceid: PCWSTR) -> windows::core::HRESULT;
-    fn SetDeviceFormat(&self, pszdeviceid: PCWSTR, pformat: *const c_void, pformatext: *const c_void) -> windows::core::HRESULT;
-    fn GetProcessingPeriod(&self, pszdeviceid: PCWSTR, bdefault: i32, pdefaultperiod: *mut i64, pminimumperiod: *mut i64) -> windows::core::HRESULT;
-    fn SetProcessingPeriod(&self, pszdeviceid: PCWSTR, pdefaultperiod: *const i64) -> windows::core::HRESULT;
-    fn GetShareMode(&self, pszdeviceid: PCWSTR, pmode: *mut i32) -> windows::core::HRESULT;
-    fn SetShareMode(&self, pszdeviceid: PCWSTR, mode: i32) -> windows::core::HRESULT;
-    fn GetPropertyValue(&self, pszdeviceid: PCWSTR, bfxenable: i32, pkey: *const c_void, pv: *mut c_void) -> windows::core::HRESULT;
-    fn SetPropertyValue(&self, pszdeviceid: PCWSTR, bfxenable: i32, pkey: *const c_void, pv: *const c_void) -> windows::core::HRESULT;
-    fn SetDefaultEndpoint(&self, pszdeviceid: PCWSTR, role: ERole) -> windows::core::Result<()>;
-    fn SetEndpointVisibility(&self, pszdeviceid: PCWSTR, bvisible: i32) -> windows::core::HRESULT;
-}
-
-fn default_true() -> bool { true }
-
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
-struct SerialConfig { port: String, baud: u32, timeout: u64 }
-
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
-struct DialConfig {
-    #[serde(rename = "type")] dial_type: String,
-    process_name: Option<String>,
-    #[serde(default)]
-    inverted: bool,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct AppConfig {
-    serial: SerialConfig,
-    value_max: f32,
-    work_device_1: String, 
-    work_device_2: String, 
-    #[serde(default)]
-    debug_mode: bool,
-    #[serde(default)]
-    use_logarithmic_scale: bool,
-    #[serde(default = "default_true")] 
-    enable_osd: bool,
-    dials: Vec<DialConfig>,
-}
-
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            serial: SerialConfig { port: "COM3".to_string(), baud: 115200, timeout: 50 },
-            value_max: 720.0,
-            work_device_1: "None".to_string(),
-            work_device_2: "None".to_string(),
-            debug_mode: false,
-            use_logarithmic_scale: false,
-            enable_osd: true,
-            dials: vec![],
-        }
-    }
-}
+#[cfg(windows)]
+mod win32;
 
 fn get_exe_dir() -> PathBuf {
     std::env::current_exe().map(|p| p.parent().map(|p| p.to_path_buf()).unwrap_or(p)).unwrap_or_else(|_| PathBuf::from("."))
 }
 
-fn get_config_path() -> PathBuf {
-    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("RVCI");
-    if !path.exists() { let _ = std::fs::create_dir_all(&path); }
-    path.join("mapping.json")
-}
-
+#[cfg(windows)]
 fn set_startup_launch(enable: bool) -> Result<()> {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     let path = hkcu.open_subkey_with_flags("Software\\Microsoft\\Windows\\CurrentVersion\\Run", KEY_ALL_ACCESS)?;
@@ -188,6 +82,7 @@ fn set_startup_launch(enable: bool) -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn check_startup_enabled() -> bool {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     if let Ok(path) = hkcu.open_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Run") {
@@ -200,592 +95,1738 @@ fn check_startup_enabled() -> bool {
     false
 }
 
-struct AudioController;
-impl AudioController {
-    unsafe fn get_system_volume() -> Result<IAudioEndpointVolume> {
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
-        let device: IMMDevice = enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)?;
-        Ok(device.Activate(CLSCTX_ALL, None)?)
+#[cfg(not(windows))]
+fn get_autostart_path() -> PathBuf {
+    dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")).join("autostart").join("rvci.desktop")
+}
+
+#[cfg(not(windows))]
+fn set_startup_launch(enable: bool) -> Result<()> {
+    let path = get_autostart_path();
+    if enable {
+        let exe_path = std::env::current_exe()?;
+        if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+        let entry = format!("[Desktop Entry]\nType=Application\nName=RVCI\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n", exe_path.display());
+        std::fs::write(&path, entry)?;
+    } else {
+        let _ = std::fs::remove_file(&path);
     }
-    
-    unsafe fn get_session_manager() -> Result<IAudioSessionManager2> {
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
-        let device: IMMDevice = enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)?;
-        Ok(device.Activate(CLSCTX_ALL, None)?)
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn check_startup_enabled() -> bool {
+    if let (Ok(entry), Ok(exe_path)) = (std::fs::read_to_string(get_autostart_path()), std::env::current_exe()) {
+        return entry.contains(&format!("Exec=\"{}\"", exe_path.display()));
     }
+    false
+}
 
-    unsafe fn get_mic_volume(mic_name: &str) -> Result<IAudioEndpointVolume> {
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
-        let collection = enumerator.EnumAudioEndpoints(eCapture, DEVICE_STATE_ACTIVE)?;
-        let count = collection.GetCount()?;
-        for i in 0..count {
-            if let Ok(item) = collection.Item(i) {
-                if let Ok(store) = item.OpenPropertyStore(STGM_READ) {
-                    if let Ok(prop) = store.GetValue(&PKEY_Device_FriendlyName) {
-                        let pwsz = prop.Anonymous.Anonymous.Anonymous.pwszVal;
-                        if !pwsz.is_null() {
-                            let name = pwsz.to_string().unwrap_or_default();
-                            if name.to_lowercase() == mic_name.to_lowercase() {
-                                return item.Activate::<IAudioEndpointVolume>(CLSCTX_ALL, None).map_err(anyhow::Error::from);
-                            }
-                        }
-                    }
-                }
-            }
+/// GUI-side lookups. Goes through the same `AudioBackend` the engine uses.
+struct AudioScanner;
+impl AudioScanner {
+    fn get_active_sessions() -> Vec<String> {
+        audio::session_names(audio::create_backend().as_mut())
+    }
+
+    fn get_playback_devices() -> Vec<String> {
+        audio::device_names(audio::create_backend().as_mut(), DataFlow::Render)
+    }
+
+    fn get_capture_devices() -> Vec<String> {
+        audio::device_names(audio::create_backend().as_mut(), DataFlow::Capture)
+    }
+
+}
+
+/// What the dropdowns in the controller sections offer.
+#[derive(Clone, Default)]
+struct MappingChoices {
+    processes: Vec<String>,
+    capture_devices: Vec<String>,
+    playback_devices: Vec<String>,
+    midi_ports: Vec<String>,
+}
+
+impl MappingChoices {
+    fn scan() -> Self {
+        Self {
+            processes: AudioScanner::get_active_sessions(),
+            capture_devices: AudioScanner::get_capture_devices(),
+            playback_devices: AudioScanner::get_playback_devices(),
+            midi_ports: transport::midi_ports(),
         }
-        Err(anyhow::anyhow!("Microphone not found"))
     }
+}
 
-    fn get_process_name(pid: u32) -> String {
-        unsafe {
-            if let Ok(handle) = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid) {
-                let mut buffer = [0u16; 1024];
-                let len = GetModuleBaseNameW(handle, None, &mut buffer);
-                let _ = CloseHandle(handle);
-                if len > 0 { 
-                    let mut name = String::from_utf16_lossy(&buffer[..len as usize]).to_string(); 
-                    if name.to_lowercase().ends_with(".exe") {
-                        name.truncate(name.len() - 4);
-                    }
-                    return name; 
-                }
+/// Controller and knob index waiting for a MIDI control to be moved, while "Learn" is active.
+static MIDI_LEARN: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+
+const VIRTUAL_MIDI_LABEL: &str = "Virtual port";
+
+/// Binds `control` to the knob that is learning, if it's on this controller. Returns true if
+/// the knob rows need redrawing.
+fn finish_midi_learn(state: &Arc<Mutex<AppConfig>>, controller_index: usize, control: MidiControl) -> bool {
+    let mut learning = MIDI_LEARN.lock().unwrap();
+    let dial = match *learning {
+        Some((ci, dial)) if ci == controller_index => dial,
+        _ => return false,
+    };
+    *learning = None;
+    if let Some(controller) = state.lock().unwrap().controllers.get_mut(controller_index) {
+        // A control drives one knob, so it moves over from wherever it was bound before
+        for d in controller.dials.iter_mut().filter(|d| d.midi == Some(control)) {
+            d.midi = None;
+        }
+        if let Some(d) = controller.dials.get_mut(dial) {
+            d.midi = Some(control);
+        }
+    }
+    true
+}
+
+/// Lowest and highest reading seen per knob while calibrating.
+type Calibration = Vec<Option<(f32, f32)>>;
+
+fn calibration_text(calibration: &Calibration, dials: &[DialConfig]) -> String {
+    let lines: Vec<String> = dials.iter().enumerate().map(|(i, dial)| {
+        let seen = match calibration.get(i).copied().flatten() {
+            _ if dial.input.is_encoder() => "encoder, nothing to calibrate".to_string(),
+            Some((low, high)) if high - low >= MIN_CALIBRATED_SPAN => format!("{:.0} to {:.0}", low, high),
+            _ => "not turned yet".to_string(),
+        };
+        format!("Knob {}: {}", i + 1, seen)
+    }).collect();
+    lines.join("\n")
+}
+
+/// The knobs' range if they all share one.
+fn range_summary(dials: &[DialConfig]) -> String {
+    match dials.first() {
+        Some(first) if dials.iter().all(|d| d.raw_min == first.raw_min && d.raw_max == first.raw_max) => {
+            format!("{:.0} to {:.0}", first.raw_min, first.raw_max)
+        }
+        Some(_) => "Per knob, hover a knob number".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Set while the calibration window measures how much the resting knobs wobble, to tune their
+/// filters, instead of their range.
+static TUNING_FILTERS: AtomicBool = AtomicBool::new(false);
+
+/// Readings kept per knob while tuning filters.
+const MAX_TUNING_READINGS: usize = 500;
+
+/// Readings per knob seen while tuning filters.
+type TuningReadings = Vec<Vec<f32>>;
+
+/// Positions (0 to 1) of knob `i`'s readings.
+fn tuning_positions(readings: &TuningReadings, i: usize, dial: &DialConfig) -> Vec<f32> {
+    readings.get(i).map(|r| r.iter().map(|raw| dial.normalize(*raw)).collect()).unwrap_or_default()
+}
+
+/// Gives every knob with enough readings the filter auto-tune picked, in every profile. Returns
+/// how many knobs changed.
+fn apply_tuning(controller: &mut ControllerConfig, readings: &TuningReadings) -> usize {
+    let mut tuned = 0;
+    for i in 0..readings.len() {
+        let dial = match controller.dials.get(i) {
+            Some(d) if !d.input.is_encoder() => d,
+            _ => continue,
+        };
+        if let Some(tuning) = smoothing::auto_tune(&tuning_positions(readings, i, dial)) {
+            for dial in controller.dial_lists_mut().filter_map(|dials| dials.get_mut(i)) {
+                dial.filter = tuning.filter;
+                dial.deadband = tuning.deadband;
+                dial.hysteresis = tuning.hysteresis;
             }
+            tuned += 1;
         }
-        String::new()
     }
+    tuned
 }
 
-struct AudioScanner;
-impl AudioScanner {
-    fn get_active_sessions() -> Vec<String> {
-        let mut names = HashSet::new();
-        unsafe {
-            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
-            if let Ok(mgr) = AudioController::get_session_manager() {
-                if let Ok(enum_sess) = mgr.GetSessionEnumerator() {
-                    if let Ok(count) = enum_sess.GetCount() {
-                        for i in 0..count {
-                            if let Ok(sess) = enum_sess.GetSession(i) {
-                                if let Ok(s2) = Interface::cast::<IAudioSessionControl2>(&sess) {
-                                    if let Ok(pid) = s2.GetProcessId() {
-                                        if pid != 0 {
-                                            let name = AudioController::get_process_name(pid);
-                                            if !name.is_empty() { names.insert(name); }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+fn tuning_text(readings: &TuningReadings, dials: &[DialConfig]) -> String {
+    let lines: Vec<String> = dials.iter().enumerate().map(|(i, dial)| {
+        let positions = tuning_positions(readings, i, dial);
+        let seen = match smoothing::auto_tune(&positions) {
+            _ if dial.input.is_encoder() => "encoder, nothing to tune".to_string(),
+            Some(tuning) => format!("wobbles {:.1}%, {}", smoothing::spread(&positions) * 100.0, tuning.filter.name()),
+            None => format!("{} of {} readings", positions.len(), smoothing::TUNE_READINGS),
+        };
+        format!("Knob {}: {}", i + 1, seen)
+    }).collect();
+    lines.join("\n")
+}
+
+/// Space between the curve preview's edge and its plot.
+const CURVE_MARGIN: i32 = 10;
+
+/// Where the `[position, volume]` point is drawn in the curve preview.
+fn curve_to_screen(frame: &Frame, point: [f32; 2]) -> (i32, i32) {
+    let (w, h) = (frame.w() - 2 * CURVE_MARGIN, frame.h() - 2 * CURVE_MARGIN);
+    let x = frame.x() + CURVE_MARGIN + (point[0] * w as f32).round() as i32;
+    let y = frame.y() + frame.h() - CURVE_MARGIN - (point[1] * h as f32).round() as i32;
+    (x, y)
+}
+
+/// The `[position, volume]` point under `(x, y)` in the curve preview, to three decimals.
+fn screen_to_curve(frame: &Frame, x: i32, y: i32) -> [f32; 2] {
+    let (w, h) = ((frame.w() - 2 * CURVE_MARGIN).max(1), (frame.h() - 2 * CURVE_MARGIN).max(1));
+    let position = (x - frame.x() - CURVE_MARGIN) as f32 / w as f32;
+    let volume = (frame.y() + frame.h() - CURVE_MARGIN - y) as f32 / h as f32;
+    [position, volume].map(|v| (v.clamp(0.0, 1.0) * 1000.0).round() / 1000.0)
+}
+
+/// Draws `curve` and lets its points be dragged. Clicking a curve that isn't custom turns it
+/// into one. A click next to the line adds a point, a right-click on a point removes it.
+/// `changed` gets the curve after every edit.
+fn curve_editor(curve: Rc<RefCell<DialCurve>>, mut changed: impl FnMut(&DialCurve) + 'static) -> Frame {
+    let mut frame = Frame::default();
+    frame.set_frame(FrameType::RFlatBox);
+    frame.set_color(WIDGET_BG);
+    frame.set_tooltip("Drag the points to shape the curve: left is the knob turned down, up is louder.\nClick to add a point, right-click a point to remove it.");
+    {
+        let curve = curve.clone();
+        frame.draw(move |f| {
+            let (left, bottom) = curve_to_screen(f, [0.0, 0.0]);
+            let (right, top) = curve_to_screen(f, [1.0, 1.0]);
+            draw::set_draw_color(WIDGET_HOVER);
+            draw::set_line_style(LineStyle::Solid, 1);
+            for quarter in [0.25, 0.5, 0.75] {
+                let (x, y) = curve_to_screen(f, [quarter, quarter]);
+                draw::draw_line(x, bottom, x, top);
+                draw::draw_line(left, y, right, y);
+            }
+
+            let curve = curve.borrow();
+            draw::set_draw_color(ACCENT_COLOR);
+            draw::set_line_style(LineStyle::Solid, 2);
+            draw::begin_line();
+            for step in 0..=64 {
+                let position = step as f32 / 64.0;
+                let (x, y) = curve_to_screen(f, [position, curve.apply(position)]);
+                draw::vertex(x as f64, y as f64);
+            }
+            draw::end_line();
+            draw::set_line_style(LineStyle::Solid, 0);
+            if let DialCurve::Points { points } = &*curve {
+                draw::set_draw_color(TEXT_COLOR);
+                for point in points {
+                    let (x, y) = curve_to_screen(f, *point);
+                    draw::draw_pie(x - 4, y - 4, 9, 9, 0.0, 360.0);
                 }
             }
-        }
-        let mut list: Vec<String> = names.into_iter().collect();
-        list.sort();
-        list
+        });
     }
 
-    fn get_devices_with_ids(data_flow: EDataFlow) -> Vec<(String, String)> {
-        let mut devices = Vec::new();
-        unsafe {
-            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
-            let enumerator: Result<IMMDeviceEnumerator, _> = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL);
-            if let Ok(enumerator) = enumerator {
-                if let Ok(collection) = enumerator.EnumAudioEndpoints(data_flow, DEVICE_STATE_ACTIVE) {
-                    if let Ok(count) = collection.GetCount() {
-                        for i in 0..count {
-                            if let Ok(item) = collection.Item(i) {
-                                let mut id_string = String::new();
-                                if let Ok(id_pwstr) = item.GetId() {
-                                    id_string = id_pwstr.to_string().unwrap_or_default();
-                                }
-                                let mut name_string = String::new();
-                                if let Ok(store) = item.OpenPropertyStore(STGM_READ) {
-                                    if let Ok(prop) = store.GetValue(&PKEY_Device_FriendlyName) {
-                                        let pwsz = prop.Anonymous.Anonymous.Anonymous.pwszVal;
-                                        if !pwsz.is_null() {
-                                            name_string = pwsz.to_string().unwrap_or_default();
-                                        }
-                                    }
-                                }
-                                if !name_string.is_empty() && !id_string.is_empty() {
-                                    devices.push((name_string, id_string));
-                                }
-                            }
+    let mut dragging: Option<usize> = None;
+    frame.handle(move |f, event| {
+        let (x, y) = app::event_coords();
+        match event {
+            Event::Push => {
+                let right = app::event_mouse_button() == app::MouseButton::Right;
+                let mut edited = curve.borrow().with_name("Custom");
+                if let DialCurve::Points { points } = &mut edited {
+                    let near = points.iter().position(|p| {
+                        let (px, py) = curve_to_screen(f, *p);
+                        (px - x).abs() <= 6 && (py - y).abs() <= 6
+                    });
+                    dragging = None;
+                    match near {
+                        // A curve needs both of its ends
+                        Some(i) if right => if points.len() > 2 { points.remove(i); },
+                        Some(i) => dragging = Some(i),
+                        None if right => return true,
+                        None => {
+                            let point = screen_to_curve(f, x, y);
+                            let i = points.iter().position(|p| p[0] > point[0]).unwrap_or(points.len());
+                            points.insert(i, point);
+                            dragging = Some(i);
                         }
                     }
                 }
+                *curve.borrow_mut() = edited;
+                changed(&curve.borrow());
+                f.redraw();
+                true
+            }
+            Event::Drag => {
+                let i = match dragging {
+                    Some(i) => i,
+                    None => return false,
+                };
+                if let DialCurve::Points { points } = &mut *curve.borrow_mut() {
+                    // Points stay in order of position
+                    let [position, volume] = screen_to_curve(f, x, y);
+                    let low = if i > 0 { points[i - 1][0] } else { 0.0 };
+                    let high = points.get(i + 1).map(|p| p[0]).unwrap_or(1.0);
+                    points[i] = [position.clamp(low, high), volume];
+                }
+                changed(&curve.borrow());
+                f.redraw();
+                true
             }
+            Event::Released => {
+                dragging = None;
+                true
+            }
+            _ => false,
+        }
+    });
+    frame
+}
+
+/// Shows `curve` in the curve dropdown and its setting in the input next to it.
+fn show_curve(curve: &DialCurve, choice: &mut Choice, lbl: &mut Frame, input: &mut FloatInput) {
+    choice.set_value(DialCurve::NAMES.iter().position(|n| *n == curve.name()).unwrap_or(0) as i32);
+    let (label, value) = match curve {
+        DialCurve::Power { exponent } => ("Exponent:", Some(*exponent)),
+        DialCurve::Db { min_db } => ("Lowest dB:", Some(*min_db)),
+        DialCurve::Linear | DialCurve::Points { .. } => ("", None),
+    };
+    lbl.set_label(label);
+    match value {
+        Some(value) => {
+            input.set_value(&value.to_string());
+            input.show();
         }
-        devices.sort_by(|a, b| a.0.cmp(&b.0));
-        devices
+        None => input.hide(),
+    }
+}
+
+/// Volumes (0 to 1) from percentages separated by commas or spaces, like "0, 50, 100".
+fn parse_percentages(text: &str) -> Option<Vec<f32>> {
+    text.split([',', ' '])
+        .map(|t| t.trim().trim_end_matches('%'))
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse::<f32>().ok().filter(|p| p.is_finite()).map(|p| (p / 100.0).clamp(0.0, 1.0)))
+        .collect()
+}
+
+fn percent(volume: f32) -> String {
+    ((volume * 1000.0).round() / 10.0).to_string()
+}
+
+/// Tooltip of a knob's "..." button.
+fn knob_summary(dial: &DialConfig) -> String {
+    let mut lines = vec![
+        format!("{} curve, {}% to {}%", dial.curve.name(), percent(dial.min_output), percent(dial.max_output)),
+        format!("{}, deadband {}, hysteresis {}", dial.filter.describe(), dial.deadband, dial.hysteresis),
+    ];
+    if !dial.snap_to.is_empty() {
+        lines.push(format!("Snaps to {}%", dial.snap_to.iter().map(|v| percent(*v)).collect::<Vec<_>>().join("%, ")));
+    }
+    if dial.pickup { lines.push("Picks up its target's volume".to_string()); }
+    if dial.dial_type == DialType::Crossfade { lines.push(format!("{} crossfade", dial.fade_law.label())); }
+    lines.join("\n")
+}
+
+/// Every process name of a knob with the apps it matches among `sessions`, one per line.
+fn match_preview(dial: &DialConfig, sessions: &[audio::AudioSession]) -> String {
+    if !dial.dial_type.matches_processes() { return "Only process and crossfade knobs pick apps by name".to_string(); }
+    let names: Vec<&str> = dial.process_targets().iter().map(|t| t.0).collect();
+    if names.is_empty() { return "No process picked yet".to_string(); }
+    matching::preview(dial.match_rule, &names, sessions).into_iter().map(|(name, matched)| match matched {
+        Ok(apps) if apps.is_empty() => format!("{}: nothing playing", name),
+        Ok(apps) => format!("{}: {}", name, apps.join(", ")),
+        Err(e) => e.to_string(),
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// Tooltip of a process name typed in for `rule`.
+fn pattern_help(rule: MatchRule) -> &'static str {
+    match rule {
+        MatchRule::Name => "The app's executable name, without .exe",
+        MatchRule::Glob => "* stands for any text and ? for one character, like discord*",
+        MatchRule::Regex => "A regular expression found anywhere in the executable's name, like ^discord(ptb|canary)?$",
+        MatchRule::Path => "The executable's full path, * and ? allowed, like C:\\Games\\*",
+        MatchRule::DisplayName => "The name the app shows in the volume mixer, * and ? allowed",
+    }
+}
+
+/// Opens the settings of knob `i` that don't fit on its row. Changes go straight into `state`.
+fn show_knob_settings(scroll_pack: &Pack, state: &Arc<Mutex<AppConfig>>, choices: &MappingChoices, ci: usize, i: usize) {
+    let (dial, midi) = match state.lock().unwrap().controllers.get(ci) {
+        Some(c) => match c.dials.get(i) {
+            Some(d) => (d.clone(), matches!(c.transport, TransportConfig::Midi { .. })),
+            None => return,
+        },
+        None => return,
+    };
+    // Writes a change into the knob, in case the window outlives it
+    let update = {
+        let state = state.clone();
+        move |change: &mut dyn FnMut(&mut DialConfig)| {
+            if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                change(d);
+            }
+        }
+    };
+
+    let mut win = Window::default().with_size(380, 760).with_label(&format!("Knob {}", i + 1));
+    win.set_color(Color::Black);
+    let mut col = Flex::default_fill().column();
+    col.set_margin(20);
+    col.set_pad(10);
+
+    // Which apps a process knob turns, with what its names match right now
+    let sessions = audio::create_backend().list_sessions().unwrap_or_default();
+    let mut row_match = Flex::default().row();
+    row_match.set_pad(10);
+    let lbl_match = section_label("Match by:");
+    let mut choice_match = Choice::default();
+    style_choice(&mut choice_match);
+    for rule in MatchRule::ALL { choice_match.add_choice(rule.label()); }
+    choice_match.set_value(MatchRule::ALL.iter().position(|r| *r == dial.match_rule).unwrap_or(0) as i32);
+    choice_match.set_tooltip("How the process names of this knob and the ones under it are compared with the apps playing");
+    row_match.end();
+    row_match.fixed(&lbl_match, 100);
+
+    let mut row_pattern = Flex::default().row();
+    row_pattern.set_pad(10);
+    let lbl_pattern = section_label("Process:");
+    let mut input_pattern = Input::default();
+    style_input(&mut input_pattern);
+    input_pattern.set_value(dial.process_name.as_deref().unwrap_or_default());
+    input_pattern.set_tooltip(pattern_help(dial.match_rule));
+    input_pattern.set_trigger(fltk::enums::CallbackTrigger::Changed);
+    row_pattern.end();
+    row_pattern.fixed(&lbl_pattern, 100);
+
+    let mut lbl_matches = Frame::default().with_label(&match_preview(&dial, &sessions));
+    lbl_matches.set_label_color(TEXT_COLOR);
+    lbl_matches.set_label_size(12);
+    lbl_matches.set_align(fltk::enums::Align::Left | fltk::enums::Align::Top | fltk::enums::Align::Inside | fltk::enums::Align::Clip);
+    if !dial.dial_type.matches_processes() {
+        choice_match.deactivate();
+        input_pattern.deactivate();
+    }
+
+    let mut row_fade = Flex::default().row();
+    row_fade.set_pad(10);
+    let lbl_fade = section_label("Fade law:");
+    let mut choice_fade = Choice::default();
+    style_choice(&mut choice_fade);
+    for law in FadeLaw::ALL { choice_fade.add_choice(law.label()); }
+    choice_fade.set_value(FadeLaw::ALL.iter().position(|l| *l == dial.fade_law).unwrap_or(0) as i32);
+    choice_fade.set_tooltip("Linear adds the volumes up to 100%, so the middle is quieter.\nEqual power keeps the mix as loud all the way across.");
+    row_fade.end();
+    row_fade.fixed(&lbl_fade, 100);
+    if dial.dial_type != DialType::Crossfade { choice_fade.deactivate(); }
+    {
+        let update = update.clone();
+        choice_fade.set_callback(move |c| {
+            let law = FadeLaw::ALL.get(c.value().max(0) as usize).copied().unwrap_or_default();
+            update(&mut |d| d.fade_law = law);
+        });
+    }
+    let sessions = Rc::new(sessions);
+    {
+        let update = update.clone();
+        let sessions = sessions.clone();
+        let mut lbl_matches = lbl_matches.clone();
+        let mut input_pattern = input_pattern.clone();
+        choice_match.set_callback(move |c| {
+            let rule = MatchRule::ALL.get(c.value().max(0) as usize).copied().unwrap_or_default();
+            update(&mut |d| {
+                d.match_rule = rule;
+                lbl_matches.set_label(&match_preview(d, &sessions));
+            });
+            input_pattern.set_tooltip(pattern_help(rule));
+        });
+    }
+    {
+        let update = update.clone();
+        let sessions = sessions.clone();
+        let mut lbl_matches = lbl_matches.clone();
+        input_pattern.set_callback(move |input| {
+            let pattern = input.value();
+            update(&mut |d| {
+                d.process_name = Some(pattern.trim().to_string()).filter(|p| !p.is_empty());
+                lbl_matches.set_label(&match_preview(d, &sessions));
+            });
+        });
     }
 
-    fn get_playback_devices_with_ids() -> Vec<(String, String)> {
-        Self::get_devices_with_ids(eRender)
+    let curve = Rc::new(RefCell::new(dial.curve.clone()));
+    let mut row_curve = Flex::default().row();
+    row_curve.set_pad(10);
+    let lbl_curve = section_label("Curve:");
+    let mut choice_curve = Choice::default();
+    style_choice(&mut choice_curve);
+    for name in DialCurve::NAMES { choice_curve.add_choice(name); }
+    choice_curve.set_tooltip("How the knob's position turns into a volume");
+    let mut lbl_curve_value = section_label("");
+    let mut input_curve = FloatInput::default();
+    style_input(&mut input_curve);
+    input_curve.set_trigger(fltk::enums::CallbackTrigger::Changed);
+    row_curve.end();
+    row_curve.fixed(&lbl_curve, 100);
+    row_curve.fixed(&input_curve, 60);
+    show_curve(&dial.curve, &mut choice_curve, &mut lbl_curve_value, &mut input_curve);
+
+    let preview = {
+        let update = update.clone();
+        let mut choice_curve = choice_curve.clone();
+        let mut lbl_curve_value = lbl_curve_value.clone();
+        let mut input_curve = input_curve.clone();
+        curve_editor(curve.clone(), move |edited| {
+            update(&mut |d| d.curve = edited.clone());
+            show_curve(edited, &mut choice_curve, &mut lbl_curve_value, &mut input_curve);
+        })
+    };
+    {
+        let update = update.clone();
+        let curve = curve.clone();
+        let mut preview = preview.clone();
+        let mut lbl_curve_value = lbl_curve_value.clone();
+        let mut input_curve = input_curve.clone();
+        choice_curve.set_callback(move |c| {
+            let edited = curve.borrow().with_name(&c.choice().unwrap_or_default());
+            update(&mut |d| d.curve = edited.clone());
+            show_curve(&edited, c, &mut lbl_curve_value, &mut input_curve);
+            *curve.borrow_mut() = edited;
+            preview.redraw();
+        });
+    }
+    {
+        let update = update.clone();
+        let curve = curve.clone();
+        let mut preview = preview.clone();
+        input_curve.set_callback(move |input| {
+            let value = match input.value().parse::<f32>() {
+                Ok(v) if v.is_finite() => v,
+                _ => return,
+            };
+            let edited = match &*curve.borrow() {
+                DialCurve::Power { .. } => DialCurve::Power { exponent: value.clamp(0.1, 10.0) },
+                // Typing 60 means 60 dB down
+                DialCurve::Db { .. } => DialCurve::Db { min_db: -value.abs().clamp(1.0, 120.0) },
+                _ => return,
+            };
+            update(&mut |d| d.curve = edited.clone());
+            *curve.borrow_mut() = edited;
+            preview.redraw();
+        });
     }
 
-    fn get_capture_devices_with_ids() -> Vec<(String, String)> {
-        Self::get_devices_with_ids(eCapture)
+    // Output limits and snap volumes are entered in percent
+    let percent_input = |value: &str, tooltip: &str| {
+        let mut input = Input::default();
+        style_input(&mut input);
+        input.set_value(value);
+        input.set_tooltip(tooltip);
+        input.set_trigger(fltk::enums::CallbackTrigger::Changed);
+        input
+    };
+    let mut row_output = Flex::default().row();
+    row_output.set_pad(10);
+    let lbl_output = section_label("Output %:");
+    let mut input_min = percent_input(&percent(dial.min_output), "Lowest volume the knob sets, so it never goes silent");
+    let lbl_to = section_label("to");
+    let mut input_max = percent_input(&percent(dial.max_output), "Highest volume the knob sets");
+    row_output.end();
+    row_output.fixed(&lbl_output, 100);
+    row_output.fixed(&lbl_to, 20);
+    for (input, is_max) in [(&mut input_min, false), (&mut input_max, true)] {
+        let update = update.clone();
+        input.set_callback(move |i| {
+            if let Some(&[volume]) = parse_percentages(&i.value()).as_deref() {
+                update(&mut |d| if is_max { d.max_output = volume } else { d.min_output = volume });
+            }
+        });
     }
 
-    fn get_com_ports() -> Vec<String> {
-        serialport::available_ports()
-            .unwrap_or_default()
-            .into_iter()
-            .map(|p| p.port_name)
-            .collect()
+    let mut row_snap = Flex::default().row();
+    row_snap.set_pad(10);
+    let lbl_snap = section_label("Snap to %:");
+    let mut input_snap = percent_input(
+        &dial.snap_to.iter().map(|v| percent(*v)).collect::<Vec<_>>().join(", "),
+        "Volumes the knob lands on when it comes close, e.g. 0, 50, 100",
+    );
+    let lbl_zone = section_label("within");
+    let mut input_zone = percent_input(&percent(dial.snap_zone), "How close the knob has to come to land on a snap volume");
+    row_snap.end();
+    row_snap.fixed(&lbl_snap, 100);
+    row_snap.fixed(&lbl_zone, 45);
+    row_snap.fixed(&input_zone, 45);
+    {
+        let update = update.clone();
+        input_snap.set_callback(move |i| {
+            if let Some(mut volumes) = parse_percentages(&i.value()) {
+                volumes.sort_by(f32::total_cmp);
+                volumes.dedup();
+                update(&mut |d| d.snap_to = volumes.clone());
+            }
+        });
+    }
+    {
+        let update = update.clone();
+        input_zone.set_callback(move |i| {
+            if let Some(&[zone]) = parse_percentages(&i.value()).as_deref() {
+                update(&mut |d| d.snap_zone = zone);
+            }
+        });
+    }
+
+    let mut row_filter = Flex::default().row();
+    row_filter.set_pad(10);
+    let lbl_filter = section_label("Filter:");
+    let mut choice_filter = Choice::default();
+    style_choice(&mut choice_filter);
+    for name in DialFilter::NAMES { choice_filter.add_choice(name); }
+    choice_filter.set_value(DialFilter::NAMES.iter().position(|n| *n == dial.filter.name()).unwrap_or(0) as i32);
+    choice_filter.set_tooltip(&dial.filter.describe());
+    {
+        let update = update.clone();
+        choice_filter.set_callback(move |c| {
+            let name = c.choice().unwrap_or_default();
+            let mut filter = DialFilter::None;
+            update(&mut |d| {
+                d.filter = d.filter.with_name(&name);
+                filter = d.filter;
+            });
+            c.set_tooltip(&filter.describe());
+        });
+    }
+    row_filter.end();
+    row_filter.fixed(&lbl_filter, 100);
+
+    // Deadband and hysteresis are volume changes from 0 to 1
+    let number_row = |label: &str, tooltip: &str, value: f32, set: fn(&mut DialConfig, f32)| {
+        let mut row = Flex::default().row();
+        row.set_pad(10);
+        let lbl = section_label(label);
+        let mut input = FloatInput::default();
+        style_input(&mut input);
+        input.set_value(&value.to_string());
+        input.set_tooltip(tooltip);
+        input.set_trigger(fltk::enums::CallbackTrigger::Changed);
+        let update = update.clone();
+        input.set_callback(move |i| {
+            if let Ok(value) = i.value().parse::<f32>() {
+                update(&mut |d| set(d, value.clamp(0.0, 1.0)));
+            }
+        });
+        row.end();
+        row.fixed(&lbl, 100);
+        row
+    };
+    let row_deadband = number_row("Deadband:", "Smallest volume change passed on, 0.005 is half a percent", dial.deadband, |d, v| d.deadband = v);
+    let row_hysteresis = number_row("Hysteresis:", "How far the knob has to go to turn back, so a wobbling reading doesn't flicker", dial.hysteresis, |d, v| d.hysteresis = v);
+    if midi {
+        // MIDI knobs only send when they move, they are never filtered
+        choice_filter.deactivate();
+    }
+
+    let mut check_pickup = CheckButton::default().with_label("Pick up: wait until the knob reaches the volume");
+    check_pickup.set_label_color(TEXT_COLOR);
+    check_pickup.set_value(dial.pickup);
+    check_pickup.clear_visible_focus();
+    check_pickup.set_tooltip("Keeps the knob from yanking a volume changed elsewhere to where the knob is.\nThe OSD asks you to move it until it gets there.");
+    {
+        let update = update.clone();
+        check_pickup.set_callback(move |b| {
+            let pickup = b.value();
+            update(&mut |d| d.pickup = pickup);
+        });
+    }
+
+    let mut row_btns = Flex::default().row();
+    Frame::default();
+    let mut btn_close = Button::default().with_label("Close");
+    style_widget(&mut btn_close);
+    btn_close.set_color(ACCENT_COLOR);
+    btn_close.set_selection_color(ACCENT_HOVER);
+    btn_close.set_label_font(Font::HelveticaBold);
+    row_btns.end();
+    row_btns.fixed(&btn_close, 100);
+    col.end();
+    for row in [&row_match, &row_pattern, &row_fade, &row_curve, &row_output, &row_snap, &row_filter, &row_deadband, &row_hysteresis] { col.fixed(row, 30); }
+    col.fixed(&lbl_matches, 60);
+    col.fixed(&preview, 200);
+    col.fixed(&check_pickup, 30);
+    col.fixed(&row_btns, 35);
+    win.end();
+
+    let close = {
+        let sp = scroll_pack.clone();
+        let state = state.clone();
+        let choices = choices.clone();
+        move |w: &mut Window| {
+            w.hide();
+            app::delete_widget(w.clone());
+            // The knob's tooltip shows the new settings
+            schedule_controllers_refresh(&sp, &state, &choices);
+        }
+    };
+    {
+        let close = close.clone();
+        let win = win.clone();
+        btn_close.set_callback(move |_| close(&mut win.clone()));
     }
+    win.set_callback(close);
+    win.make_modal(true);
+    win.show();
 }
 
-struct Smoother { last_value: f32 }
-impl Smoother {
-    fn new() -> Self { Self { last_value: 0.0 } }
-    fn process(&mut self, new_val: f32) -> f32 {
-        let delta = new_val - self.last_value;
-        if delta.abs() >= 0.08 { self.last_value = new_val; return new_val; }
-        let smoothed = self.last_value + delta * 0.35;
-        self.last_value = smoothed;
-        smoothed
+const AUTO_PORT_LABEL: &str = "Auto-detect";
+
+const BAUD_RATES: [u32; 5] = [9600, 19200, 38400, 57600, 115200];
+
+/// Entries for the port dropdown and the one matching `serial`.
+fn port_choices(serial: &SerialConfig) -> (Vec<String>, String) {
+    let ports = detect::list_ports();
+    let mut selected = if serial.port == config::AUTO_PORT { AUTO_PORT_LABEL.to_string() } else { String::new() };
+    for entry in &ports {
+        let pinned = serial.usb.is_some() && serial.usb == entry.usb;
+        if pinned || (serial.usb.is_none() && entry.name == serial.port) {
+            selected = entry.label();
+        }
+    }
+    let mut labels = vec![AUTO_PORT_LABEL.to_string()];
+    labels.extend(ports.iter().map(|p| p.label()));
+    if selected.is_empty() {
+        // Keep an unplugged controller selectable, otherwise saving would silently switch ports
+        selected = format!("{} (not connected)", serial.port);
+        labels.push(selected.clone());
     }
+    (labels, selected)
 }
 
-fn switch_device(clean_name: &str) {
-    if clean_name == "None" || clean_name.is_empty() { return; }
-    println!("DEBUG: Attempting to switch output device to -> '{}'", clean_name);
+/// Applies a port dropdown entry. USB ports are pinned by their id so they survive replugging.
+fn apply_port_choice(serial: &mut SerialConfig, label: &str) {
+    if label == AUTO_PORT_LABEL {
+        serial.port = config::AUTO_PORT.to_string();
+        serial.usb = None;
+        return;
+    }
+    if let Some(entry) = detect::list_ports().into_iter().find(|p| p.label() == label) {
+        serial.port = entry.name;
+        serial.usb = entry.usb;
+    }
+}
 
-    let all_devices = AudioScanner::get_playback_devices_with_ids();
-    let match_result = all_devices.iter()
-        .find(|(name, _id)| name.to_lowercase().contains(&clean_name.to_lowercase()));
+fn show_notification(summary: &str, body: &str) {
+    let _ = notify_rust::Notification::new()
+        .appname("RVCI")
+        .summary(summary)
+        .body(body)
+        .show();
+}
 
-    if let Some((found_name, real_id)) = match_result {
-        println!("DEBUG: Found matching device: '{}' (ID: {})", found_name, real_id);
-        unsafe {
-            if let Ok(policy) = CoCreateInstance::<_, IPolicyConfig>(&CLSID_PolicyConfigClient, None, CLSCTX_ALL) {
-                let mut id_utf16: Vec<u16> = real_id.encode_utf16().collect();
-                id_utf16.push(0); 
-                let pcwstr_id = PCWSTR(id_utf16.as_ptr());
-
-                let _ = policy.SetDefaultEndpoint(pcwstr_id, eConsole);
-                let _ = policy.SetDefaultEndpoint(pcwstr_id, eMultimedia);
-                let _ = policy.SetDefaultEndpoint(pcwstr_id, eCommunications);
-                println!("DEBUG: Successfully switched to '{}'", found_name);
-            } else {
-                println!("ERROR: Failed to instantiate IPolicyConfig COM object.");
+/// Loads mapping.json, reporting each distinct error once. `None` means keep the current settings.
+fn load_config_logged(config_path: &Path, last_config_error: &mut String) -> Option<AppConfig> {
+    match config::load_config(config_path) {
+        Ok(config) => {
+            last_config_error.clear();
+            Some(config)
+        }
+        Err(e) => {
+            let msg = e.to_string();
+            if msg != *last_config_error {
+                println!("ERROR: Could not load {}: {}", config_path.display(), msg);
+                if e != ConfigError::Missing {
+                    show_notification("mapping.json has an error", &format!("{}\nRVCI keeps using the last working settings.", msg));
+                }
+                *last_config_error = msg;
             }
+            None
         }
-    } else {
-        println!("ERROR: Could not find playback device matching '{}'", clean_name);
     }
 }
 
-fn run_volume_logic_loop(config_path: PathBuf, osd_tx: app::Sender<(String, f32)>) {
-    let mut current_config_sig = String::new(); 
-    let mut smoothers: Vec<Smoother> = Vec::new();
+/// Watches mapping.json and runs one worker thread per controller, handing every new config to
+/// all of them. Workers whose controller disappeared from the list stop on their own.
+fn run_volume_logic_loop(config_path: PathBuf, gui_tx: app::Sender<EngineEvent>, recorder: Option<Arc<Recorder>>) {
+    let mut watcher = ConfigWatcher::new(&config_path);
+    let mut last_config_error = String::new();
+    let mut workers: Vec<Sender<AppConfig>> = Vec::new();
+    let ports = Arc::new(PortClaims::default());
+
     loop {
-        let config_result = File::open(&config_path).and_then(|f| {
-            serde_json::from_reader::<_, AppConfig>(BufReader::new(f))
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
-        });
-        
-        if let Ok(config) = config_result {
-            let new_sig = format!("{}{}", config.serial.port, config.serial.baud);
-            if new_sig != current_config_sig {
-                current_config_sig = new_sig;
-                println!("DEBUG: Serial configuration updated. Target: {} @ {}", config.serial.port, config.serial.baud);
-                smoothers = (0..config.dials.len()).map(|_| Smoother::new()).collect();
-            }
-             if let Err(e) = run_serial_processing(&config, &config_path, &mut smoothers, &osd_tx) {
-                println!("DEBUG: Serial Connection Error: {}. Retrying in 2 seconds...", e);
-                std::thread::sleep(Duration::from_secs(2));
-             }
+        let config = if workers.is_empty() || watcher.changed() {
+            load_config_logged(&config_path, &mut last_config_error)
         } else {
-            std::thread::sleep(Duration::from_secs(2));
+            None
+        };
+        if let Some(config) = config {
+            for worker in &workers {
+                let _ = worker.send(config.clone());
+            }
+            workers.truncate(config.controllers.len());
+            while workers.len() < config.controllers.len() {
+                let (tx, rx) = channel();
+                let index = workers.len();
+                let config = config.clone();
+                let recorder = recorder.clone();
+                let ports = Arc::clone(&ports);
+                std::thread::spawn(move || run_controller_loop(index, config, rx, gui_tx, recorder, ports));
+                workers.push(tx);
+            }
         }
+        std::thread::sleep(if workers.is_empty() { Duration::from_secs(2) } else { Duration::from_millis(100) });
     }
 }
 
-fn run_serial_processing(config: &AppConfig, config_path: &PathBuf, smoothers: &mut Vec<Smoother>, osd_tx: &app::Sender<(String, f32)>) -> Result<()> {
-    let port = serialport::new(&config.serial.port, config.serial.baud)
-        .timeout(Duration::from_millis(config.serial.timeout))
-        .open()
-        .context("Failed to open serial port")?;
-    
-    println!("DEBUG: Connected to serial port successfully.");
-    
-    let mut reader = BufReader::new(port);
-    let mut line_buf = String::new();
-    let mut last_update = Instant::now();
-    
-    let mut last_applied_values: Vec<f32> = vec![-1.0; config.dials.len()];
-
-    let mut pid_name_cache: HashMap<u32, String> = HashMap::new();
-    let mut mic_device_cache: HashMap<String, IAudioEndpointVolume> = HashMap::new();
-    let mut cache_counter = 0;
-
-    let mut process_map: HashSet<String> = HashSet::new();
-    for dial in &config.dials {
-        if let Some(name) = &dial.process_name { 
-            let mut clean_name = name.clone();
-            if clean_name.to_lowercase().ends_with(".exe") {
-                clean_name.truncate(clean_name.len() - 4);
+/// Owns one controller: its own audio backend, pipeline and serial connection.
+fn run_controller_loop(
+    index: usize,
+    config: AppConfig,
+    updates: Receiver<AppConfig>,
+    gui_tx: app::Sender<EngineEvent>,
+    recorder: Option<Arc<Recorder>>,
+    ports: Arc<PortClaims>,
+) {
+    let mut backend = audio::create_backend();
+    let mut events = |event: EngineEvent| gui_tx.send(event);
+    let mut pipeline = DialPipeline::new(config, index, backend.as_mut(), &mut events);
+
+    while let Some(controller) = pipeline.controller() {
+        let label = controller.label(index);
+        match &controller.transport {
+            TransportConfig::Serial => println!("DEBUG: [{}] Serial configuration updated. Target: {} @ {}", label, controller.serial.port, controller.serial.baud),
+            other => println!("DEBUG: [{}] Network configuration updated. Listening for {} on {}", label, other.name(), other.network().map(|(l, _)| l).unwrap_or_default()),
+        }
+        if let Err(e) = run_connection(&mut pipeline, &updates, recorder.as_deref(), &ports) {
+            println!("DEBUG: [{}] Connection Error: {}. Retrying in 2 seconds...", label, e);
+            // The port in the config may be the reason we can't connect, so pick up edits right away
+            match updates.recv_timeout(Duration::from_secs(2)) {
+                Ok(config) => pipeline.update_config(updates.try_iter().last().unwrap_or(config)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
             }
-            process_map.insert(clean_name.to_lowercase()); 
         }
     }
-    
-    unsafe { let _ = CoInitializeEx(None, COINIT_MULTITHREADED); }
-    let last_file_mod = std::fs::metadata(config_path).and_then(|m| m.modified()).ok();
+    println!("DEBUG: [Controller {}] Removed from mapping.json, stopping.", index + 1);
+}
+
+/// Reads controller lines until the connection settings change (returns `Ok`) or the transport
+/// fails. Any other config change is applied in place without reconnecting.
+fn run_connection(pipeline: &mut DialPipeline, updates: &Receiver<AppConfig>, recorder: Option<&Recorder>, ports: &Arc<PortClaims>) -> Result<()> {
+    let controller = match pipeline.controller() {
+        Some(c) => c.clone(),
+        None => return Ok(()),
+    };
+    let label = controller.label(pipeline.index());
+    let mut transport = transport::open(&controller, ports)?;
 
     loop {
-        if let Ok(meta) = std::fs::metadata(config_path) {
-            if let Ok(mod_time) = meta.modified() {
-                if Some(mod_time) != last_file_mod { return Ok(()); }
+        if let Some(config) = updates.try_iter().last() {
+            let old = pipeline.controller().cloned();
+            pipeline.update_config(config);
+            match (old, pipeline.controller()) {
+                (_, None) => return Ok(()),
+                (Some(old), Some(new)) if old != *new => {
+                    if old.needs_reconnect(new) {
+                        println!("DEBUG: [{}] Connection settings changed, reconnecting.", label);
+                        return Ok(());
+                    }
+                    if new.serial.timeout != old.serial.timeout {
+                        transport.set_timeout(Duration::from_millis(new.serial.timeout));
+                    }
+                    println!("DEBUG: [{}] Config reloaded without reconnecting.", label);
+                }
+                _ => {}
             }
         }
-        
-        line_buf.clear();
-        
-        match reader.read_line(&mut line_buf) {
-            Ok(bytes) if bytes > 0 => {
-                let line = line_buf.trim();
-                if line.is_empty() { continue; }
+
+        match transport.next_event()? {
+            TransportEvent::Connected(peer) => {
+                println!("DEBUG: [{}] Connected to {} successfully.", label, peer);
+                pipeline.reset_connection();
+                if let Some(request) = pipeline.handshake_request() {
+                    let _ = transport.send(request);
+                }
+            }
+            TransportEvent::Line(line) => {
+                if let Some(recorder) = recorder { recorder.record(pipeline.index(), &line); }
+                pipeline.handle_line(&line);
+            }
+            TransportEvent::Midi { control, value } => pipeline.handle_midi(control, value),
+            TransportEvent::Idle => std::thread::sleep(Duration::from_millis(10)),
+            TransportEvent::Finished => return Err(anyhow!("{} has nothing more to send", label)),
+        }
+        pipeline.poll();
+    }
+}
+
+// ==========================================
+// MODERN iOS-STYLE GUI RENDERING & THEME
+// ==========================================
+
+const WIDGET_BG: Color = Color::from_rgb(28, 28, 30);             // Secondary Grouped Background
+const WIDGET_HOVER: Color = Color::from_rgb(44, 44, 46);          // Tertiary Hover State
+const TEXT_COLOR: Color = Color::from_rgb(255, 255, 255);         // Pure White
+const ACCENT_COLOR: Color = Color::from_rgb(10, 132, 255);        // iOS Dark Mode Blue
+const ACCENT_HOVER: Color = Color::from_rgb(64, 156, 255);        // Lighter iOS Blue
+const DESTRUCTIVE_COLOR: Color = Color::from_rgb(255, 69, 58);    // iOS Red
+const DESTRUCTIVE_HOVER: Color = Color::from_rgb(255, 105, 97);   // Lighter iOS Red
+
+fn style_widget<W: WidgetExt>(w: &mut W) {
+    w.set_color(WIDGET_BG);
+    w.set_label_color(TEXT_COLOR);
+    w.set_frame(FrameType::RFlatBox); 
+    w.set_selection_color(WIDGET_HOVER);
+    w.clear_visible_focus(); 
+}
+
+fn style_choice(w: &mut Choice) {
+    w.set_color(WIDGET_BG);
+    w.set_text_color(TEXT_COLOR);
+    w.set_frame(FrameType::RFlatBox);
+    w.set_selection_color(WIDGET_HOVER);
+    w.clear_visible_focus();
+}
+
+fn load_tray_icon(filename: &str) -> Icon {
+    let path = get_exe_dir().join(filename);
+    if let Ok(img) = image::open(&path) {
+        let rgba = img.into_rgba8();
+        let (w, h) = rgba.dimensions();
+        if let Ok(icon) = Icon::from_rgba(rgba.into_raw(), w, h) { return icon; }
+    }
+    let (width, height) = (32, 32);
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for _ in 0..height { for _ in 0..width { rgba.extend_from_slice(&[255, 0, 0, 255]); } }
+    Icon::from_rgba(rgba, width, height).unwrap_or_else(|_| panic!("Icon error"))
+}
+
+const TRAY_OPEN_ID: &str = "open";
+const TRAY_RESTORE_ID: &str = "restore";
+const TRAY_QUIT_ID: &str = "quit";
+
+fn build_tray_icon() -> Result<TrayIcon> {
+    let tray_menu = Menu::new();
+    let _ = tray_menu.append(&MenuItem::with_id(TRAY_OPEN_ID, "Open Settings", true, None));
+    let _ = tray_menu.append(&MenuItem::with_id(TRAY_RESTORE_ID, "Restore Previous Config", true, None));
+    let _ = tray_menu.append(&MenuItem::with_id(TRAY_QUIT_ID, "Quit", true, None));
+
+    Ok(TrayIconBuilder::new()
+        .with_menu(Box::new(tray_menu))
+        .with_tooltip("RVCI")
+        .with_icon(load_tray_icon("rvci.ico"))
+        .build()?)
+}
+
+fn load_win_icon(filename: &str) -> Option<RgbImage> {
+    let path = get_exe_dir().join(filename);
+    if let Ok(img) = image::open(&path) {
+        let rgba = img.into_rgba8();
+        let (w, h) = rgba.dimensions();
+        return RgbImage::new(&rgba.into_raw(), w as i32, h as i32, fltk::enums::ColorDepth::Rgba8).ok();
+    }
+    None
+}
+
+fn style_input<W: InputExt>(w: &mut W) {
+    w.set_frame(FrameType::RFlatBox);
+    w.set_color(WIDGET_BG);
+    w.set_text_color(TEXT_COLOR);
+    w.set_cursor_color(Color::White);
+    w.set_selection_color(WIDGET_HOVER);
+}
+
+fn section_label(text: &str) -> Frame {
+    let mut lbl = Frame::default().with_label(text);
+    lbl.set_label_color(TEXT_COLOR);
+    lbl.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
+    lbl
+}
+
+/// Rebuilds the controller sections once the current callback has returned. Rows are deleted by
+/// the rebuild, so it can't run from inside one of their callbacks.
+fn schedule_controllers_refresh(scroll_pack: &Pack, state: &Arc<Mutex<AppConfig>>, choices: &MappingChoices) {
+    let mut sp = scroll_pack.clone();
+    let state = state.clone();
+    let choices = choices.clone();
+    app::add_timeout3(0.0, move |_| refresh_controllers_ui(&mut sp, &state, &choices));
+}
+
+/// One section per controller: name, serial port, max value and its knob rows. Every widget
+/// writes straight into `state`, Save then only has to write `state` to disk.
+fn refresh_controllers_ui(scroll_pack: &mut Pack, state: &Arc<Mutex<AppConfig>>, choices: &MappingChoices) {
+    let cfg = state.lock().unwrap().clone();
+    let active_processes = &choices.processes;
+    let capture_devices = &choices.capture_devices;
+
+    scroll_pack.clear(); 
+    scroll_pack.begin();
+    
+    let scroll_w = scroll_pack.w();
+    let label_w = 125;
+    let mut row_count = 0;
+
+    for (ci, controller) in cfg.controllers.iter().enumerate() {
+        let mut row_header = Flex::default().with_size(scroll_w, 40).row();
+        row_header.set_pad(10);
+        row_header.set_frame(FrameType::NoBox);
+        let mut input_name = Input::default();
+        style_input(&mut input_name);
+        input_name.set_text_font(Font::HelveticaBold);
+        input_name.set_value(&controller.label(ci));
+        input_name.set_trigger(fltk::enums::CallbackTrigger::Changed);
+        {
+            let state = state.clone();
+            input_name.set_callback(move |i| {
+                if let Some(c) = state.lock().unwrap().controllers.get_mut(ci) { c.name = i.value(); }
+            });
+        }
+        let mut btn_add_knob = Button::default().with_label("+ Add Knob");
+        style_widget(&mut btn_add_knob);
+        btn_add_knob.set_label_font(Font::HelveticaBold);
+        btn_add_knob.set_color(ACCENT_COLOR);
+        btn_add_knob.set_selection_color(ACCENT_HOVER);
+        {
+            let state = state.clone();
+            let sp = scroll_pack.clone();
+            let choices = choices.clone();
+            btn_add_knob.set_callback(move |_| {
+                if let Some(c) = state.lock().unwrap().controllers.get_mut(ci) {
+                    // Most likely the same kind of pot as the others
+                    let (raw_min, raw_max) = c.dials.last().map(|d| (d.raw_min, d.raw_max)).unwrap_or((0.0, config::DEFAULT_RAW_MAX));
+                    c.dials.push(DialConfig { raw_min, raw_max, ..DialConfig::default() });
+                }
+                schedule_controllers_refresh(&sp, &state, &choices);
+            });
+        }
+        let mut btn_remove = Button::default().with_label("Remove");
+        style_widget(&mut btn_remove);
+        btn_remove.set_color(DESTRUCTIVE_COLOR);
+        btn_remove.set_selection_color(DESTRUCTIVE_HOVER);
+        btn_remove.set_label_color(Color::White);
+        btn_remove.set_label_font(Font::HelveticaBold);
+        if cfg.controllers.len() < 2 { btn_remove.deactivate(); }
+        {
+            let state = state.clone();
+            let sp = scroll_pack.clone();
+            let choices = choices.clone();
+            btn_remove.set_callback(move |_| {
+                {
+                    let mut cfg = state.lock().unwrap();
+                    if ci < cfg.controllers.len() { cfg.controllers.remove(ci); }
+                }
+                schedule_controllers_refresh(&sp, &state, &choices);
+            });
+        }
+        row_header.end();
+        row_header.fixed(&btn_add_knob, 110);
+        row_header.fixed(&btn_remove, 80);
+
+        let mut row_serial = Flex::default().with_size(scroll_w, 40).row();
+        row_serial.set_pad(10);
+        row_serial.set_frame(FrameType::NoBox);
+        let lbl_port = section_label("Connection:");
+        let mut choice_transport = Choice::default();
+        style_choice(&mut choice_transport);
+        for name in TransportConfig::NAMES { choice_transport.add_choice(name); }
+        if let Some(idx) = TransportConfig::NAMES.iter().position(|&n| n == controller.transport.name()) {
+            choice_transport.set_value(idx as i32);
+        }
+        {
+            let state = state.clone();
+            let sp = scroll_pack.clone();
+            let choices = choices.clone();
+            choice_transport.set_callback(move |c| {
+                if let (Some(name), Some(ctrl)) = (c.choice(), state.lock().unwrap().controllers.get_mut(ci)) {
+                    ctrl.transport = ctrl.transport.with_name(&name);
+                    // MIDI controls go from 0 to 127
+                    if matches!(ctrl.transport, TransportConfig::Midi { .. }) {
+                        for dial in ctrl.dial_lists_mut().flatten() {
+                            dial.raw_min = 0.0;
+                            dial.raw_max = 127.0;
+                        }
+                    }
+                }
+                schedule_controllers_refresh(&sp, &state, &choices);
+            });
+        }
+        if let Some((listen, token)) = controller.transport.network() {
+            let mut input_listen = Input::default();
+            style_input(&mut input_listen);
+            input_listen.set_value(listen);
+            input_listen.set_tooltip("Address and port to listen on, e.g. 0.0.0.0:5000");
+            input_listen.set_trigger(fltk::enums::CallbackTrigger::Changed);
+            let mut input_token = Input::default();
+            style_input(&mut input_token);
+            input_token.set_value(token.unwrap_or_default());
+            input_token.set_tooltip("Pairing token the controller sends as AUTH <token>. Empty accepts any controller");
+            input_token.set_trigger(fltk::enums::CallbackTrigger::Changed);
+            let mut btn_token = Button::default().with_label("New Token");
+            style_widget(&mut btn_token);
+            let set_network = {
+                let state = state.clone();
+                move |listen: String, token: String| {
+                    if let Some(ctrl) = state.lock().unwrap().controllers.get_mut(ci) {
+                        let token = Some(token.trim().to_string()).filter(|t| !t.is_empty());
+                        ctrl.transport = ctrl.transport.with_network(listen.trim().to_string(), token);
+                    }
+                }
+            };
+            {
+                let set_network = set_network.clone();
+                let input_token = input_token.clone();
+                input_listen.set_callback(move |i| set_network(i.value(), input_token.value()));
+            }
+            {
+                let set_network = set_network.clone();
+                let input_listen = input_listen.clone();
+                input_token.set_callback(move |i| set_network(input_listen.value(), i.value()));
+            }
+            {
+                let mut input_token = input_token.clone();
+                let input_listen = input_listen.clone();
+                btn_token.set_callback(move |_| match transport::generate_token() {
+                    Ok(token) => {
+                        input_token.set_value(&token);
+                        set_network(input_listen.value(), input_token.value());
+                    }
+                    Err(e) => dialog::alert_default(&format!("Could not make a token: {}", e)),
+                });
+            }
+            row_serial.fixed(&input_listen, 150);
+            row_serial.fixed(&btn_token, 95);
+        } else if let TransportConfig::Midi { port } = &controller.transport {
+            let mut choice_midi = Choice::default();
+            style_choice(&mut choice_midi);
+            let mut ports = choices.midi_ports.clone();
+            if !cfg!(windows) { ports.insert(0, VIRTUAL_MIDI_LABEL.to_string()); }
+            if !port.is_empty() && !ports.iter().any(|p| p.contains(port.as_str())) { ports.push(port.clone()); }
+            let selected = if port.is_empty() { VIRTUAL_MIDI_LABEL } else { port.as_str() };
+            populate_choice(&mut choice_midi, &ports, selected, false);
+            {
+                let state = state.clone();
+                choice_midi.set_callback(move |c| {
+                    if let (Some(label), Some(ctrl)) = (c.choice(), state.lock().unwrap().controllers.get_mut(ci)) {
+                        let port = if label == VIRTUAL_MIDI_LABEL { String::new() } else { label };
+                        ctrl.transport = TransportConfig::Midi { port };
+                    }
+                });
+            }
+        } else {
+            let mut choice_port = Choice::default();
+            style_choice(&mut choice_port);
+            let (ports, selected_port) = port_choices(&controller.serial);
+            populate_choice(&mut choice_port, &ports, &selected_port, false);
+            {
+                let state = state.clone();
+                choice_port.set_callback(move |c| {
+                    if let (Some(label), Some(ctrl)) = (c.choice(), state.lock().unwrap().controllers.get_mut(ci)) {
+                        apply_port_choice(&mut ctrl.serial, &label);
+                    }
+                });
+            }
+            let mut choice_baud = Choice::default();
+            style_choice(&mut choice_baud);
+            for baud in BAUD_RATES { choice_baud.add_choice(&baud.to_string()); }
+            if let Some(idx) = BAUD_RATES.iter().position(|&x| x == controller.serial.baud) {
+                choice_baud.set_value(idx as i32);
+            }
+            {
+                let state = state.clone();
+                choice_baud.set_callback(move |c| {
+                    if let (Some(&baud), Some(ctrl)) = (BAUD_RATES.get(c.value().max(0) as usize), state.lock().unwrap().controllers.get_mut(ci)) {
+                        ctrl.serial.baud = baud;
+                    }
+                });
+            }
+            row_serial.fixed(&choice_baud, 95);
+        }
+        row_serial.end();
+        row_serial.fixed(&lbl_port, label_w);
+        row_serial.fixed(&choice_transport, 110);
+
+        let mut row_range = Flex::default().with_size(scroll_w, 40).row();
+        row_range.set_pad(10);
+        row_range.set_frame(FrameType::NoBox);
+        let lbl_range = section_label("Knob Range:");
+        let mut btn_calibrate = Button::default().with_label("Calibrate");
+        style_widget(&mut btn_calibrate);
+        btn_calibrate.set_tooltip("Measure where each pot ends by turning every knob fully left and right");
+        btn_calibrate.set_callback(move |_| {
+            if engine::calibrating_controller().is_none() {
+                TUNING_FILTERS.store(false, Ordering::Relaxed);
+                engine::set_calibrating_controller(Some(ci));
+            }
+        });
+        let mut btn_tune = Button::default().with_label("Tune Filters");
+        style_widget(&mut btn_tune);
+        btn_tune.set_tooltip("Pick each pot's filter from how much it wobbles while nobody touches it");
+        btn_tune.set_callback(move |_| {
+            if engine::calibrating_controller().is_none() {
+                TUNING_FILTERS.store(true, Ordering::Relaxed);
+                engine::set_calibrating_controller(Some(ci));
+            }
+        });
+        let mut lbl_ranges = Frame::default().with_label(&range_summary(&controller.dials));
+        lbl_ranges.set_label_color(TEXT_COLOR);
+        lbl_ranges.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
+        row_range.end();
+        row_range.fixed(&lbl_range, label_w);
+        row_range.fixed(&btn_calibrate, 95);
+        row_range.fixed(&btn_tune, 110);
+        row_count += 3;
+
+        for (i, dial) in controller.dials.iter().enumerate() {
+            let mut row = Flex::default().with_size(scroll_w, 40).row(); 
+            row.set_pad(10);
+            row.set_frame(FrameType::NoBox); 
+            
+            let mut lbl = Frame::default().with_label(&format!("{}:", i + 1));
+            lbl.set_label_color(TEXT_COLOR);
+            lbl.set_label_font(Font::HelveticaBold); 
+            lbl.set_tooltip(&format!("Reads {:.0} to {:.0}", dial.raw_min, dial.raw_max));
+            
+            let mut choice_type = Choice::default();
+            style_choice(&mut choice_type);
+            for t in DialType::ALL { choice_type.add_choice(t.label()); }
+            choice_type.set_value(dial.dial_type.index());
+            
+            let mut choice_proc = Choice::default();
+            style_choice(&mut choice_proc);
+
+            let mut available_choices = match dial.dial_type {
+                DialType::Process | DialType::Crossfade => active_processes.to_vec(),
+                DialType::Microphone => capture_devices.to_vec(),
+                _ => Vec::new(),
+            };
+
+            if let Some(pname) = &dial.process_name {
+                let mut clean_pname = pname.clone();
+                if clean_pname.to_lowercase().ends_with(".exe") {
+                    clean_pname.truncate(clean_pname.len() - 4);
+                }
+                if !clean_pname.is_empty() && clean_pname != "None" && !available_choices.contains(&clean_pname) {
+                    available_choices.push(clean_pname.clone());
+                }
+            }
+            available_choices.sort();
+            available_choices.insert(0, "None".to_string());
+
+            for p in &available_choices { choice_proc.add_choice(p); }
+
+            if dial.dial_type.has_target() {
+                choice_proc.activate();
+                let mut target = dial.process_name.clone().unwrap_or_else(|| "None".to_string());
+                if target.to_lowercase().ends_with(".exe") {
+                    target.truncate(target.len() - 4);
+                }
                 
-                if line == "WORKS 1" {
-                    switch_device(&config.work_device_1);
-                    continue; 
-                } else if line == "WORKS 2" {
-                    switch_device(&config.work_device_2);
-                    continue;
+                if let Some(idx) = available_choices.iter().position(|x| x.to_lowercase() == target.to_lowercase()) {
+                    choice_proc.set_value(idx as i32);
+                } else {
+                    choice_proc.set_value(0); 
                 }
+            } else {
+                choice_proc.deactivate();
+                choice_proc.set_color(Color::from_rgb(20, 20, 22)); 
+                choice_proc.set_value(0); 
+            }
 
-                if last_update.elapsed() < Duration::from_millis(25) { continue; }
-                last_update = Instant::now();
+            {
+                let state = state.clone();
+                choice_proc.set_callback(move |c| {
+                    if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                        d.process_name = c.choice().filter(|val| val != "None");
+                    }
+                });
+            }
+
+            // Wildcards, regexes and paths are typed in instead of picked from the running apps
+            let typed_names = dial.dial_type.matches_processes() && dial.match_rule != MatchRule::Name;
+            if typed_names {
+                choice_proc.hide();
+                let mut input = Input::default();
+                style_input(&mut input);
+                input.set_value(dial.process_name.as_deref().unwrap_or_default());
+                input.set_tooltip(pattern_help(dial.match_rule));
+                input.set_trigger(fltk::enums::CallbackTrigger::Changed);
+                let state = state.clone();
+                input.set_callback(move |input| {
+                    if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                        d.process_name = Some(input.value().trim().to_string()).filter(|p| !p.is_empty());
+                    }
+                });
+            }
 
-                cache_counter += 1;
-                if cache_counter > 200 {
-                    pid_name_cache.clear();
-                    mic_device_cache.clear(); 
-                    cache_counter = 0;
+            let mut btn_add_target = Button::default().with_label("+");
+            style_widget(&mut btn_add_target);
+            btn_add_target.set_tooltip("Turn another process with this knob");
+            if dial.dial_type != DialType::Process { btn_add_target.deactivate(); }
+            {
+                let state = state.clone();
+                let sp = scroll_pack.clone();
+                let choices = choices.clone();
+                btn_add_target.set_callback(move |_| {
+                    if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                        d.targets.push(ProcessTarget { process_name: String::new(), gain: 1.0 });
+                    }
+                    schedule_controllers_refresh(&sp, &state, &choices);
+                });
+            }
+
+            let mut cp_clone = choice_proc.clone();
+            let mut add_target_clone = btn_add_target.clone();
+            let sp_type = scroll_pack.clone();
+            let choices_type = choices.clone();
+            let active_procs_clone = active_processes.to_vec();
+            let capture_devices_clone = capture_devices.to_vec();
+            let state_type = state.clone();
+            let was_crossfade = dial.dial_type == DialType::Crossfade;
+            
+            choice_type.set_callback(move |c| {
+                let selected = DialType::from_index(c.value());
+                if selected.matches_processes() {
+                    cp_clone.activate();
+                    cp_clone.set_color(WIDGET_BG);
+                    cp_clone.clear();
+                    cp_clone.add_choice("None");
+                    for p in &active_procs_clone { cp_clone.add_choice(p); }
+                    if cp_clone.value() < 0 { cp_clone.set_value(0); }
+                } else if selected == DialType::Microphone {
+                    cp_clone.activate();
+                    cp_clone.set_color(WIDGET_BG);
+                    cp_clone.clear();
+                    cp_clone.add_choice("None");
+                    for p in &capture_devices_clone { cp_clone.add_choice(p); }
+                    if cp_clone.value() < 0 { cp_clone.set_value(0); }
+                } else {
+                    cp_clone.deactivate();
+                    cp_clone.set_color(Color::from_rgb(20, 20, 22));
+                    cp_clone.set_value(0);
+                }
+                if selected == DialType::Process { add_target_clone.activate(); } else { add_target_clone.deactivate(); }
+                let mut had_targets = false;
+                if let Some(d) = state_type.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                    d.dial_type = selected;
+                    d.process_name = None;
+                    had_targets = !d.targets.is_empty();
+                    d.targets.clear();
+                    d.match_rule = MatchRule::Name;
+                    d.fade_to = None;
+                }
+                // Rebuilt so typed names go back to the dropdown and a crossfade gets its second row
+                let crossfade_changed = (selected == DialType::Crossfade) != was_crossfade;
+                if had_targets || typed_names || crossfade_changed {
+                    schedule_controllers_refresh(&sp_type, &state_type, &choices_type);
+                }
+            });
+
+            // MIDI knobs are bound to a control instead of being a pot or an encoder
+            let input_widget = if let TransportConfig::Midi { .. } = controller.transport {
+                let learning = *MIDI_LEARN.lock().unwrap() == Some((ci, i));
+                let label = if learning { "Move it...".to_string() } else { dial.midi.map(MidiControl::label).unwrap_or_else(|| "Learn".to_string()) };
+                let mut btn_learn = Button::default().with_label(&label);
+                style_widget(&mut btn_learn);
+                if learning { btn_learn.set_color(ACCENT_COLOR); }
+                btn_learn.set_tooltip("Click, then move a control on the MIDI controller (save first so RVCI listens to it).\nRight-click to unbind.");
+                {
+                    let state = state.clone();
+                    let sp = scroll_pack.clone();
+                    let choices = choices.clone();
+                    btn_learn.set_callback(move |_| {
+                        let mut learn = MIDI_LEARN.lock().unwrap();
+                        if app::event_mouse_button() == app::MouseButton::Right {
+                            if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                                d.midi = None;
+                            }
+                            *learn = None;
+                        } else {
+                            *learn = if *learn == Some((ci, i)) { None } else { Some((ci, i)) };
+                        }
+                        drop(learn);
+                        schedule_controllers_refresh(&sp, &state, &choices);
+                    });
+                }
+                btn_learn.as_base_widget()
+            } else {
+                let mut choice_input = Choice::default();
+                style_choice(&mut choice_input);
+                choice_input.add_choice("Pot");
+                choice_input.add_choice("Encoder");
+                choice_input.set_value(if dial.input.is_encoder() { 1 } else { 0 });
+                {
+                    let state = state.clone();
+                    choice_input.set_callback(move |c| {
+                        if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                            // Picking Encoder again keeps a hand-tuned step size
+                            if c.value() == 1 && !d.input.is_encoder() {
+                                d.input = DialInput::encoder();
+                            } else if c.value() == 0 {
+                                d.input = DialInput::Pot;
+                            }
+                        }
+                    });
                 }
+                choice_input.as_base_widget()
+            };
+
+            let mut check_inv = CheckButton::default().with_label("Inv");
+            check_inv.set_color(WIDGET_BG); 
+            check_inv.set_label_color(TEXT_COLOR);
+            check_inv.set_value(dial.inverted);
+            check_inv.clear_visible_focus();
+            {
+                let state = state.clone();
+                check_inv.set_callback(move |b| {
+                    if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                        d.inverted = b.value();
+                    }
+                });
+            }
+
+            let mut btn_more = Button::default().with_label("...");
+            style_widget(&mut btn_more);
+            btn_more.set_tooltip(&knob_summary(dial));
+            {
+                let state = state.clone();
+                let sp = scroll_pack.clone();
+                let choices = choices.clone();
+                btn_more.set_callback(move |_| show_knob_settings(&sp, &state, &choices, ci, i));
+            }
 
-                let parts: Vec<&str> = line.split('|').collect();
-                if parts.len() != config.dials.len() { continue; }
-
-                for (i, part) in parts.iter().enumerate() {
-                    if let Ok(raw_val) = part.parse::<f32>() {
-                        let dial_cfg = &config.dials[i];
-                        
-                        let mut normalized = raw_val.clamp(0.0, config.value_max) / config.value_max;
-                        
-                        if dial_cfg.inverted {
-                            normalized = 1.0 - normalized;
+            let mut btn_del = Button::default().with_label("X");
+            style_widget(&mut btn_del);
+            btn_del.set_color(DESTRUCTIVE_COLOR);
+            btn_del.set_selection_color(DESTRUCTIVE_HOVER);
+            btn_del.set_label_color(Color::White);
+            btn_del.set_label_font(Font::HelveticaBold);
+            
+            row.end();
+            
+            row.fixed(&lbl, 25);
+            row.fixed(&btn_add_target, 35);
+            row.fixed(&input_widget, 95);
+            row.fixed(&check_inv, 45);
+            row.fixed(&btn_more, 35);
+            row.fixed(&btn_del, 35);
+            
+            {
+                let state = state.clone();
+                let sp = scroll_pack.clone();
+                let choices = choices.clone();
+                btn_del.set_callback(move |_| {
+                    if let Some(c) = state.lock().unwrap().controllers.get_mut(ci) {
+                        if i < c.dials.len() { c.dials.remove(i); }
+                    }
+                    schedule_controllers_refresh(&sp, &state, &choices);
+                });
+            }
+            row_count += 1;
+
+            // The side a crossfade knob fades to, under the side it fades from
+            if dial.dial_type == DialType::Crossfade {
+                let mut row = Flex::default().with_size(scroll_w, 40).row();
+                row.set_pad(10);
+                row.set_frame(FrameType::NoBox);
+
+                let mut lbl = Frame::default().with_label("to");
+                lbl.set_label_color(TEXT_COLOR);
+                Frame::default();
+
+                let fade_to = dial.fade_to.clone().unwrap_or_default();
+                if typed_names {
+                    let mut input_fade = Input::default();
+                    style_input(&mut input_fade);
+                    input_fade.set_value(&fade_to);
+                    input_fade.set_tooltip(pattern_help(dial.match_rule));
+                    input_fade.set_trigger(fltk::enums::CallbackTrigger::Changed);
+                    let state = state.clone();
+                    input_fade.set_callback(move |input| {
+                        if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                            d.fade_to = Some(input.value().trim().to_string()).filter(|p| !p.is_empty());
                         }
+                    });
+                } else {
+                    let mut choice_fade = Choice::default();
+                    style_choice(&mut choice_fade);
+                    choice_fade.set_tooltip("The apps turned up as the knob turns right, while the ones above are turned down");
+                    let name = audio::clean_process_name(&fade_to);
+                    let mut processes = active_processes.to_vec();
+                    if !name.is_empty() && !processes.contains(&name) { processes.push(name.clone()); }
+                    processes.sort();
+                    populate_choice(&mut choice_fade, &processes, if name.is_empty() { "None" } else { &name }, true);
+                    let state = state.clone();
+                    choice_fade.set_callback(move |c| {
+                        if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                            d.fade_to = c.choice().filter(|v| v != "None");
+                        }
+                    });
+                }
+                let spacer = Frame::default();
+                row.end();
+                row.fixed(&lbl, 25);
+                row.fixed(&spacer, 35 + 10 + 95 + 10 + 45 + 10 + 35 + 10 + 35);
+                row_count += 1;
+            }
 
-                        if config.use_logarithmic_scale {
-                            normalized = normalized.powf(3.0);
-                        }
-                        
-                        if i >= smoothers.len() { smoothers.push(Smoother::new()); }
-                        if i >= last_applied_values.len() { last_applied_values.push(-1.0); }
-
-                        let smoothed = smoothers[i].process(normalized);
-                        
-                        if (smoothed - last_applied_values[i]).abs() < 0.005 {
-                            continue;
+            // One row per extra process, under the knob's own process
+            for (ti, target) in dial.targets.iter().enumerate() {
+                let mut row = Flex::default().with_size(scroll_w, 40).row();
+                row.set_pad(10);
+                row.set_frame(FrameType::NoBox);
+
+                let mut lbl = Frame::default().with_label("and");
+                lbl.set_label_color(TEXT_COLOR);
+                Frame::default();
+
+                if typed_names {
+                    let mut input_target = Input::default();
+                    style_input(&mut input_target);
+                    input_target.set_value(&target.process_name);
+                    input_target.set_tooltip(pattern_help(dial.match_rule));
+                    input_target.set_trigger(fltk::enums::CallbackTrigger::Changed);
+                    let state = state.clone();
+                    input_target.set_callback(move |input| {
+                        if let Some(t) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)).and_then(|d| d.targets.get_mut(ti)) {
+                            t.process_name = input.value().trim().to_string();
                         }
-                        
-                        last_applied_values[i] = smoothed;
-                        
-                        let target_lbl = dial_cfg.process_name.as_deref().unwrap_or("Unassigned");
-                        println!("DEBUG: [Knob {}] {} ({}) -> {:.3}", i + 1, dial_cfg.dial_type, target_lbl, smoothed);
-
-                        if config.enable_osd {
-                            let display_name = match dial_cfg.dial_type.as_str() {
-                                "system" => "Master Volume".to_string(),
-                                "all_others" => "Other Apps".to_string(),
-                                _ => {
-                                    let mut clean = target_lbl.to_string();
-                                    if clean.to_lowercase().ends_with(".exe") {
-                                        clean.truncate(clean.len() - 4);
-                                    }
-                                    clean
-                                }
-                            };
-                            if display_name != "None" && display_name != "Unassigned" {
-                                osd_tx.send((display_name, smoothed));
+                    });
+                } else {
+                    let mut choice_target = Choice::default();
+                    style_choice(&mut choice_target);
+                    let name = audio::clean_process_name(&target.process_name);
+                    let mut processes = active_processes.to_vec();
+                    if !name.is_empty() && !processes.contains(&name) { processes.push(name.clone()); }
+                    processes.sort();
+                    populate_choice(&mut choice_target, &processes, if name.is_empty() { "None" } else { &name }, true);
+                    let state = state.clone();
+                    let sp = scroll_pack.clone();
+                    let choices = choices.clone();
+                    choice_target.set_callback(move |c| {
+                        let selected = c.choice().filter(|v| v != "None");
+                        let mut removed = false;
+                        if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                            match selected {
+                                Some(name) if ti < d.targets.len() => d.targets[ti].process_name = name,
+                                None if ti < d.targets.len() => { d.targets.remove(ti); removed = true; }
+                                _ => {}
                             }
                         }
+                        if removed { schedule_controllers_refresh(&sp, &state, &choices); }
+                    });
+                }
 
-                        unsafe {
-                            match dial_cfg.dial_type.as_str() {
-                                "system" => {
-                                    if let Ok(vol) = AudioController::get_system_volume() {
-                                        let _ = vol.SetMasterVolumeLevelScalar(smoothed, std::ptr::null());
-                                    }
-                                },
-                                "microphone" => {
-                                    if let Some(target) = &dial_cfg.process_name {
-                                        if target != "None" {
-                                            let vol_opt = mic_device_cache.get(target).cloned().or_else(|| {
-                                                if let Ok(v) = AudioController::get_mic_volume(target) {
-                                                    mic_device_cache.insert(target.clone(), v.clone());
-                                                    Some(v)
-                                                } else {
-                                                    None
-                                                }
-                                            });
-                                            if let Some(vol) = vol_opt {
-                                                let _ = vol.SetMasterVolumeLevelScalar(smoothed, std::ptr::null());
-                                            }
-                                        }
-                                    }
-                                },
-                                "process" | "all_others" => {
-                                    if let Ok(mgr) = AudioController::get_session_manager() {
-                                        if let Ok(enum_sess) = mgr.GetSessionEnumerator() {
-                                            if let Ok(count) = enum_sess.GetCount() {
-                                                for s_idx in 0..count {
-                                                    if let Ok(sess) = enum_sess.GetSession(s_idx) {
-                                                        if let Ok(s2) = Interface::cast::<IAudioSessionControl2>(&sess) {
-                                                            if let Ok(pid) = s2.GetProcessId() {
-                                                                if pid == 0 { continue; }
-                                                                
-                                                                let pname = pid_name_cache.entry(pid).or_insert_with(|| {
-                                                                    AudioController::get_process_name(pid)
-                                                                });
-
-                                                                let should_change = if dial_cfg.dial_type == "all_others" {
-                                                                    !process_map.contains(&pname.to_lowercase())
-                                                                } else {
-                                                                    match &dial_cfg.process_name {
-                                                                        Some(target) => {
-                                                                            let mut clean_target = target.clone();
-                                                                            if clean_target.to_lowercase().ends_with(".exe") {
-                                                                                clean_target.truncate(clean_target.len() - 4);
-                                                                            }
-                                                                            pname.to_lowercase() == clean_target.to_lowercase()
-                                                                        },
-                                                                        None => false,
-                                                                    }
-                                                                };
-
-                                                                if should_change {
-                                                                    if let Ok(simple_vol) = Interface::cast::<ISimpleAudioVolume>(&sess) {
-                                                                        let _ = simple_vol.SetMasterVolume(smoothed, std::ptr::null());
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                },
-                                _ => {}
+                let lbl_gain = section_label("Gain %");
+                let mut input_gain = FloatInput::default();
+                style_input(&mut input_gain);
+                input_gain.set_value(&((target.gain * 1000.0).round() / 10.0).to_string());
+                input_gain.set_tooltip("Volume of this process next to the knob's, from 0 to 100%");
+                input_gain.set_trigger(fltk::enums::CallbackTrigger::Changed);
+                {
+                    let state = state.clone();
+                    input_gain.set_callback(move |input| {
+                        if let Ok(percent) = input.value().parse::<f32>() {
+                            if let Some(t) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)).and_then(|d| d.targets.get_mut(ti)) {
+                                t.gain = (percent / 100.0).clamp(0.0, 1.0);
                             }
                         }
-                    }
+                    });
+                }
+                let spacer = Frame::default();
+
+                let mut btn_del = Button::default().with_label("X");
+                style_widget(&mut btn_del);
+                btn_del.set_color(DESTRUCTIVE_COLOR);
+                btn_del.set_selection_color(DESTRUCTIVE_HOVER);
+                btn_del.set_label_color(Color::White);
+                btn_del.set_label_font(Font::HelveticaBold);
+                btn_del.set_tooltip("Stop turning this process");
+                {
+                    let state = state.clone();
+                    let sp = scroll_pack.clone();
+                    let choices = choices.clone();
+                    btn_del.set_callback(move |_| {
+                        if let Some(d) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.dials.get_mut(i)) {
+                            if ti < d.targets.len() { d.targets.remove(ti); }
+                        }
+                        schedule_controllers_refresh(&sp, &state, &choices);
+                    });
                 }
-            },
-            _ => {
-                std::thread::sleep(Duration::from_millis(10));
-                continue;
+                row.end();
+                row.fixed(&lbl, 25);
+                row.fixed(&lbl_gain, 35 + 10 + 35);
+                row.fixed(&input_gain, 50);
+                row.fixed(&spacer, 45 + 10 + 35);
+                row.fixed(&btn_del, 35);
+                row_count += 1;
             }
         }
-    }
-}
-
-// ==========================================
-// MODERN iOS-STYLE GUI RENDERING & THEME
-// ==========================================
-
-const WIDGET_BG: Color = Color::from_rgb(28, 28, 30);             // Secondary Grouped Background
-const WIDGET_HOVER: Color = Color::from_rgb(44, 44, 46);          // Tertiary Hover State
-const TEXT_COLOR: Color = Color::from_rgb(255, 255, 255);         // Pure White
-const ACCENT_COLOR: Color = Color::from_rgb(10, 132, 255);        // iOS Dark Mode Blue
-const ACCENT_HOVER: Color = Color::from_rgb(64, 156, 255);        // Lighter iOS Blue
-const DESTRUCTIVE_COLOR: Color = Color::from_rgb(255, 69, 58);    // iOS Red
-const DESTRUCTIVE_HOVER: Color = Color::from_rgb(255, 105, 97);   // Lighter iOS Red
-
-fn style_widget<W: WidgetExt>(w: &mut W) {
-    w.set_color(WIDGET_BG);
-    w.set_label_color(TEXT_COLOR);
-    w.set_frame(FrameType::RFlatBox); 
-    w.set_selection_color(WIDGET_HOVER);
-    w.clear_visible_focus(); 
-}
-
-fn style_choice(w: &mut Choice) {
-    w.set_color(WIDGET_BG);
-    w.set_text_color(TEXT_COLOR);
-    w.set_frame(FrameType::RFlatBox);
-    w.set_selection_color(WIDGET_HOVER);
-    w.clear_visible_focus();
-}
-
-fn load_tray_icon(filename: &str) -> Icon {
-    let path = get_exe_dir().join(filename);
-    if let Ok(img) = image::open(&path) {
-        let rgba = img.into_rgba8();
-        let (w, h) = rgba.dimensions();
-        if let Ok(icon) = Icon::from_rgba(rgba.into_raw(), w, h) { return icon; }
-    }
-    let (width, height) = (32, 32);
-    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
-    for _ in 0..height { for _ in 0..width { rgba.extend_from_slice(&[255, 0, 0, 255]); } }
-    Icon::from_rgba(rgba, width, height).unwrap_or_else(|_| panic!("Icon error"))
-}
-
-fn load_win_icon(filename: &str) -> Option<RgbImage> {
-    let path = get_exe_dir().join(filename);
-    if let Ok(img) = image::open(&path) {
-        let rgba = img.into_rgba8();
-        let (w, h) = rgba.dimensions();
-        return RgbImage::new(&rgba.into_raw(), w as i32, h as i32, fltk::enums::ColorDepth::Rgba8).ok();
-    }
-    None
-}
-
-fn extract_clean_name(full_name: &str) -> String {
-    if full_name == "None" { return "None".to_string(); }
-    if let (Some(start), Some(end)) = (full_name.find('('), full_name.rfind(')')) {
-        if start < end { return full_name[start+1..end].trim().to_string(); }
-    }
-    full_name.to_string()
-}
 
-fn refresh_knobs_ui(scroll_pack: &mut Pack, dials: &Vec<DialConfig>, active_processes: &[String], capture_devices: &[String]) {
-    scroll_pack.clear(); 
-    scroll_pack.begin();
-    
-    let scroll_w = scroll_pack.w();
-
-    for (i, dial) in dials.iter().enumerate() {
-        let mut row = Flex::default().with_size(scroll_w, 40).row(); 
-        row.set_pad(10);
-        row.set_frame(FrameType::NoBox); 
-        
-        let mut lbl = Frame::default().with_label(&format!("{}:", i + 1));
-        lbl.set_label_color(TEXT_COLOR);
-        lbl.set_label_font(Font::HelveticaBold); 
-        
-        let mut choice_type = Choice::default();
-        style_choice(&mut choice_type);
-        choice_type.add_choice("System|Process|Others|Microphone");
-        
-        let sel_idx = match dial.dial_type.as_str() { 
-            "process" => 1, 
-            "all_others" => 2, 
-            "microphone" => 3,
-            _ => 0 
-        };
-        choice_type.set_value(sel_idx);
-        
-        let mut choice_proc = Choice::default();
-        style_choice(&mut choice_proc);
+        let mut row_switch = Flex::default().with_size(scroll_w, 40).row();
+        row_switch.set_pad(10);
+        row_switch.set_frame(FrameType::NoBox);
+        let lbl_switch = section_label("Switch Positions:");
+        Frame::default();
+        let mut btn_add_position = Button::default().with_label("+ Add Position");
+        style_widget(&mut btn_add_position);
+        btn_add_position.set_label_font(Font::HelveticaBold);
+        btn_add_position.set_color(ACCENT_COLOR);
+        btn_add_position.set_selection_color(ACCENT_HOVER);
+        {
+            let state = state.clone();
+            let sp = scroll_pack.clone();
+            let choices = choices.clone();
+            btn_add_position.set_callback(move |_| {
+                if let Some(c) = state.lock().unwrap().controllers.get_mut(ci) {
+                    c.selector.push(SelectorPosition::default());
+                }
+                schedule_controllers_refresh(&sp, &state, &choices);
+            });
+        }
+        row_switch.end();
+        row_switch.fixed(&lbl_switch, label_w);
+        row_switch.fixed(&btn_add_position, 140);
+        row_count += 1;
+
+        for (pi, position) in controller.selector.iter().enumerate() {
+            // One row per output device, the ones after the first are fallbacks
+            let outputs = if position.outputs.is_empty() { vec![String::new()] } else { position.outputs.clone() };
+            for (oi, output) in outputs.iter().enumerate() {
+                let mut row = Flex::default().with_size(scroll_w, 40).row();
+                row.set_pad(10);
+                row.set_frame(FrameType::NoBox);
+
+                let mut lbl = Frame::default().with_label(&if oi == 0 { format!("{}:", pi + 1) } else { "or".to_string() });
+                lbl.set_label_color(TEXT_COLOR);
+                lbl.set_label_font(Font::HelveticaBold);
+
+                let mut choice_out = Choice::default();
+                style_choice(&mut choice_out);
+                choice_out.set_tooltip("Playback device. The first connected one in the list is used.");
+                let mut devices = choices.playback_devices.clone();
+                if !output.is_empty() && !devices.contains(output) { devices.push(output.clone()); }
+                populate_choice(&mut choice_out, &devices, if output.is_empty() { "None" } else { output }, true);
+                {
+                    let state = state.clone();
+                    let sp = scroll_pack.clone();
+                    let choices = choices.clone();
+                    choice_out.set_callback(move |c| {
+                        let selected = c.choice().filter(|v| v != "None");
+                        let mut removed = false;
+                        if let Some(p) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.selector.get_mut(pi)) {
+                            match selected {
+                                Some(name) if oi < p.outputs.len() => p.outputs[oi] = name,
+                                Some(name) => p.outputs.push(name),
+                                None if oi < p.outputs.len() => { p.outputs.remove(oi); removed = true; }
+                                None => {}
+                            }
+                        }
+                        if removed { schedule_controllers_refresh(&sp, &state, &choices); }
+                    });
+                }
 
-        let mut available_choices = match dial.dial_type.as_str() {
-            "process" => active_processes.to_vec(),
-            "microphone" => capture_devices.to_vec(),
-            _ => Vec::new(),
-        };
+                if oi == 0 {
+                    let mut choice_in = Choice::default();
+                    style_choice(&mut choice_in);
+                    choice_in.set_tooltip("Recording device to switch to as well");
+                    let mut inputs = choices.capture_devices.clone();
+                    let input = position.input.clone().unwrap_or_else(|| "None".to_string());
+                    if input != "None" && !inputs.contains(&input) { inputs.push(input.clone()); }
+                    populate_choice(&mut choice_in, &inputs, &input, true);
+                    {
+                        let state = state.clone();
+                        choice_in.set_callback(move |c| {
+                            if let Some(p) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.selector.get_mut(pi)) {
+                                p.input = c.choice().filter(|v| v != "None");
+                            }
+                        });
+                    }
 
-        if let Some(pname) = &dial.process_name {
-            let mut clean_pname = pname.clone();
-            if clean_pname.to_lowercase().ends_with(".exe") {
-                clean_pname.truncate(clean_pname.len() - 4);
-            }
-            if !clean_pname.is_empty() && clean_pname != "None" && !available_choices.contains(&clean_pname) {
-                available_choices.push(clean_pname.clone());
-            }
-        }
-        available_choices.sort();
-        available_choices.insert(0, "None".to_string());
+                    let mut btn_fallback = Button::default().with_label("+");
+                    style_widget(&mut btn_fallback);
+                    btn_fallback.set_tooltip("Add a fallback playback device");
+                    {
+                        let state = state.clone();
+                        let sp = scroll_pack.clone();
+                        let choices = choices.clone();
+                        btn_fallback.set_callback(move |_| {
+                            if let Some(p) = state.lock().unwrap().controllers.get_mut(ci).and_then(|c| c.selector.get_mut(pi)) {
+                                if p.outputs.is_empty() { p.outputs.push(String::new()); }
+                                p.outputs.push(String::new());
+                            }
+                            schedule_controllers_refresh(&sp, &state, &choices);
+                        });
+                    }
 
-        for p in &available_choices { choice_proc.add_choice(p); }
+                    let mut btn_test = Button::default().with_label("Test");
+                    style_widget(&mut btn_test);
+                    {
+                        let state = state.clone();
+                        btn_test.set_callback(move |_| {
+                            let devices = state.lock().unwrap().controllers.get(ci).and_then(|c| c.selector.get(pi)).cloned();
+                            if let Some(devices) = devices {
+                                engine::apply_selector_position(audio::create_backend().as_mut(), &devices);
+                            }
+                        });
+                    }
+                    row.fixed(&choice_in, 150);
+                    row.fixed(&btn_fallback, 35);
+                    row.fixed(&btn_test, 55);
+                } else {
+                    let spacer = Frame::default();
+                    row.fixed(&spacer, 150 + 10 + 35 + 10 + 55);
+                }
 
-        if dial.dial_type == "process" || dial.dial_type == "microphone" {
-            choice_proc.activate();
-            let mut target = dial.process_name.clone().unwrap_or_else(|| "None".to_string());
-            if target.to_lowercase().ends_with(".exe") {
-                target.truncate(target.len() - 4);
-            }
-            
-            if let Some(idx) = available_choices.iter().position(|x| x.to_lowercase() == target.to_lowercase()) {
-                choice_proc.set_value(idx as i32);
-            } else {
-                choice_proc.set_value(0); 
+                let mut btn_del = Button::default().with_label("X");
+                style_widget(&mut btn_del);
+                btn_del.set_color(DESTRUCTIVE_COLOR);
+                btn_del.set_selection_color(DESTRUCTIVE_HOVER);
+                btn_del.set_label_color(Color::White);
+                btn_del.set_label_font(Font::HelveticaBold);
+                btn_del.set_tooltip(if oi == 0 { "Remove this switch position" } else { "Remove this fallback" });
+                {
+                    let state = state.clone();
+                    let sp = scroll_pack.clone();
+                    let choices = choices.clone();
+                    btn_del.set_callback(move |_| {
+                        if let Some(c) = state.lock().unwrap().controllers.get_mut(ci) {
+                            if oi == 0 {
+                                if pi < c.selector.len() { c.selector.remove(pi); }
+                            } else if let Some(p) = c.selector.get_mut(pi) {
+                                if oi < p.outputs.len() { p.outputs.remove(oi); }
+                            }
+                        }
+                        schedule_controllers_refresh(&sp, &state, &choices);
+                    });
+                }
+                row.end();
+                row.fixed(&lbl, 25);
+                row.fixed(&btn_del, 35);
+                row_count += 1;
             }
-        } else {
-            choice_proc.deactivate();
-            choice_proc.set_color(Color::from_rgb(20, 20, 22)); 
-            choice_proc.set_value(0); 
         }
 
-        let mut cp_clone = choice_proc.clone();
-        let active_procs_clone = active_processes.to_vec();
-        let capture_devices_clone = capture_devices.to_vec();
-        
-        choice_type.set_callback(move |c| {
-            if c.value() == 1 { 
-                cp_clone.activate();
-                cp_clone.set_color(WIDGET_BG);
-                cp_clone.clear();
-                cp_clone.add_choice("None");
-                for p in &active_procs_clone { cp_clone.add_choice(p); }
-                if cp_clone.value() < 0 { cp_clone.set_value(0); }
-            } else if c.value() == 3 {
-                cp_clone.activate();
-                cp_clone.set_color(WIDGET_BG);
-                cp_clone.clear();
-                cp_clone.add_choice("None");
-                for p in &capture_devices_clone { cp_clone.add_choice(p); }
-                if cp_clone.value() < 0 { cp_clone.set_value(0); }
-            } else {
-                cp_clone.deactivate();
-                cp_clone.set_color(Color::from_rgb(20, 20, 22));
-                cp_clone.set_value(0);
-            }
-        });
-
-        let mut check_inv = CheckButton::default().with_label("Inv");
-        check_inv.set_color(WIDGET_BG); 
-        check_inv.set_label_color(TEXT_COLOR);
-        check_inv.set_value(dial.inverted);
-        check_inv.clear_visible_focus();
-
-        let mut btn_del = Button::default().with_label("X");
-        style_widget(&mut btn_del);
-        btn_del.set_color(DESTRUCTIVE_COLOR);
-        btn_del.set_selection_color(DESTRUCTIVE_HOVER);
-        btn_del.set_label_color(Color::White);
-        btn_del.set_label_font(Font::HelveticaBold);
-        
-        row.end();
-        
-        row.fixed(&lbl, 25);
-        row.fixed(&check_inv, 45);
-        row.fixed(&btn_del, 35);
-        
-        let mut sp = scroll_pack.clone();
-        let r = row.clone(); 
-        btn_del.set_callback(move |_| {
-            sp.remove(&r);
-            sp.redraw();
-            if let Some(mut p) = sp.parent() { p.redraw(); }
-        });
+        // Gap before the next controller
+        Frame::default().with_size(scroll_w, 10);
+        row_count += 1;
     }
     scroll_pack.end();
 
-    let total_height = dials.len() as i32 * 50; 
+    let total_height = row_count * 50 - 30; 
     scroll_pack.set_size(scroll_w, total_height);
 
     scroll_pack.redraw();
     if let Some(mut parent) = scroll_pack.parent() { parent.redraw(); }
 }
 
+fn show_config_banner(col: &mut Flex, banner: &mut Flex, lbl: &mut Frame, error: Option<&ConfigError>) {
+    match error {
+        Some(e) => {
+            lbl.set_label(&format!(
+                "mapping.json could not be read ({}). The file is left untouched and saving is disabled. Fix it and press Reload, or Save to replace it.",
+                e
+            ));
+            banner.show();
+        }
+        None => banner.hide(),
+    }
+    col.layout();
+    col.redraw();
+}
+
 fn populate_choice(choice: &mut Choice, items: &[String], selected_clean: &str, allow_none: bool) {
     choice.clear();
     if allow_none { choice.add_choice("None"); }
@@ -799,17 +1840,45 @@ fn populate_choice(choice: &mut Choice, items: &[String], selected_clean: &str,
     } else if allow_none { choice.set_value(0); }
 }
 
-unsafe fn apply_main_window_theme(hwnd: *mut c_void) {
-    let preference: u32 = DWMWCP_ROUND;
-    DwmSetWindowAttribute(hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
-    
-    let dark_mode: u32 = 1;
-    DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE_WIN11, &dark_mode as *const u32 as *const c_void, 4);
-    DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE_WIN10, &dark_mode as *const u32 as *const c_void, 4);
-}
 
+/// Asks whether to add or remove knobs so the mapping matches the controller. Returns true if
+/// mapping.json was changed.
+fn offer_dial_count_fix(config_path: &Path, controller_index: usize, controller: &str, sliders: usize, dials: usize) -> bool {
+    let prompt = if sliders > dials {
+        format!("{} has {} knobs, but only {} are set up in RVCI.\nAdd the missing {}?", controller, sliders, dials, sliders - dials)
+    } else {
+        format!("{} has {} knobs, but {} are set up in RVCI.\nRemove the last {} from the list?", controller, sliders, dials, dials - sliders)
+    };
+    let action = if sliders > dials { "Add" } else { "Remove" };
+    if dialog::choice2_default(&prompt, "Ignore", action, "") != Some(1) { return false; }
+
+    let mut cfg = match config::load_config(config_path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            dialog::alert_default(&format!("Could not read mapping.json: {}", e));
+            return false;
+        }
+    };
+    // Only pots show up in frames, encoders are left alone
+    match cfg.controllers.get_mut(controller_index) {
+        Some(c) => {
+            let mut pots = c.dials.iter().filter(|d| !d.input.is_encoder()).count();
+            while pots > sliders {
+                if let Some(last) = c.dials.iter().rposition(|d| !d.input.is_encoder()) { c.dials.remove(last); }
+                pots -= 1;
+            }
+            for _ in pots..sliders { c.dials.push(DialConfig::default()); }
+        }
+        None => return false,
+    }
+    if let Err(e) = config::save_config(config_path, &cfg) {
+        dialog::alert_default(&format!("Could not save mapping.json: {}", e));
+        return false;
+    }
+    true
+}
 
-fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>) -> Result<()> {
+fn build_gui_and_run(config_path: PathBuf, engine_rx: app::Receiver<EngineEvent>) -> Result<()> {
     let app = app::App::default();
     
     app::set_scheme(app::Scheme::Base);
@@ -823,20 +1892,22 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
     app::set_font_size(14); 
 
     let taskbar_icon = load_win_icon("rvci.ico");
-    let open_item = MenuItem::new("Open Settings", true, None);
-    let quit_item = MenuItem::new("Quit", true, None);
-    let open_id = open_item.id().clone();
-    let quit_id = quit_item.id().clone();
-    
-    let tray_menu = Menu::new();
-    let _ = tray_menu.append(&open_item);
-    let _ = tray_menu.append(&quit_item);
 
-    let _tray_icon = TrayIconBuilder::new()
-        .with_menu(Box::new(tray_menu))
-        .with_tooltip("RVCI")
-        .with_icon(load_tray_icon("rvci.ico"))
-        .build()?;
+    #[cfg(not(target_os = "linux"))]
+    let _tray_icon = build_tray_icon()?;
+
+    // tray-icon is gtk based on Linux and has to live on a thread running the gtk main loop
+    #[cfg(target_os = "linux")]
+    std::thread::spawn(|| {
+        if gtk::init().is_err() {
+            println!("ERROR: Failed to initialize gtk, tray icon disabled.");
+            return;
+        }
+        match build_tray_icon() {
+            Ok(_tray_icon) => gtk::main(),
+            Err(e) => println!("ERROR: Failed to create tray icon: {}", e),
+        }
+    });
 
     let mut win = Window::default().with_size(600, 970).with_label("RVCI");
     win.make_resizable(true);
@@ -858,132 +1929,43 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
     title.set_label_font(Font::HelveticaBold);
     title.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
 
-    let label_w = 125; 
-
-    let mut row_serial = Flex::default().row();
-    row_serial.set_frame(FrameType::NoBox); 
-    row_serial.set_pad(10);
-    let mut lbl_port = Frame::default().with_label("Serial Port:");
-    lbl_port.set_label_color(TEXT_COLOR);
-    lbl_port.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    
-    let mut choice_port = Choice::default();
-    style_choice(&mut choice_port);
-    let mut choice_baud = Choice::default();
-    style_choice(&mut choice_baud);
-    for baud in [9600, 19200, 38400, 57600, 115200] { choice_baud.add_choice(&baud.to_string()); }
-    
-    let mut btn_scan = Button::default().with_label("Update");
-    style_widget(&mut btn_scan);
-    btn_scan.set_label_font(Font::HelveticaBold);
-    btn_scan.set_color(ACCENT_COLOR);
-    btn_scan.set_selection_color(ACCENT_HOVER);
-    
-    row_serial.end();
-    let _ = row_serial.fixed(&lbl_port, label_w);
-    let _ = row_serial.fixed(&choice_baud, 95);
-    let _ = row_serial.fixed(&btn_scan, 80);
-
-    let mut row_max_val = Flex::default().row();
-    row_max_val.set_frame(FrameType::NoBox);
-    row_max_val.set_pad(10);
-    
-    let mut lbl_max_val = Frame::default().with_label("Max Pot Value:");
-    lbl_max_val.set_label_color(TEXT_COLOR);
-    lbl_max_val.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    
-    let mut fake_bubble = Flex::default().row();
-    fake_bubble.set_frame(FrameType::RFlatBox);
-    fake_bubble.set_color(WIDGET_BG);
-
-    fake_bubble.set_margin(2); 
-    
-    let mut spacer_left = Button::default();
-    spacer_left.set_frame(FrameType::NoBox);
-
-    let mut input_max_val = IntInput::default();
-    input_max_val.set_frame(FrameType::FlatBox); 
-    input_max_val.set_color(WIDGET_BG); 
-    input_max_val.set_text_color(TEXT_COLOR);
-    input_max_val.set_cursor_color(Color::White); 
-    input_max_val.set_selection_color(WIDGET_HOVER);
-
-    let mut spacer_right = Button::default();
-    spacer_right.set_frame(FrameType::NoBox);
-    
-    fake_bubble.end();
-    
-    let mut ic1 = input_max_val.clone();
-    spacer_left.set_callback(move |_| { let _ = ic1.take_focus(); });
-    let mut ic2 = input_max_val.clone();
-    spacer_right.set_callback(move |_| { let _ = ic2.take_focus(); });
-    
-    let _ = fake_bubble.fixed(&input_max_val, 50); 
-    
-    row_max_val.end();
-    let _ = row_max_val.fixed(&lbl_max_val, label_w);
-
-    let mut row_curve = Flex::default().row();
-    row_curve.set_frame(FrameType::NoBox);
-    row_curve.set_pad(10);
-    let mut lbl_curve = Frame::default().with_label("Volume Curve:");
-    lbl_curve.set_label_color(TEXT_COLOR);
-    lbl_curve.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    let mut choice_curve = Choice::default();
-    style_choice(&mut choice_curve);
-    choice_curve.add_choice("Linear (Default)|Logarithmic");
-    row_curve.end();
-    let _ = row_curve.fixed(&lbl_curve, label_w);
-
-    let mut lbl_switcher = Frame::default().with_label("Audio Routing");
-    lbl_switcher.set_label_color(TEXT_COLOR);
-    lbl_switcher.set_label_size(18);
-    lbl_switcher.set_label_font(Font::HelveticaBold);
-    lbl_switcher.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-
-    let mut row_wd1 = Flex::default().row();
-    row_wd1.set_frame(FrameType::NoBox);
-    row_wd1.set_pad(10);
-    let mut lbl_wd1 = Frame::default().with_label("Output 1:");
-    lbl_wd1.set_label_color(TEXT_COLOR);
-    lbl_wd1.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    let mut choice_wd1 = Choice::default();
-    style_choice(&mut choice_wd1);
-    let mut btn_test1 = Button::default().with_label("Test");
-    style_widget(&mut btn_test1);
-    row_wd1.end();
-    let _ = row_wd1.fixed(&lbl_wd1, label_w);
-    let _ = row_wd1.fixed(&btn_test1, 70);
-
-    let mut row_wd2 = Flex::default().row();
-    row_wd2.set_frame(FrameType::NoBox);
-    row_wd2.set_pad(10);
-    let mut lbl_wd2 = Frame::default().with_label("Output 2:");
-    lbl_wd2.set_label_color(TEXT_COLOR);
-    lbl_wd2.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    let mut choice_wd2 = Choice::default();
-    style_choice(&mut choice_wd2);
-    let mut btn_test2 = Button::default().with_label("Test");
-    style_widget(&mut btn_test2);
-    row_wd2.end();
-    let _ = row_wd2.fixed(&lbl_wd2, label_w);
-    let _ = row_wd2.fixed(&btn_test2, 70);
+    let mut row_banner = Flex::default().row();
+    row_banner.set_frame(FrameType::RFlatBox);
+    row_banner.set_color(DESTRUCTIVE_COLOR);
+    row_banner.set_margin(8);
+    row_banner.set_pad(10);
+    let mut lbl_banner = Frame::default();
+    lbl_banner.set_label_color(Color::White);
+    lbl_banner.set_label_size(13);
+    lbl_banner.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside | fltk::enums::Align::Wrap);
+    let mut btn_reload = Button::default().with_label("Reload");
+    style_widget(&mut btn_reload);
+    btn_reload.set_label_font(Font::HelveticaBold);
+    row_banner.end();
+    let _ = row_banner.fixed(&btn_reload, 80);
+    row_banner.hide();
 
     let mut row_knobs_header = Flex::default().row();
     row_knobs_header.set_frame(FrameType::NoBox);
     row_knobs_header.set_pad(10);
-    let mut lbl_knobs = Frame::default().with_label("Knob Mappings");
+    let mut lbl_knobs = Frame::default().with_label("Controllers");
     lbl_knobs.set_label_color(TEXT_COLOR);
     lbl_knobs.set_label_size(18);
     lbl_knobs.set_label_font(Font::HelveticaBold);
     lbl_knobs.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
-    let mut btn_add = Button::default().with_label("+ Add Knob");
+    let mut btn_scan = Button::default().with_label("Update");
+    style_widget(&mut btn_scan);
+    btn_scan.set_label_font(Font::HelveticaBold);
+    btn_scan.set_color(ACCENT_COLOR);
+    btn_scan.set_selection_color(ACCENT_HOVER);
+    let mut btn_add = Button::default().with_label("+ Add Controller");
     style_widget(&mut btn_add);
     btn_add.set_label_font(Font::HelveticaBold);
     btn_add.set_color(ACCENT_COLOR);
     btn_add.set_selection_color(ACCENT_HOVER);
     row_knobs_header.end();
-    let _ = row_knobs_header.fixed(&btn_add, 110);
+    let _ = row_knobs_header.fixed(&btn_scan, 80);
+    let _ = row_knobs_header.fixed(&btn_add, 150);
 
     let mut scroll = Scroll::default();
     scroll.set_type(fltk::group::ScrollType::VerticalAlways); 
@@ -1060,12 +2042,7 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
 
     col.end();
     let _ = col.fixed(&title, 45);
-    let _ = col.fixed(&row_serial, 40);
-    let _ = col.fixed(&row_max_val, 40); 
-    let _ = col.fixed(&row_curve, 40);
-    let _ = col.fixed(&lbl_switcher, 35);
-    let _ = col.fixed(&row_wd1, 40);
-    let _ = col.fixed(&row_wd2, 40);
+    let _ = col.fixed(&row_banner, 70);
     let _ = col.fixed(&row_knobs_header, 35);
     let _ = col.fixed(&row_footer1, 35);
     let _ = col.fixed(&row_btns, 50); 
@@ -1103,168 +2080,137 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
     
     osd_fg_win.end();
 
-    let config = if let Ok(file) = File::open(&config_path) {
-        serde_json::from_reader(BufReader::new(file)).unwrap_or_default()
-    } else { AppConfig::default() };
-    let state = Arc::new(Mutex::new(config));
+    let mut calib_win = Window::default().with_size(360, 340).with_label("Calibrate Knobs");
+    calib_win.set_color(Color::Black);
+    let mut calib_col = Flex::default_fill().column();
+    calib_col.set_margin(20);
+    calib_col.set_pad(10);
+    let mut calib_help = Frame::default();
+    calib_help.set_label_color(TEXT_COLOR);
+    calib_help.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside | fltk::enums::Align::Wrap);
+    let mut calib_readings = Frame::default();
+    calib_readings.set_label_color(TEXT_COLOR);
+    calib_readings.set_align(fltk::enums::Align::Left | fltk::enums::Align::Top | fltk::enums::Align::Inside);
+    let mut calib_btns = Flex::default().row();
+    calib_btns.set_pad(15);
+    let mut btn_calib_cancel = Button::default().with_label("Cancel");
+    style_widget(&mut btn_calib_cancel);
+    let mut btn_calib_apply = Button::default().with_label("Apply");
+    style_widget(&mut btn_calib_apply);
+    btn_calib_apply.set_color(ACCENT_COLOR);
+    btn_calib_apply.set_selection_color(ACCENT_HOVER);
+    btn_calib_apply.set_label_font(Font::HelveticaBold);
+    calib_btns.end();
+    calib_col.end();
+    calib_col.fixed(&calib_help, 45);
+    calib_col.fixed(&calib_btns, 40);
+    calib_win.end();
+    calib_win.hide();
+
+    let state = Arc::new(Mutex::new(AppConfig::default()));
+    // Set while mapping.json on disk is unreadable. Saving is refused until the user decides what to do.
+    let config_error: Rc<RefCell<Option<ConfigError>>> = Rc::new(RefCell::new(None));
     
-    let mut refresh_all_data = {
-        let mut choice_port = choice_port.clone();
-        let mut choice_wd1 = choice_wd1.clone();
-        let mut choice_wd2 = choice_wd2.clone();
+    let refresh_all_data = {
+        let mut scroll_pack = scroll_pack.clone();
+        let state = state.clone();
+        move || refresh_controllers_ui(&mut scroll_pack, &state, &MappingChoices::scan())
+    };
+
+    let mut reload_from_disk = {
         let state = state.clone();
+        let config_error = config_error.clone();
+        let mut col = col.clone();
+        let mut row_banner = row_banner.clone();
+        let mut lbl_banner = lbl_banner.clone();
+        let mut check_debug = check_debug.clone();
+        let mut check_osd = check_osd.clone();
+        let mut refresh_logic = refresh_all_data.clone();
+        let path = config_path.clone();
         move || {
-            let cfg = state.lock().unwrap();
-            let ports = AudioScanner::get_com_ports();
-            populate_choice(&mut choice_port, &ports, &cfg.serial.port, false);
-            let devices_with_ids = AudioScanner::get_playback_devices_with_ids();
-            let device_names: Vec<String> = devices_with_ids.iter().map(|d| d.0.clone()).collect();
-            populate_choice(&mut choice_wd1, &device_names, &cfg.work_device_1, true);
-            populate_choice(&mut choice_wd2, &device_names, &cfg.work_device_2, true);
+            match config::load_config(&path) {
+                Ok(cfg) => {
+                    *state.lock().unwrap() = cfg;
+                    *config_error.borrow_mut() = None;
+                }
+                Err(ConfigError::Missing) => { *config_error.borrow_mut() = None; }
+                Err(e) => {
+                    println!("ERROR: Could not load {}: {}", path.display(), e);
+                    *config_error.borrow_mut() = Some(e);
+                }
+            }
+            show_config_banner(&mut col, &mut row_banner, &mut lbl_banner, config_error.borrow().as_ref());
+
+            {
+                let cfg = state.lock().unwrap();
+                check_debug.set_value(cfg.debug_mode);
+                check_osd.set_value(cfg.enable_osd);
+            }
+
+            refresh_logic();
         }
     };
 
-    refresh_all_data();
-    {
-        let cfg = state.lock().unwrap();
-        if let Some(idx) = [9600, 19200, 38400, 57600, 115200].iter().position(|&x| x == cfg.serial.baud) {
-             choice_baud.set_value(idx as i32);
-        }
-        
-        input_max_val.set_value(&(cfg.value_max as i32).to_string());
-        
-        check_debug.set_value(cfg.debug_mode);
-        check_osd.set_value(cfg.enable_osd);
-        choice_curve.set_value(if cfg.use_logarithmic_scale { 1 } else { 0 });
+    reload_from_disk();
 
-        let procs = AudioScanner::get_active_sessions();
-        let capture_devices: Vec<String> = AudioScanner::get_capture_devices_with_ids().into_iter().map(|d| d.0).collect();
-        refresh_knobs_ui(&mut scroll_pack, &cfg.dials, &procs, &capture_devices);
+    {
+        let mut reload = reload_from_disk.clone();
+        btn_reload.set_callback(move |_| reload());
     }
 
     {
-        let mut scroll_pack = scroll_pack.clone();
-        let state = state.clone();
         let mut refresh_logic = refresh_all_data.clone();
-        btn_scan.set_callback(move |_| {
-            refresh_logic();
-            let cfg = state.lock().unwrap();
-            let procs = AudioScanner::get_active_sessions();
-            let capture_devices: Vec<String> = AudioScanner::get_capture_devices_with_ids().into_iter().map(|d| d.0).collect();
-            refresh_knobs_ui(&mut scroll_pack, &cfg.dials, &procs, &capture_devices);
-        });
+        btn_scan.set_callback(move |_| refresh_logic());
     }
 
     {
         let state = state.clone();
         let mut scroll_pack = scroll_pack.clone();
         btn_add.set_callback(move |_| {
-            let mut cfg = state.lock().unwrap();
-            
-            let mut current_dials = Vec::new();
-            for i in 0..scroll_pack.children() {
-                if let Some(row) = scroll_pack.child(i) {
-                    if let Some(node) = row.as_group() {
-                        if node.children() >= 5 { 
-                            let c_type = unsafe { Choice::from_widget_ptr(node.child(1).unwrap().as_widget_ptr()) };
-                            let c_proc = unsafe { Choice::from_widget_ptr(node.child(2).unwrap().as_widget_ptr()) };
-                            let c_inv = unsafe { CheckButton::from_widget_ptr(node.child(3).unwrap().as_widget_ptr()) };
-                            
-                            let t_str = match c_type.value() { 
-                                1 => "process", 
-                                2 => "all_others", 
-                                3 => "microphone",
-                                _ => "system" 
-                            }.to_string();
-                            
-                            let p_str = if c_proc.active() { 
-                                c_proc.choice().and_then(|val| if val == "None" { None } else { Some(val) })
-                            } else { 
-                                None 
-                            };
-                            
-                            current_dials.push(DialConfig { 
-                                dial_type: t_str, 
-                                process_name: p_str,
-                                inverted: c_inv.value()
-                            });
-                        }
-                    }
-                }
-            }
-            cfg.dials = current_dials;
-            cfg.dials.push(DialConfig { dial_type: "system".to_string(), process_name: None, inverted: false });
-            let procs = AudioScanner::get_active_sessions();
-            let capture_devices: Vec<String> = AudioScanner::get_capture_devices_with_ids().into_iter().map(|d| d.0).collect();
-            refresh_knobs_ui(&mut scroll_pack, &cfg.dials, &procs, &capture_devices);
+            state.lock().unwrap().controllers.push(ControllerConfig::default());
+            refresh_controllers_ui(&mut scroll_pack, &state, &MappingChoices::scan());
         });
     }
 
     {
         let state = state.clone();
-        let scroll_pack = scroll_pack.clone();
-        let choice_port = choice_port.clone();
-        let choice_baud = choice_baud.clone();
-        let choice_curve = choice_curve.clone();
-        let choice_wd1 = choice_wd1.clone();
-        let choice_wd2 = choice_wd2.clone();
         let check_startup = check_startup.clone();
         let check_debug = check_debug.clone();
         let check_osd = check_osd.clone();
-        let input_max_val = input_max_val.clone();
         let path = config_path.clone();
+        let config_error = config_error.clone();
+        let mut col = col.clone();
+        let mut row_banner = row_banner.clone();
+        let mut lbl_banner = lbl_banner.clone();
         
         btn_apply.set_callback(move |_| {
+            let pending_error = config_error.borrow().clone();
+            if let Some(err) = pending_error {
+                let prompt = format!(
+                    "mapping.json could not be read:\n{}\n\nSaving will replace it with the settings shown here.\nThe broken file will be kept as mapping.json.broken.",
+                    err
+                );
+                if dialog::choice2_default(&prompt, "Cancel", "Replace", "") != Some(1) { return; }
+                if let Err(e) = config::set_aside_broken_config(&path) {
+                    dialog::alert_default(&format!("Could not move the broken mapping.json aside: {}", e));
+                    return;
+                }
+                *config_error.borrow_mut() = None;
+                show_config_banner(&mut col, &mut row_banner, &mut lbl_banner, None);
+            }
+
             let _ = set_startup_launch(check_startup.value());
+            // Controller sections write into `state` as they are edited
             let mut cfg = state.lock().unwrap();
-            if let Some(port) = choice_port.choice() { cfg.serial.port = port; }
-            if let Some(baud_str) = choice_baud.choice() {
-                if let Ok(b) = baud_str.parse::<u32>() { cfg.serial.baud = b; }
-            }
-            if let Some(s) = choice_wd1.choice() { cfg.work_device_1 = extract_clean_name(&s); }
-            if let Some(s) = choice_wd2.choice() { cfg.work_device_2 = extract_clean_name(&s); }
-            
-            if let Ok(v) = input_max_val.value().trim().parse::<f32>() {
-                cfg.value_max = v;
+            for position in cfg.controllers.iter_mut().flat_map(|c| c.selector.iter_mut()) {
+                position.outputs.retain(|o| !o.is_empty());
             }
-            
             cfg.debug_mode = check_debug.value();
             cfg.enable_osd = check_osd.value();
-            cfg.use_logarithmic_scale = choice_curve.value() == 1;
-
-            let mut new_dials = Vec::new();
-            for i in 0..scroll_pack.children() {
-                if let Some(row) = scroll_pack.child(i) {
-                    if let Some(node) = row.as_group() {
-                        if node.children() >= 5 { 
-                            let c_type = unsafe { Choice::from_widget_ptr(node.child(1).unwrap().as_widget_ptr()) };
-                            let c_proc = unsafe { Choice::from_widget_ptr(node.child(2).unwrap().as_widget_ptr()) };
-                            let c_inv = unsafe { CheckButton::from_widget_ptr(node.child(3).unwrap().as_widget_ptr()) };
-                            
-                            let t_str = match c_type.value() { 
-                                1 => "process", 
-                                2 => "all_others", 
-                                3 => "microphone",
-                                _ => "system" 
-                            }.to_string();
-                            
-                            let p_str = if c_proc.active() { 
-                                c_proc.choice().and_then(|val| if val == "None" { None } else { Some(val) })
-                            } else { 
-                                None 
-                            };
-                            
-                            new_dials.push(DialConfig { 
-                                dial_type: t_str, 
-                                process_name: p_str,
-                                inverted: c_inv.value()
-                            });
-                        }
-                    }
-                }
+
+            if let Err(e) = config::save_config(&path, &cfg) {
+                dialog::alert_default(&format!("Could not save mapping.json: {}", e));
             }
-            cfg.dials = new_dials;
-            if let Ok(f) = File::create(&path) { let _ = serde_json::to_writer_pretty(f, &*cfg); }
         });
     }
 
@@ -1273,6 +2219,45 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
         btn_cancel.set_callback(move |_| win.hide());
     }
 
+    let calibration: Rc<RefCell<Calibration>> = Rc::new(RefCell::new(Vec::new()));
+    let tuning: Rc<RefCell<TuningReadings>> = Rc::new(RefCell::new(Vec::new()));
+    {
+        let mut stop_calibrating = {
+            let mut calib_win = calib_win.clone();
+            move || {
+                engine::set_calibrating_controller(None);
+                calib_win.hide();
+            }
+        };
+        let mut stop = stop_calibrating.clone();
+        btn_calib_cancel.set_callback(move |_| stop());
+        let mut stop = stop_calibrating.clone();
+        calib_win.set_callback(move |_| stop());
+
+        let state = state.clone();
+        let calibration = calibration.clone();
+        let tuning = tuning.clone();
+        let mut refresh_logic = refresh_all_data.clone();
+        btn_calib_apply.set_callback(move |_| {
+            if let Some(ci) = engine::calibrating_controller() {
+                let tuning_filters = TUNING_FILTERS.load(Ordering::Relaxed);
+                let changed = match state.lock().unwrap().controllers.get_mut(ci) {
+                    Some(controller) if tuning_filters => apply_tuning(controller, &tuning.borrow()),
+                    Some(controller) => controller.apply_calibration(&calibration.borrow()),
+                    None => 0,
+                };
+                if tuning_filters {
+                    println!("DEBUG: Tuned the filters of {} knobs, press Save to keep them", changed);
+                } else {
+                    println!("DEBUG: Calibrated {} knobs, press Save to keep them", changed);
+                }
+            }
+            stop_calibrating();
+            refresh_logic();
+        });
+    }
+
+    let mut refresh_after_learn = refresh_all_data.clone();
     let mut last_osd_update = Instant::now();
     let mut osd_is_visible = false;
 
@@ -1280,50 +2265,128 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
         app::check();
 
         if let Ok(event) = MenuEvent::receiver().try_recv() {
-            if event.id == open_id {
-                refresh_all_data();
-                let cfg = state.lock().unwrap();
-                let procs = AudioScanner::get_active_sessions();
-                let capture_devices: Vec<String> = AudioScanner::get_capture_devices_with_ids().into_iter().map(|d| d.0).collect();
-                refresh_knobs_ui(&mut scroll_pack, &cfg.dials, &procs, &capture_devices);
+            if event.id == TRAY_OPEN_ID {
+                reload_from_disk();
                 
                 win.show();
                 app::flush(); 
                 win.redraw();
 
-                unsafe { apply_main_window_theme(win.raw_handle()); }
-            } else if event.id == quit_id { app.quit(); break; }
+                #[cfg(windows)]
+                unsafe { win32::apply_main_window_theme(win.raw_handle()); }
+            } else if event.id == TRAY_RESTORE_ID {
+                let backup = config::backup_path(&config_path, 1);
+                if !backup.exists() {
+                    dialog::message_default("There is no previous config to restore yet.");
+                } else if dialog::choice2_default("Replace the current mapping.json with the previously saved version?\nThe current one is kept as a backup.", "Cancel", "Restore", "") == Some(1) {
+                    match config::restore_previous_config(&config_path) {
+                        Ok(()) => reload_from_disk(),
+                        Err(e) => dialog::alert_default(&format!("Could not restore the previous config: {:#}", e)),
+                    }
+                }
+            } else if event.id == TRAY_QUIT_ID { app.quit(); break; }
         }
         
         if let Ok(event) = TrayIconEvent::receiver().try_recv() {
              if let TrayIconEvent::Click { button: MouseButton::Left, .. } = event {
-                refresh_all_data();
-                let cfg = state.lock().unwrap();
-                let procs = AudioScanner::get_active_sessions();
-                let capture_devices: Vec<String> = AudioScanner::get_capture_devices_with_ids().into_iter().map(|d| d.0).collect();
-                refresh_knobs_ui(&mut scroll_pack, &cfg.dials, &procs, &capture_devices);
+                reload_from_disk();
                 
                 win.show();
                 app::flush(); 
                 win.redraw();
 
-                unsafe { apply_main_window_theme(win.raw_handle()); }
+                #[cfg(windows)]
+                unsafe { win32::apply_main_window_theme(win.raw_handle()); }
              }
         }
 
         let mut got_msg = false;
         let mut final_app = String::new();
-        let mut final_vol = 0.0;
+        let mut final_vol = None;
+        let mut mismatches = Vec::new();
+        let mut learned = false;
+
+        let calibrating = engine::calibrating_controller();
+        let tuning_filters = TUNING_FILTERS.load(Ordering::Relaxed);
+        let readings_text = |dials: &[DialConfig]| {
+            if tuning_filters { tuning_text(&tuning.borrow(), dials) } else { calibration_text(&calibration.borrow(), dials) }
+        };
+        match calibrating {
+            Some(ci) if !calib_win.shown() => {
+                calibration.borrow_mut().clear();
+                tuning.borrow_mut().clear();
+                if tuning_filters {
+                    calib_win.set_label("Tune Filters");
+                    calib_help.set_label("Set every knob somewhere in the middle and leave them alone for a few seconds.");
+                } else {
+                    calib_win.set_label("Calibrate Knobs");
+                    calib_help.set_label("Turn every knob fully left, then fully right.\nKnobs you don't turn keep their range.");
+                }
+                let dials = state.lock().unwrap().controllers.get(ci).map(|c| c.dials.clone()).unwrap_or_default();
+                calib_readings.set_label(&readings_text(&dials));
+                calib_win.show();
+            }
+            None if calib_win.shown() => calib_win.hide(),
+            _ => {}
+        }
+        let mut calibration_changed = false;
+
+        while let Some(event) = engine_rx.recv() {
+            match event {
+                EngineEvent::Osd { name, level } => {
+                    got_msg = true;
+                    final_app = name;
+                    final_vol = level;
+                }
+                EngineEvent::DialCountMismatch { controller_index, controller, sliders, dials } => {
+                    mismatches.push((controller_index, controller, sliders, dials));
+                }
+                EngineEvent::MidiControl { controller_index, control } => {
+                    if finish_midi_learn(&state, controller_index, control) { learned = true; }
+                }
+                EngineEvent::RawReadings { controller_index, readings } if calibrating == Some(controller_index) && tuning_filters => {
+                    let mut seen = tuning.borrow_mut();
+                    for (knob, reading) in readings {
+                        if knob >= seen.len() { seen.resize(knob + 1, Vec::new()); }
+                        if seen[knob].len() < MAX_TUNING_READINGS { seen[knob].push(reading); }
+                    }
+                    calibration_changed = true;
+                }
+                EngineEvent::RawReadings { controller_index, readings } if calibrating == Some(controller_index) => {
+                    let mut ranges = calibration.borrow_mut();
+                    for (knob, reading) in readings {
+                        if knob >= ranges.len() { ranges.resize(knob + 1, None); }
+                        ranges[knob] = Some(match ranges[knob] {
+                            Some((low, high)) => (low.min(reading), high.max(reading)),
+                            None => (reading, reading),
+                        });
+                    }
+                    calibration_changed = true;
+                }
+                EngineEvent::RawReadings { .. } => {}
+            }
+        }
+
+        if let (true, Some(ci)) = (calibration_changed, calibrating) {
+            let dials = state.lock().unwrap().controllers.get(ci).map(|c| c.dials.clone()).unwrap_or_default();
+            calib_readings.set_label(&readings_text(&dials));
+            calib_win.redraw();
+        }
+
+        if learned { refresh_after_learn(); }
 
-        while let Some((app_name, vol_level)) = osd_rx.recv() {
-            got_msg = true;
-            final_app = app_name;
-            final_vol = vol_level;
+        for (controller_index, controller, sliders, dials) in mismatches {
+            if offer_dial_count_fix(&config_path, controller_index, &controller, sliders, dials) {
+                reload_from_disk();
+            }
         }
 
         if got_msg {
             osd_lbl.set_label(&final_app);
-            osd_bar.set_value(final_vol as f64);
+            match final_vol {
+                Some(level) => { osd_bar.set_value(level as f64); osd_bar.show(); }
+                None => osd_bar.hide(),
+            }
             
             if !osd_is_visible {
                 
@@ -1358,32 +2421,8 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
                 osd_bg_win.show();
                 osd_fg_win.show();
 
-                unsafe {
-                    let preference: u32 = DWMWCP_ROUND;
-
-                    // --- LAYER 1: The Tinted Box ---
-                    let bg_hwnd = osd_bg_win.raw_handle();
-                    DwmSetWindowAttribute(bg_hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
-
-                    let mut ex_style = get_win_long(bg_hwnd, GWL_EXSTYLE);
-                    ex_style |= WS_EX_LAYERED | WS_EX_TRANSPARENT; 
-                    set_win_long(bg_hwnd, GWL_EXSTYLE, ex_style);
-                    
-                    SetLayeredWindowAttributes(bg_hwnd as _, 0, 180, LWA_ALPHA); 
-                    SetWindowPos(bg_hwnd, HWND_TOPMOST as *mut c_void, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE);
-
-                    // --- LAYER 2: The Text ---
-                    let fg_hwnd = osd_fg_win.raw_handle();
-                    DwmSetWindowAttribute(fg_hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
-
-                    let mut ex_style_fg = get_win_long(fg_hwnd, GWL_EXSTYLE);
-                    ex_style_fg |= WS_EX_LAYERED | WS_EX_TRANSPARENT; 
-                    set_win_long(fg_hwnd, GWL_EXSTYLE, ex_style_fg);
-
-                    SetLayeredWindowAttributes(fg_hwnd as _, 0x00000000, 0, LWA_COLORKEY);
-
-                    SetWindowPos(fg_hwnd, HWND_TOPMOST as *mut c_void, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE);
-                }
+                #[cfg(windows)]
+                unsafe { win32::apply_osd_window_style(osd_bg_win.raw_handle(), osd_fg_win.raw_handle()); }
                 
                 osd_is_visible = true;
             }
@@ -1405,16 +2444,36 @@ fn build_gui_and_run(config_path: PathBuf, osd_rx: app::Receiver<(String, f32)>)
 }
 
 fn main() -> Result<()> {
-    let path = get_config_path();
-
-    let debug_mode_enabled = if let Ok(file) = File::open(&path) {
-        let config: AppConfig = serde_json::from_reader(BufReader::new(file)).unwrap_or_default();
-        config.debug_mode
-    } else {
-        false
+    let path = config::get_config_path();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(options) = replay::ReplayOptions::from_args(&args, path.clone())? {
+        #[cfg(windows)]
+        unsafe {
+            let _ = AllocConsole();
+        }
+        let matched = replay::run(&options)?;
+        std::process::exit(if matched { 0 } else { 1 });
+    }
+    if let Some(options) = simulator::SimulatorOptions::from_args(&args)? {
+        #[cfg(windows)]
+        unsafe {
+            let _ = AllocConsole();
+        }
+        return simulator::run(&options);
+    }
+    let recorder = match args.iter().position(|a| a == "--record") {
+        Some(i) => {
+            let file = args.get(i + 1).ok_or_else(|| anyhow!("--record needs a file\n{}", replay::USAGE))?;
+            Some(Arc::new(Recorder::create(Path::new(file))?))
+        }
+        None => None,
     };
+    let migration_result = config::migrate_config_file(&path);
+
+    let debug_mode_enabled = config::load_config(&path).map(|c| c.debug_mode).unwrap_or(false);
 
     if debug_mode_enabled {
+        #[cfg(windows)]
         unsafe {
             let _ = AllocConsole();
         }
@@ -1425,9 +2484,13 @@ fn main() -> Result<()> {
         println!("==========================================");
     }
 
+    if let Err(e) = migration_result {
+        println!("ERROR: Could not migrate {}: {}", path.display(), e);
+    }
+
     let path_clone = path.clone();
-    let (osd_tx, osd_rx) = app::channel::<(String, f32)>();
+    let (gui_tx, gui_rx) = app::channel::<EngineEvent>();
 
-    std::thread::spawn(move || { run_volume_logic_loop(path_clone, osd_tx); });
-    build_gui_and_run(path, osd_rx)
+    std::thread::spawn(move || { run_volume_logic_loop(path_clone, gui_tx, recorder); });
+    build_gui_and_run(path, gui_rx)
 }
\ No newline at end of file
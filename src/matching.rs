@@ -0,0 +1,89 @@
+//! Picking the audio sessions a knob's process names stand for, by name, wildcards, regex, the
+//! executable's path or the session's display name.
+
+use crate::audio::{clean_process_name, AudioSession};
+use crate::config::MatchRule;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of characters and `?` for one.
+/// Ignores case.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was and how much of the text it swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Paths compare the same whichever way the slashes lean.
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// One process name of a knob, ready to be compared with sessions.
+pub struct SessionMatcher {
+    rule: MatchRule,
+    pattern: String,
+    regex: Option<Regex>,
+}
+
+impl SessionMatcher {
+    /// Fails on a regex that doesn't compile.
+    pub fn new(rule: MatchRule, pattern: &str) -> Result<Self> {
+        let regex = match rule {
+            MatchRule::Regex => Some(RegexBuilder::new(pattern).case_insensitive(true).build()
+                .with_context(|| format!("Invalid regex \"{}\"", pattern))?),
+            _ => None,
+        };
+        let pattern = match rule {
+            MatchRule::Name | MatchRule::Glob => clean_process_name(pattern).to_lowercase(),
+            MatchRule::Path => normalize_path(pattern),
+            _ => pattern.to_string(),
+        };
+        Ok(Self { rule, pattern, regex })
+    }
+
+    pub fn matches(&self, session: &AudioSession) -> bool {
+        match self.rule {
+            MatchRule::Name => session.process_name.to_lowercase() == self.pattern,
+            MatchRule::Glob => glob_matches(&self.pattern, &session.process_name),
+            MatchRule::Regex => self.regex.as_ref().is_some_and(|r| r.is_match(&session.process_name)),
+            MatchRule::Path => !session.exe_path.is_empty() && glob_matches(&self.pattern, &normalize_path(&session.exe_path)),
+            MatchRule::DisplayName => !session.display_name.is_empty() && glob_matches(&self.pattern, &session.display_name),
+        }
+    }
+}
+
+/// Names of the `sessions` each of `patterns` matches, for the preview in a knob's settings.
+/// An invalid pattern gets its error instead.
+pub fn preview(rule: MatchRule, patterns: &[&str], sessions: &[AudioSession]) -> Vec<(String, Result<Vec<String>>)> {
+    patterns.iter().map(|pattern| {
+        let matched = SessionMatcher::new(rule, pattern).map(|matcher| {
+            let mut names: Vec<String> = sessions.iter()
+                .filter(|sess| matcher.matches(sess))
+                .map(|sess| sess.process_name.clone())
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        });
+        (pattern.to_string(), matched)
+    }).collect()
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// Newest handshake version this build understands.
+pub const HANDSHAKE_VERSION: u32 = 1;
+
+/// What the controller announced about itself in its handshake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerInfo {
+    pub version: u32,
+    pub sliders: usize,
+    pub switches: usize,
+    pub buttons: usize,
+    pub encoders: usize,
+    pub name: String,
+}
+
+/// One thing the controller told us, independent of how it was encoded on the wire.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControllerEvent {
+    /// Raw readings for every dial, in dial order. Not yet normalized.
+    Dials(Vec<f32>),
+    /// The selector switch moved to this position (1-based).
+    Switch(usize),
+    /// A button went down (`pressed: true`) or up. `index` is 1-based.
+    Button { index: usize, pressed: bool },
+    /// A rotary encoder turned by `delta` steps, positive clockwise. `index` is 1-based and counts
+    /// encoders only.
+    Encoder { index: usize, delta: i32 },
+    /// The controller identified itself. Only sent by firmware that supports the handshake.
+    Hello(ControllerInfo),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A field that should be a number isn't one. `field` is 1-based.
+    InvalidValue { field: usize, text: String },
+    /// The line doesn't look like anything this protocol knows.
+    Unrecognized(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidValue { field, text } => write!(f, "field {} is not a number: \"{}\"", field, text),
+            ParseError::Unrecognized(line) => write!(f, "unrecognized line \"{}\"", line),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Turns lines from the controller into events. Implement this to support other firmware formats.
+pub trait LineProtocol {
+    /// Blank lines are `Ok(None)`. `line` may still carry its line ending.
+    fn parse_line(&mut self, line: &str) -> Result<Option<ControllerEvent>, ParseError>;
+
+    /// Sent to the controller after connecting to ask for a `Hello`. Firmware that doesn't support
+    /// the handshake ignores it.
+    fn handshake_request(&self) -> Option<&'static [u8]> { None }
+}
+
+/// The deej wire format plus the RVCI firmware extras:
+///
+/// - `512|1023|0|300|100`: one reading per dial
+/// - `WORKS 1`: the selector switch moved to position 1
+/// - `BUTTON 2 DOWN` / `BUTTON 2 UP`: button 2 was pressed or released
+/// - `ENCODER 1 +2` / `ENCODER 1 -1`: encoder 1 turned two steps clockwise or one back
+/// - `HELLO version=1 sliders=5 switches=1 buttons=0 encoders=0 name=RVCI Nano`: the optional
+///   handshake, sent on boot and in reply to `HELLO?`. `name` takes the rest of the line.
+#[derive(Debug, Default)]
+pub struct DeejProtocol;
+
+fn parse_number<T: std::str::FromStr>(field: usize, text: &str) -> Result<T, ParseError> {
+    text.trim().parse::<T>().map_err(|_| ParseError::InvalidValue { field, text: text.trim().to_string() })
+}
+
+fn parse_hello(line: &str, rest: &str) -> Result<ControllerInfo, ParseError> {
+    let mut info = ControllerInfo { version: 1, sliders: 0, switches: 0, buttons: 0, encoders: 0, name: String::new() };
+    let mut has_sliders = false;
+    let mut remaining = rest.trim();
+    let mut field = 1;
+    while !remaining.is_empty() {
+        field += 1;
+        let (pair, tail) = remaining.split_once(' ').unwrap_or((remaining, ""));
+        let (key, value) = pair.split_once('=').ok_or_else(|| ParseError::Unrecognized(line.to_string()))?;
+        match key {
+            "name" => {
+                // Device names may contain spaces
+                info.name = remaining["name=".len()..].trim().to_string();
+                break;
+            }
+            "version" => info.version = parse_number(field, value)?,
+            "sliders" => { info.sliders = parse_number(field, value)?; has_sliders = true; }
+            "switches" => info.switches = parse_number(field, value)?,
+            "buttons" => info.buttons = parse_number(field, value)?,
+            "encoders" => info.encoders = parse_number(field, value)?,
+            _ => {} // Newer firmware may announce more than we know about
+        }
+        remaining = tail.trim_start();
+    }
+    if !has_sliders { return Err(ParseError::Unrecognized(line.to_string())); }
+    if info.name.is_empty() { info.name = "Controller".to_string(); }
+    Ok(info)
+}
+
+impl LineProtocol for DeejProtocol {
+    fn handshake_request(&self) -> Option<&'static [u8]> { Some(b"HELLO?\n") }
+
+    fn parse_line(&mut self, line: &str) -> Result<Option<ControllerEvent>, ParseError> {
+        let line = line.trim();
+        if line.is_empty() { return Ok(None); }
+
+        if let Some(rest) = line.strip_prefix("WORKS ") {
+            let position = parse_number::<usize>(2, rest)?;
+            return Ok(Some(ControllerEvent::Switch(position)));
+        }
+
+        if let Some(rest) = line.strip_prefix("HELLO ") {
+            return parse_hello(line, rest).map(|info| Some(ControllerEvent::Hello(info)));
+        }
+
+        if let Some(rest) = line.strip_prefix("BUTTON ") {
+            let mut words = rest.split_whitespace();
+            let index = parse_number::<usize>(2, words.next().unwrap_or_default())?;
+            let pressed = match (words.next(), words.next()) {
+                (Some("DOWN"), None) => true,
+                (Some("UP"), None) => false,
+                _ => return Err(ParseError::Unrecognized(line.to_string())),
+            };
+            return Ok(Some(ControllerEvent::Button { index, pressed }));
+        }
+
+        if let Some(rest) = line.strip_prefix("ENCODER ") {
+            let mut words = rest.split_whitespace();
+            let index = parse_number::<usize>(2, words.next().unwrap_or_default())?;
+            let delta = parse_number::<i32>(3, words.next().unwrap_or_default())?;
+            if words.next().is_some() { return Err(ParseError::Unrecognized(line.to_string())); }
+            return Ok(Some(ControllerEvent::Encoder { index, delta }));
+        }
+
+        if !line.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') {
+            return Err(ParseError::Unrecognized(line.to_string()));
+        }
+
+        let mut values = Vec::new();
+        for (i, part) in line.split('|').enumerate() {
+            let value = parse_number::<f32>(i + 1, part)?;
+            if !value.is_finite() {
+                return Err(ParseError::InvalidValue { field: i + 1, text: part.trim().to_string() });
+            }
+            values.push(value);
+        }
+        Ok(Some(ControllerEvent::Dials(values)))
+    }
+}
+
+/// Protocol names accepted in `serial.protocol` in mapping.json.
+pub const PROTOCOL_NAMES: &[&str] = &["deej"];
+
+pub fn create_protocol(name: &str) -> Option<Box<dyn LineProtocol>> {
+    match name {
+        "deej" => Some(Box::new(DeejProtocol)),
+        _ => None,
+    }
+}
@@ -0,0 +1,251 @@
+//! `RVCI --replay <recording>` runs recorded controller lines through the engine without the
+//! settings window. With `--expect` the volume calls go to a mock mixer and are compared to a
+//! trace written earlier with `--write-expected`, so a reported bug can become a regression check.
+
+use crate::audio::{self, clean_process_name, AudioBackend, AudioDevice, AudioSession, DataFlow, Endpoint, MockBackend};
+use crate::config::{self, AppConfig, ButtonAction, DialType, MatchRule};
+use crate::engine::{DialPipeline, EngineEvent};
+use crate::matching::SessionMatcher;
+use crate::transport::{load_recording, ReplayTransport, Transport, TransportEvent};
+use anyhow::{anyhow, Context, Result};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "Usage: RVCI --replay <recording> [--speed <factor>] [--config <mapping.json>] [--expect <trace> | --write-expected <trace>]
+       RVCI --record <recording>";
+
+pub struct ReplayOptions {
+    pub recording: PathBuf,
+    /// 1 is real time, 0 as fast as possible.
+    pub speed: f32,
+    pub config: PathBuf,
+    pub expect: Option<PathBuf>,
+    pub write_expected: Option<PathBuf>,
+}
+
+impl ReplayOptions {
+    /// Reads the replay options from the command line. `None` without `--replay`.
+    pub fn from_args(args: &[String], default_config: PathBuf) -> Result<Option<Self>> {
+        let value = |flag: &str| -> Result<Option<&String>> {
+            match args.iter().position(|a| a == flag) {
+                Some(i) => args.get(i + 1).map(Some).ok_or_else(|| anyhow!("{} needs a value\n{}", flag, USAGE)),
+                None => Ok(None),
+            }
+        };
+        let recording = match value("--replay")? {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(None),
+        };
+        let speed = match value("--speed")? {
+            Some(speed) => speed.parse::<f32>().ok().filter(|s| *s >= 0.0).ok_or_else(|| anyhow!("--speed must be a number, 0 or more"))?,
+            None => 1.0,
+        };
+        let options = Self {
+            recording,
+            speed,
+            config: value("--config")?.map(PathBuf::from).unwrap_or(default_config),
+            expect: value("--expect")?.map(PathBuf::from),
+            write_expected: value("--write-expected")?.map(PathBuf::from),
+        };
+        if options.expect.is_some() && options.write_expected.is_some() {
+            return Err(anyhow!("--expect and --write-expected can't be used together\n{}", USAGE));
+        }
+        Ok(Some(options))
+    }
+
+    fn checks_trace(&self) -> bool { self.expect.is_some() || self.write_expected.is_some() }
+}
+
+/// An app that `pattern` picks under `rule`, as (process name, executable path, display name).
+/// Wildcards are left out of the sample; a regex needs to contain its match literally. Patterns
+/// without a sample turn nothing in the replay.
+fn sample_app(rule: MatchRule, pattern: &str) -> Option<(String, String, String)> {
+    let literal = pattern.replace('*', "").replace('?', "x");
+    let name_of = |path: &str| {
+        let file = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        clean_process_name(file).to_lowercase()
+    };
+    let app = match rule {
+        MatchRule::Name => (clean_process_name(pattern).to_lowercase(), String::new(), String::new()),
+        MatchRule::Glob => (clean_process_name(&literal).to_lowercase(), String::new(), String::new()),
+        MatchRule::Regex => {
+            let literal: String = pattern.chars().filter(|c| !"^$\\.*+?()[]{}|".contains(*c)).collect();
+            (literal.to_lowercase(), String::new(), String::new())
+        }
+        MatchRule::Path => {
+            // A folder like `C:\Games\*` needs a file in it
+            let path = pattern.replace('*', "app").replace('?', "x");
+            (name_of(&path), path, String::new())
+        }
+        MatchRule::DisplayName => (literal.to_lowercase().replace(' ', ""), String::new(), literal.clone()),
+    };
+    let session = AudioSession { id: 0, pid: 0, process_name: app.0.clone(), exe_path: app.1.clone(), display_name: app.2.clone() };
+    let fits = !app.0.is_empty() && SessionMatcher::new(rule, pattern).is_ok_and(|m| m.matches(&session));
+    if !fits { println!("DEBUG: No sample app for \"{}\", nothing is turned for it in the replay.", pattern); }
+    fits.then_some(app)
+}
+
+/// A mock mixer with a sample app for every process pattern and a device for every device name
+/// the mapping mentions, plus one unmapped app for "Others".
+fn mock_backend(config: &AppConfig) -> MockBackend {
+    let mut apps: Vec<(String, String, String)> = Vec::new();
+    let mut playback: Vec<String> = Vec::new();
+    let mut capture: Vec<String> = Vec::new();
+    let add = |list: &mut Vec<String>, name: &str| {
+        if !name.is_empty() && name != "None" && !list.iter().any(|n| n == name) { list.push(name.to_string()); }
+    };
+    let mut add_app = |rule: MatchRule, pattern: &str| {
+        if let Some(app) = sample_app(rule, pattern) {
+            if !apps.iter().any(|a| a.0 == app.0) { apps.push(app); }
+        }
+    };
+
+    for controller in &config.controllers {
+        let dials = controller.dials.iter().chain(controller.profiles.iter().flat_map(|p| p.dials.iter()));
+        for dial in dials {
+            match (dial.dial_type, dial.process_name.as_deref()) {
+                (DialType::Process | DialType::Crossfade, _) => {
+                    for (name, _) in dial.process_targets() { add_app(dial.match_rule, name); }
+                }
+                (DialType::Microphone, Some(name)) => add(&mut capture, name),
+                _ => {}
+            }
+        }
+        for position in &controller.selector {
+            for output in &position.outputs { add(&mut playback, output); }
+            if let Some(input) = &position.input { add(&mut capture, input); }
+        }
+        let actions = controller.buttons.iter().flat_map(|b| b.press.iter().chain(&b.long_press).chain(&b.double_press));
+        for action in actions {
+            match action {
+                ButtonAction::ToggleMute { target_type: DialType::Process, process_name: Some(name), match_rule } => {
+                    add_app(*match_rule, name);
+                }
+                ButtonAction::SwitchDevice { device } => add(&mut playback, device),
+                _ => {}
+            }
+        }
+    }
+    add_app(MatchRule::Name, "unmapped-app");
+
+    let mut mock = MockBackend::new();
+    for (pid, (name, exe_path, display_name)) in apps.iter().enumerate() {
+        mock = mock.with_app(pid as u32 + 1, name, exe_path, display_name);
+    }
+    for name in &playback { mock = mock.with_device(DataFlow::Render, name); }
+    for name in &capture { mock = mock.with_device(DataFlow::Capture, name); }
+    mock
+}
+
+/// A controller's handle on the mock mixer, so the calls of every controller end up in one trace
+/// in the order they were made.
+struct SharedMock<'a>(&'a RefCell<MockBackend>);
+
+impl AudioBackend for SharedMock<'_> {
+    fn list_sessions(&mut self) -> Result<Vec<AudioSession>> { self.0.borrow_mut().list_sessions() }
+    fn session_volume(&mut self, session: &AudioSession) -> Result<f32> { self.0.borrow_mut().session_volume(session) }
+    fn set_session_volume(&mut self, session: &AudioSession, volume: f32) -> Result<()> { self.0.borrow_mut().set_session_volume(session, volume) }
+    fn endpoint_volume(&mut self, endpoint: &Endpoint) -> Result<f32> { self.0.borrow_mut().endpoint_volume(endpoint) }
+    fn set_endpoint_volume(&mut self, endpoint: &Endpoint, volume: f32) -> Result<()> { self.0.borrow_mut().set_endpoint_volume(endpoint, volume) }
+    fn list_devices(&mut self, flow: DataFlow) -> Result<Vec<AudioDevice>> { self.0.borrow_mut().list_devices(flow) }
+    fn set_default_device(&mut self, flow: DataFlow, device: &AudioDevice) -> Result<()> { self.0.borrow_mut().set_default_device(flow, device) }
+    fn session_muted(&mut self, session: &AudioSession) -> Result<bool> { self.0.borrow_mut().session_muted(session) }
+    fn set_session_mute(&mut self, session: &AudioSession, muted: bool) -> Result<()> { self.0.borrow_mut().set_session_mute(session, muted) }
+    fn endpoint_muted(&mut self, endpoint: &Endpoint) -> Result<bool> { self.0.borrow_mut().endpoint_muted(endpoint) }
+    fn set_endpoint_mute(&mut self, endpoint: &Endpoint, muted: bool) -> Result<()> { self.0.borrow_mut().set_endpoint_mute(endpoint, muted) }
+}
+
+/// Plays the recording into one pipeline per controller, line by line in recording order, handing
+/// the pipelines the recording's clock.
+fn replay_controllers(config: &AppConfig, replay: &mut ReplayTransport, backends: &mut [Box<dyn AudioBackend + '_>]) -> Result<()> {
+    let mut events: Vec<fn(EngineEvent)> = vec![|_| {}; backends.len()];
+    let mut pipelines: Vec<DialPipeline> = backends.iter_mut().zip(events.iter_mut()).enumerate()
+        .map(|(index, (backend, events))| DialPipeline::new(config.clone(), index, backend.as_mut(), events))
+        .collect();
+    for (index, pipeline) in pipelines.iter().enumerate() {
+        if pipeline.controller().is_none() {
+            println!("ERROR: The recording has lines for controller {}, but mapping.json has no such controller.", index + 1);
+        }
+    }
+    pipelines.retain(|p| p.controller().is_some());
+
+    let base = Instant::now();
+    loop {
+        match replay.next_event()? {
+            TransportEvent::Connected(_) => pipelines.iter_mut().for_each(|p| p.reset_connection()),
+            TransportEvent::Line(line) => {
+                // Timers of every controller that ran out before this line fire first, as they would have live
+                for pipeline in &mut pipelines {
+                    pipeline.set_clock(base + replay.position());
+                    pipeline.poll();
+                }
+                if let Some(pipeline) = pipelines.iter_mut().find(|p| p.index() == replay.controller()) {
+                    pipeline.handle_line(&line);
+                }
+            }
+            TransportEvent::Midi { control, value } => {
+                if let Some(pipeline) = pipelines.iter_mut().find(|p| p.index() == replay.controller()) {
+                    pipeline.handle_midi(control, value);
+                }
+            }
+            TransportEvent::Idle => {}
+            TransportEvent::Finished => break,
+        }
+    }
+    // Let pending presses complete
+    for pipeline in &mut pipelines {
+        pipeline.set_clock(base + replay.position() + Duration::from_secs(1));
+        pipeline.poll();
+    }
+    Ok(())
+}
+
+/// Returns whether the volume calls matched the expected trace. Always true without `--expect`.
+pub fn run(options: &ReplayOptions) -> Result<bool> {
+    let config = config::load_config(&options.config).map_err(|e| anyhow!("Could not load {}: {}", options.config.display(), e))?;
+    let recording = load_recording(&options.recording)?;
+    let controllers = recording.iter().map(|l| l.controller + 1).max().unwrap_or(0);
+    println!("DEBUG: Replaying {} lines from {} at {}x.", recording.len(), options.recording.display(), options.speed);
+
+    let mock = RefCell::new(mock_backend(&config));
+    {
+        // Live, every controller has a mixer connection of its own
+        let mut backends: Vec<Box<dyn AudioBackend + '_>> = (0..controllers)
+            .map(|_| if options.checks_trace() { Box::new(SharedMock(&mock)) } else { audio::create_backend() })
+            .collect();
+        let mut replay = ReplayTransport::new(recording, options.speed);
+        replay_controllers(&config, &mut replay, &mut backends)?;
+    }
+    let mock = mock.into_inner();
+
+    let actual: Vec<String> = mock.calls.iter().map(|c| c.to_string()).collect();
+    if let Some(path) = &options.write_expected {
+        let mut text = actual.join("\n");
+        text.push('\n');
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {} volume calls to {}.", actual.len(), path.display());
+        return Ok(true);
+    }
+    let path = match &options.expect {
+        Some(path) => path,
+        None => return Ok(true),
+    };
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let expected: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).collect();
+
+    let mismatch = (0..expected.len().max(actual.len())).find(|&i| expected.get(i).copied() != actual.get(i).map(String::as_str));
+    match mismatch {
+        None => {
+            println!("Replay matches {}: {} volume calls.", path.display(), actual.len());
+            Ok(true)
+        }
+        Some(i) => {
+            println!("Replay does not match {} at volume call {}:", path.display(), i + 1);
+            println!("  expected: {}", expected.get(i).copied().unwrap_or("(nothing more)"));
+            println!("  got:      {}", actual.get(i).map(String::as_str).unwrap_or("(nothing more)"));
+            println!("Expected {} calls, got {}.", expected.len(), actual.len());
+            Ok(false)
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! `RVCI --simulate` pretends to be a controller, so mappings can be tried without an Arduino on
+//! the desk. It speaks the deej lines of `firmware.ino` over a pseudo-terminal (which RVCI then
+//! finds like a serial port), an existing serial port, or a TCP connection to a controller set to
+//! TCP. What it sends comes from a script or from commands typed into the terminal.
+
+use crate::detect;
+use anyhow::{anyhow, Context, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "Usage: RVCI --simulate [--pty | --port <serial port> | --tcp <host:port> [--token <token>]]
+                     [--script <file>] [--sliders <n>] [--buttons <n>] [--encoders <n>]";
+
+pub const COMMANDS: &str = "Commands, one per line:
+  slider <n> <0-1023> [seconds]   move slider n, gradually when seconds are given
+  switch <position>               move the selector switch
+  press <n>                       press and release button n
+  hold <n> <seconds>              hold button n down
+  turn <n> <steps>                turn encoder n, negative steps turn it back
+  wait <seconds>                  pause the script
+  send <line>                     send a line as is
+  quit";
+
+/// Slider lines are sent this often, like the firmware's loop.
+const STREAM_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How often a simulated pseudo-terminal tells RVCI it's still there.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum Target {
+    /// A new pseudo-terminal, announced to RVCI's port list. Unix only.
+    Pty,
+    /// One end of a virtual null-modem pair like com0com.
+    Port(String),
+    Tcp { address: String, token: Option<String> },
+}
+
+pub struct SimulatorOptions {
+    pub target: Target,
+    /// Commands are read from the terminal without one.
+    pub script: Option<PathBuf>,
+    pub sliders: usize,
+    pub buttons: usize,
+    pub encoders: usize,
+}
+
+impl SimulatorOptions {
+    /// Reads the simulator options from the command line. `None` without `--simulate`.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>> {
+        if !args.iter().any(|a| a == "--simulate") { return Ok(None); }
+        let value = |flag: &str| -> Result<Option<&String>> {
+            match args.iter().position(|a| a == flag) {
+                Some(i) => args.get(i + 1).map(Some).ok_or_else(|| anyhow!("{} needs a value\n{}", flag, USAGE)),
+                None => Ok(None),
+            }
+        };
+        let count = |flag: &str, default: usize| -> Result<usize> {
+            match value(flag)? {
+                Some(n) => n.parse().map_err(|_| anyhow!("{} must be a number", flag)),
+                None => Ok(default),
+            }
+        };
+        let target = match (value("--tcp")?, value("--port")?) {
+            (Some(_), Some(_)) => return Err(anyhow!("--tcp and --port can't be used together\n{}", USAGE)),
+            (Some(address), None) => Target::Tcp { address: address.clone(), token: value("--token")?.cloned() },
+            (None, Some(port)) => Target::Port(port.clone()),
+            (None, None) => Target::Pty,
+        };
+        Ok(Some(Self {
+            target,
+            script: value("--script")?.map(PathBuf::from),
+            sliders: count("--sliders", 5)?,
+            buttons: count("--buttons", 2)?,
+            encoders: count("--encoders", 0)?,
+        }))
+    }
+}
+
+/// One thing for the simulator to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Send(String),
+    /// Slider `index` (0-based) moves to `value`, sent with the next slider line.
+    Slider { index: usize, value: u16 },
+    /// Slider `index` slides from `from` to `to` over `duration`, a little with every slider line.
+    /// The next step waits until it's there.
+    Ramp { index: usize, from: u16, to: u16, duration: Duration },
+    Wait(Duration),
+    Quit,
+}
+
+/// The state of the pretend controller.
+pub struct SimulatedController {
+    /// What the sliders send right now.
+    pub sliders: Vec<u16>,
+    /// Where the sliders end up once the gradual moves are done.
+    targets: Vec<u16>,
+    pub switch: usize,
+    pub buttons: usize,
+    pub encoders: usize,
+}
+
+/// Only takes as long as the clock can count from now.
+fn parse_seconds(text: Option<&str>) -> Result<Duration> {
+    text.and_then(|t| t.parse::<f64>().ok())
+        .and_then(|s| Duration::try_from_secs_f64(s).ok())
+        .filter(|d| Instant::now().checked_add(*d).is_some())
+        .ok_or_else(|| anyhow!("expected a number of seconds"))
+}
+
+impl SimulatedController {
+    pub fn new(sliders: usize, buttons: usize, encoders: usize) -> Self {
+        Self { sliders: vec![0; sliders], targets: vec![0; sliders], switch: 1, buttons, encoders }
+    }
+
+    /// What the firmware sends on boot and in reply to `HELLO?`.
+    pub fn hello(&self) -> Vec<String> {
+        vec![
+            format!("HELLO version=1 sliders={} switches=1 buttons={} encoders={} name=RVCI Simulator", self.sliders.len(), self.buttons, self.encoders),
+            format!("WORKS {}", self.switch),
+        ]
+    }
+
+    /// `None` without sliders.
+    pub fn slider_line(&self) -> Option<String> {
+        if self.sliders.is_empty() { return None; }
+        Some(self.sliders.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("|"))
+    }
+
+    /// Turns a command (see `COMMANDS`) into steps. Blank lines and `#` comments are no steps.
+    pub fn command(&mut self, line: &str) -> Result<Vec<Step>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { return Ok(Vec::new()); }
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut args = rest.split_whitespace();
+        let mut number = |what: &str, count: usize| -> Result<usize> {
+            let n = args.next().and_then(|n| n.parse::<usize>().ok()).ok_or_else(|| anyhow!("expected a {} number", what))?;
+            if n == 0 || n > count { return Err(anyhow!("there is no {} {}, the simulator has {}", what, n, count)); }
+            Ok(n)
+        };
+        Ok(match word {
+            "slider" => {
+                let index = number("slider", self.sliders.len())? - 1;
+                let value = args.next().and_then(|v| v.parse::<u16>().ok()).filter(|v| *v <= 1023).ok_or_else(|| anyhow!("expected a value from 0 to 1023"))?;
+                let duration = match args.next() {
+                    Some(seconds) => parse_seconds(Some(seconds))?,
+                    None => Duration::ZERO,
+                };
+                let from = self.targets[index];
+                self.targets[index] = value;
+                if duration.is_zero() {
+                    vec![Step::Slider { index, value }]
+                } else {
+                    vec![Step::Ramp { index, from, to: value, duration }]
+                }
+            }
+            "switch" => {
+                let position = args.next().and_then(|p| p.parse::<usize>().ok()).filter(|p| *p > 0).ok_or_else(|| anyhow!("expected a switch position"))?;
+                self.switch = position;
+                vec![Step::Send(format!("WORKS {}", position))]
+            }
+            "press" => {
+                let n = number("button", self.buttons)?;
+                vec![Step::Send(format!("BUTTON {} DOWN", n)), Step::Send(format!("BUTTON {} UP", n))]
+            }
+            "hold" => {
+                let n = number("button", self.buttons)?;
+                let duration = parse_seconds(args.next())?;
+                vec![Step::Send(format!("BUTTON {} DOWN", n)), Step::Wait(duration), Step::Send(format!("BUTTON {} UP", n))]
+            }
+            "turn" => {
+                let n = number("encoder", self.encoders)?;
+                let steps = args.next().and_then(|s| s.parse::<i32>().ok()).ok_or_else(|| anyhow!("expected a number of steps"))?;
+                vec![Step::Send(format!("ENCODER {} {:+}", n, steps))]
+            }
+            "wait" => vec![Step::Wait(parse_seconds(args.next())?)],
+            "send" => vec![Step::Send(rest.trim().to_string())],
+            "quit" => vec![Step::Quit],
+            _ => return Err(anyhow!("unknown command \"{}\"", word)),
+        })
+    }
+}
+
+trait Link: Read + Write + Send {}
+impl<T: Read + Write + Send> Link for T {}
+
+/// The connection to RVCI. Keeps the far end of a pseudo-terminal open so RVCI can come and go.
+struct Connection {
+    link: Box<dyn Link>,
+    _pty_slave: Option<Box<dyn Link>>,
+    pty_name: Option<String>,
+    received: Vec<u8>,
+}
+
+impl Connection {
+    fn open(target: &Target) -> Result<Self> {
+        match target {
+            Target::Pty => Self::open_pty(),
+            Target::Port(port) => {
+                let link = serialport::new(port, 115200).timeout(Duration::from_millis(10)).open().with_context(|| format!("Failed to open {}", port))?;
+                println!("Simulating a controller on {}.", port);
+                Ok(Self { link: Box::new(link), _pty_slave: None, pty_name: None, received: Vec::new() })
+            }
+            Target::Tcp { address, token } => {
+                let mut stream = TcpStream::connect(address).with_context(|| format!("Failed to connect to {}", address))?;
+                stream.set_read_timeout(Some(Duration::from_millis(10)))?;
+                stream.set_nodelay(true)?;
+                if let Some(token) = token {
+                    stream.write_all(format!("AUTH {}\n", token).as_bytes())?;
+                }
+                println!("Simulating a controller connected to {}.", address);
+                Ok(Self { link: Box::new(stream), _pty_slave: None, pty_name: None, received: Vec::new() })
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn open_pty() -> Result<Self> {
+        use serialport::SerialPort;
+        let (mut master, slave) = serialport::TTYPort::pair().context("Failed to create a pseudo-terminal")?;
+        master.set_timeout(Duration::from_millis(10))?;
+        let name = slave.name().ok_or_else(|| anyhow!("The pseudo-terminal has no name"))?;
+        println!("Simulating a controller on {}. RVCI finds it with the port on Auto-detect, or pick it in the port list.", name);
+        Ok(Self { link: Box::new(master), _pty_slave: Some(Box::new(slave)), pty_name: Some(name), received: Vec::new() })
+    }
+
+    #[cfg(not(unix))]
+    fn open_pty() -> Result<Self> {
+        Err(anyhow!("Pseudo-terminals are not supported on this system. Use --tcp, or --port with one end of a com0com pair.\n{}", USAGE))
+    }
+
+    /// Lines that arrived from RVCI since the last call.
+    fn receive(&mut self) -> Result<Vec<String>> {
+        let mut buf = [0u8; 256];
+        match self.link.read(&mut buf) {
+            Ok(0) if self.pty_name.is_none() => return Err(anyhow!("RVCI closed the connection")),
+            Ok(n) => self.received.extend_from_slice(&buf[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut lines = Vec::new();
+        while let Some(end) = self.received.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+        Ok(lines)
+    }
+
+    /// Lines nobody reads are dropped, like a controller sending into an unopened port.
+    fn send(&mut self, line: &str) -> Result<()> {
+        match self.link.write_all(format!("{}\r\n", line).as_bytes()) {
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+/// Script lines, or lines typed into the terminal as they come.
+enum Source {
+    Script(VecDeque<String>),
+    Terminal(Receiver<String>),
+}
+
+impl Source {
+    /// `None` when there is nothing more to come.
+    fn next_line(&mut self) -> Option<Option<String>> {
+        match self {
+            Source::Script(lines) => lines.pop_front().map(Some),
+            Source::Terminal(typed) => match typed.try_recv() {
+                Ok(line) => Some(Some(line)),
+                Err(TryRecvError::Empty) => Some(None),
+                Err(TryRecvError::Disconnected) => None,
+            },
+        }
+    }
+}
+
+/// Runs until the script ends, `quit` is typed or the connection breaks.
+pub fn run(options: &SimulatorOptions) -> Result<()> {
+    let mut source = match &options.script {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            Source::Script(text.lines().map(str::to_string).collect())
+        }
+        None => {
+            let (tx, rx) = channel();
+            std::thread::spawn(move || {
+                for line in std::io::stdin().lock().lines().map_while(|l| l.ok()) {
+                    if tx.send(line).is_err() { break; }
+                }
+            });
+            println!("{}", COMMANDS);
+            Source::Terminal(rx)
+        }
+    };
+
+    let mut controller = SimulatedController::new(options.sliders, options.buttons, options.encoders);
+    let mut connection = Connection::open(&options.target)?;
+    let result = simulate(&mut controller, &mut connection, &mut source);
+    if connection.pty_name.is_some() { detect::withdraw_simulator_port(); }
+    result
+}
+
+fn simulate(controller: &mut SimulatedController, connection: &mut Connection, source: &mut Source) -> Result<()> {
+    for line in controller.hello() { connection.send(&line)?; }
+    let mut steps: VecDeque<Step> = VecDeque::new();
+    let mut waiting_until: Option<Instant> = None;
+    // The gradual move in progress and when it started
+    let mut ramp: Option<(Step, Instant)> = None;
+    let mut last_stream = Instant::now();
+    let mut last_publish: Option<Instant> = None;
+
+    loop {
+        if let Some(name) = &connection.pty_name {
+            if last_publish.is_none_or(|t| t.elapsed() >= PUBLISH_INTERVAL) {
+                detect::publish_simulator_port(name);
+                last_publish = Some(Instant::now());
+            }
+        }
+        for request in connection.receive()? {
+            if request == "HELLO?" {
+                for line in controller.hello() { connection.send(&line)?; }
+            }
+        }
+
+        if waiting_until.is_some_and(|until| Instant::now() >= until) { waiting_until = None; }
+        if let Some((Step::Ramp { index, from, to, duration }, start)) = &ramp {
+            // The wait ends with the move, so the next step starts from where it ended
+            let done = if waiting_until.is_none() { 1.0 } else { (start.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0) };
+            controller.sliders[*index] = (*from as f64 + (*to as f64 - *from as f64) * done).round() as u16;
+            if done >= 1.0 { ramp = None; }
+        }
+        while waiting_until.is_none() {
+            let step = match steps.pop_front() {
+                Some(step) => step,
+                None => match source.next_line() {
+                    Some(Some(line)) => {
+                        match controller.command(&line) {
+                            Ok(new_steps) => steps.extend(new_steps),
+                            Err(e) => println!("ERROR: {}: {}", line.trim(), e),
+                        }
+                        continue;
+                    }
+                    Some(None) => break,
+                    // Give the last slider values time to go out before stopping
+                    None => Step::Quit,
+                },
+            };
+            match step {
+                Step::Send(line) => connection.send(&line)?,
+                Step::Slider { index, value } => controller.sliders[index] = value,
+                Step::Ramp { duration, .. } | Step::Wait(duration) => match Instant::now().checked_add(duration) {
+                    Some(until) => {
+                        waiting_until = Some(until);
+                        if matches!(step, Step::Ramp { .. }) { ramp = Some((step, Instant::now())); }
+                    }
+                    None => println!("ERROR: {:?} is longer than the clock can count", duration),
+                },
+                Step::Quit => {
+                    std::thread::sleep(STREAM_INTERVAL * 2);
+                    if let Some(line) = controller.slider_line() { connection.send(&line)?; }
+                    return Ok(());
+                }
+            }
+        }
+
+        if last_stream.elapsed() >= STREAM_INTERVAL {
+            last_stream = Instant::now();
+            if let Some(line) = controller.slider_line() { connection.send(&line)?; }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Taking the jitter out of a pot's readings before they become a volume, and picking filter
+//! settings from how much a pot wobbles while nobody touches it.
+
+use crate::config::{DialConfig, DialFilter};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Cutoff (Hz) for the one-euro filter's estimate of how fast the knob turns.
+const DERIVATIVE_CUTOFF: f32 = 1.0;
+
+/// Readings of a resting pot auto-tune needs before it trusts what it saw.
+pub const TUNE_READINGS: usize = 40;
+
+/// A resting pot whose readings stay within this much (of its full range) needs no filter.
+const CLEAN_SPREAD: f32 = 0.004;
+
+/// Spread this many times the standard deviation means a quiet pot with the odd spike.
+const SPIKY_RATIO: f32 = 6.0;
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Fresh,
+    Ema { last: f32 },
+    OneEuro { value: f32, speed: f32, at: Instant },
+    Median { window: VecDeque<f32> },
+}
+
+/// Filter state of one knob.
+#[derive(Default)]
+pub struct Smoother {
+    state: State,
+    /// Direction of the last change that was passed on, for hysteresis.
+    direction: f32,
+}
+
+/// Smoothing factor for a low-pass filter at `cutoff` Hz over `dt` seconds.
+fn alpha(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+impl Smoother {
+    /// Runs `value` through `filter`. Switching to another kind of filter starts it afresh.
+    pub fn process(&mut self, filter: &DialFilter, value: f32, now: Instant) -> f32 {
+        match *filter {
+            DialFilter::None => {
+                self.state = State::Fresh;
+                value
+            }
+            DialFilter::Ema { factor, jump } => {
+                let last = match self.state {
+                    State::Ema { last } => last,
+                    _ => 0.0,
+                };
+                let delta = value - last;
+                let next = if delta.abs() >= jump { value } else { last + delta * factor };
+                self.state = State::Ema { last: next };
+                next
+            }
+            DialFilter::OneEuro { min_cutoff, beta } => {
+                let (last, speed, at) = match self.state {
+                    State::OneEuro { value, speed, at } => (value, speed, at),
+                    _ => {
+                        self.state = State::OneEuro { value, speed: 0.0, at: now };
+                        return value;
+                    }
+                };
+                let dt = now.duration_since(at).as_secs_f32();
+                if dt <= 0.0 { return last; }
+                let speed = speed + alpha(DERIVATIVE_CUTOFF, dt) * ((value - last) / dt - speed);
+                let cutoff = min_cutoff + beta * speed.abs();
+                let next = last + alpha(cutoff, dt) * (value - last);
+                self.state = State::OneEuro { value: next, speed, at: now };
+                next
+            }
+            DialFilter::Median { samples } => {
+                let window = match &mut self.state {
+                    State::Median { window } => window,
+                    state => {
+                        *state = State::Median { window: VecDeque::from([value]) };
+                        return value;
+                    }
+                };
+                window.push_back(value);
+                while window.len() > samples.max(1) { window.pop_front(); }
+                let mut sorted: Vec<f32> = window.iter().copied().collect();
+                sorted.sort_by(f32::total_cmp);
+                sorted[sorted.len() / 2]
+            }
+        }
+    }
+
+    /// Whether `value` moved far enough from `last`, the volume applied last, to be passed on.
+    /// `last` is negative when nothing was applied yet. The ends of the output range and the
+    /// snap volumes always get through, so a wide deadband can't keep a knob from reaching them.
+    pub fn should_apply(&mut self, dial: &DialConfig, value: f32, last: f32) -> bool {
+        let change = value - last;
+        if last >= 0.0 {
+            let landmark = value == dial.min_output || value == dial.max_output || dial.snap_to.contains(&value);
+            let lands = landmark && change != 0.0;
+            let turns_back = self.direction != 0.0 && change.signum() != self.direction;
+            if !lands && (change.abs() < dial.deadband || (turns_back && change.abs() < dial.hysteresis)) {
+                return false;
+            }
+        }
+        self.direction = if last >= 0.0 { change.signum() } else { 0.0 };
+        true
+    }
+}
+
+/// Filter settings picked by `auto_tune`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    pub filter: DialFilter,
+    pub deadband: f32,
+    pub hysteresis: f32,
+}
+
+fn round_to(value: f32, places: i32) -> f32 {
+    let scale = 10f32.powi(places);
+    (value * scale).round() / scale
+}
+
+/// How far apart the highest and lowest of `positions` are.
+pub fn spread(positions: &[f32]) -> f32 {
+    let low = positions.iter().copied().fold(f32::INFINITY, f32::min);
+    let high = positions.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if positions.is_empty() { 0.0 } else { high - low }
+}
+
+/// Picks settings for a pot from its positions (0 to 1) while it was left alone. `None` with
+/// fewer than `TUNE_READINGS` positions.
+///
+/// A steady pot gets no filter, one that is quiet apart from the odd spike a median, and one
+/// that wobbles all the time a one-euro filter that smooths harder the more it wobbles. The
+/// deadband and hysteresis cover what is left of the wobble.
+pub fn auto_tune(positions: &[f32]) -> Option<Tuning> {
+    if positions.len() < TUNE_READINGS { return None; }
+    let mean = positions.iter().sum::<f32>() / positions.len() as f32;
+    let deviation = (positions.iter().map(|p| (p - mean).powi(2)).sum::<f32>() / positions.len() as f32).sqrt();
+    let spread = spread(positions);
+
+    let filter = if spread < CLEAN_SPREAD {
+        DialFilter::None
+    } else if spread > SPIKY_RATIO * deviation {
+        DialFilter::Median { samples: 5 }
+    } else {
+        DialFilter::OneEuro { min_cutoff: round_to((0.01 / deviation).clamp(0.2, 2.0), 2), beta: 5.0 }
+    };
+    Some(Tuning {
+        filter,
+        deadband: round_to((spread * 0.5).clamp(0.002, 0.03), 3),
+        hysteresis: round_to(spread.min(0.05), 3),
+    })
+}
@@ -0,0 +1,117 @@
+use super::{Transport, TransportEvent, NETWORK_POLL};
+use crate::config::MidiControl;
+use anyhow::{anyhow, Result};
+use midir::{MidiInput, MidiInputConnection, MidiInputPort};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Name of the MIDI client, and of the virtual port created when no port is set.
+const CLIENT_NAME: &str = "RVCI";
+
+/// How often we look whether the MIDI device is still plugged in. Closed ports just go quiet.
+const PORT_CHECK: Duration = Duration::from_secs(2);
+
+/// Control changes and pitch bend, as the control and its value on a 0-127 scale. Pitch bend
+/// keeps its 14-bit resolution as a fraction.
+pub fn decode(message: &[u8]) -> Option<(MidiControl, f32)> {
+    let (&status, data) = message.split_first()?;
+    let channel = (status & 0x0f) + 1;
+    match (status & 0xf0, data) {
+        (0xb0, [number, value, ..]) => Some((MidiControl::Cc { channel, number: *number }, *value as f32)),
+        (0xe0, [lsb, msb, ..]) => {
+            let bend = (*msb as u16) << 7 | *lsb as u16;
+            Some((MidiControl::PitchBend { channel }, bend as f32 / 128.0))
+        }
+        _ => None,
+    }
+}
+
+/// Names of the MIDI inputs that are currently available.
+pub fn list_ports() -> Vec<String> {
+    let input = match MidiInput::new(CLIENT_NAME) {
+        Ok(input) => input,
+        Err(_) => return Vec::new(),
+    };
+    input.ports().iter().filter_map(|p| input.port_name(p).ok()).collect()
+}
+
+fn find_port(input: &MidiInput, wanted: &str) -> Option<(MidiInputPort, String)> {
+    let wanted = wanted.to_lowercase();
+    input.ports().into_iter()
+        .filter_map(|p| input.port_name(&p).ok().map(|name| (p, name)))
+        .find(|(_, name)| name.to_lowercase().contains(&wanted))
+}
+
+pub struct MidiTransport {
+    _connection: MidiInputConnection<()>,
+    messages: Receiver<(MidiControl, f32)>,
+    /// The configured port name, empty for our own virtual port.
+    port: String,
+    description: String,
+    announced: bool,
+    last_check: Instant,
+}
+
+impl MidiTransport {
+    pub fn open(port: &str) -> Result<Self> {
+        let input = MidiInput::new(CLIENT_NAME).map_err(|e| anyhow!("MIDI is not available: {}", e))?;
+        let (tx, messages) = channel();
+        let callback = move |_stamp: u64, message: &[u8], _: &mut ()| {
+            if let Some(decoded) = decode(message) {
+                let _ = tx.send(decoded);
+            }
+        };
+
+        let (connection, description) = if port.trim().is_empty() {
+            (Self::create_virtual(input, callback)?, format!("virtual MIDI port {}", CLIENT_NAME))
+        } else {
+            let (found, name) = find_port(&input, port.trim()).ok_or_else(|| anyhow!("No MIDI input matching '{}'", port))?;
+            let connection = input.connect(&found, CLIENT_NAME, callback, ()).map_err(|e| anyhow!("Failed to open MIDI input {}: {}", name, e))?;
+            (connection, format!("MIDI input {}", name))
+        };
+        Ok(Self { _connection: connection, messages, port: port.trim().to_string(), description, announced: false, last_check: Instant::now() })
+    }
+
+    #[cfg(unix)]
+    fn create_virtual<F>(input: MidiInput, callback: F) -> Result<MidiInputConnection<()>>
+    where
+        F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+    {
+        use midir::os::unix::VirtualInput;
+        input.create_virtual(CLIENT_NAME, callback, ()).map_err(|e| anyhow!("Failed to create a virtual MIDI port: {}", e))
+    }
+
+    #[cfg(not(unix))]
+    fn create_virtual<F>(_input: MidiInput, _callback: F) -> Result<MidiInputConnection<()>>
+    where
+        F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+    {
+        Err(anyhow!("Virtual MIDI ports are not supported on this system, pick a MIDI input"))
+    }
+}
+
+impl Transport for MidiTransport {
+    fn next_event(&mut self) -> Result<TransportEvent> {
+        if !self.announced {
+            self.announced = true;
+            return Ok(TransportEvent::Connected(self.description.clone()));
+        }
+        match self.messages.recv_timeout(NETWORK_POLL) {
+            Ok((control, value)) => Ok(TransportEvent::Midi { control, value }),
+            Err(RecvTimeoutError::Timeout) => {
+                if !self.port.is_empty() && self.last_check.elapsed() >= PORT_CHECK {
+                    self.last_check = Instant::now();
+                    let input = MidiInput::new(CLIENT_NAME).map_err(|e| anyhow!("MIDI is not available: {}", e))?;
+                    if find_port(&input, &self.port).is_none() {
+                        return Err(anyhow!("{} disappeared", self.description));
+                    }
+                }
+                Ok(TransportEvent::Idle)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("{} closed", self.description)),
+        }
+    }
+
+    /// MIDI controllers don't understand our lines, the handshake request is simply dropped.
+    fn send(&mut self, _data: &[u8]) -> Result<()> { Ok(()) }
+}
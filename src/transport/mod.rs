@@ -0,0 +1,88 @@
+//! Where controller lines come from. The engine only sees lines and (re)connects; whether they
+//! arrive over a serial port, a TCP connection, UDP datagrams or a WebSocket is up to the
+//! transport picked in the controller's `transport` section. MIDI is the odd one out and hands
+//! over decoded control changes instead of lines.
+
+use crate::config::{ControllerConfig, MidiControl, TransportConfig};
+use crate::detect::PortClaims;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod midi;
+mod network;
+pub mod replay;
+mod serial;
+mod websocket;
+
+pub use midi::{list_ports as midi_ports, MidiTransport};
+pub use network::{TcpTransport, UdpTransport};
+pub use replay::{load_recording, Recorder, ReplayTransport};
+pub use serial::SerialTransport;
+
+/// How long network and MIDI transports wait for data before letting the engine run its timers.
+const NETWORK_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportEvent {
+    /// A controller connected, described for the log. Anything learned about the previous one
+    /// (handshake, switch position) is stale and the handshake request should be sent.
+    Connected(String),
+    Line(String),
+    /// A MIDI control moved to `value` (0-127).
+    Midi { control: MidiControl, value: f32 },
+    /// Nothing arrived within the read timeout.
+    Idle,
+    /// The source has nothing more to send, like a recording that played to the end.
+    Finished,
+}
+
+pub trait Transport {
+    /// Waits up to the read timeout for the next line.
+    fn next_event(&mut self) -> Result<TransportEvent>;
+
+    /// Sends to the connected controller, if there is one.
+    fn send(&mut self, data: &[u8]) -> Result<()>;
+
+    fn set_timeout(&mut self, _timeout: Duration) {}
+}
+
+/// `ports` are the serial ports in use by all controllers, see `detect::PortClaims`.
+pub fn open(controller: &ControllerConfig, ports: &Arc<PortClaims>) -> Result<Box<dyn Transport>> {
+    Ok(match &controller.transport {
+        TransportConfig::Serial => Box::new(SerialTransport::open(&controller.serial, ports)?),
+        TransportConfig::Tcp { listen, token } => Box::new(TcpTransport::listen(listen, token.clone(), false)?),
+        TransportConfig::Websocket { listen, token } => Box::new(TcpTransport::listen(listen, token.clone(), true)?),
+        TransportConfig::Udp { listen, token } => Box::new(UdpTransport::listen(listen, token.clone())?),
+        TransportConfig::Midi { port } => Box::new(MidiTransport::open(port)?),
+    })
+}
+
+/// What a network peer has to send first when a token is configured.
+fn check_auth(line: &str, token: &str) -> bool {
+    line.trim().strip_prefix("AUTH ").is_some_and(|t| same_secret(t.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Compares every byte whatever the first difference, so how long a wrong guess takes doesn't
+/// tell a peer how much of it was right.
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && std::hint::black_box(a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y))) == 0
+}
+
+/// Takes the complete lines out of `buf`, leaving a trailing partial line in place.
+fn take_lines(buf: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = buf.drain(..=end).collect();
+        lines.push(String::from_utf8_lossy(&line).into_owned());
+    }
+    lines
+}
+
+/// A random token for pairing a networked controller, as 32 hex digits from the OS's random
+/// number generator.
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("No random numbers for a token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
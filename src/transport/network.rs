@@ -0,0 +1,285 @@
+use super::websocket::{self, Frame, FrameReader};
+use super::{check_auth, take_lines, Transport, TransportEvent, NETWORK_POLL};
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// How long a WebSocket client gets to send its HTTP upgrade request.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn is_timeout(kind: ErrorKind) -> bool {
+    matches!(kind, ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted)
+}
+
+struct Client {
+    stream: TcpStream,
+    peer: SocketAddr,
+    connected_at: Instant,
+    authenticated: bool,
+    buf: Vec<u8>,
+    /// What a WebSocket client sent of its upgrade request so far. `None` once it's answered.
+    upgrade: Option<Vec<u8>>,
+    /// Set for WebSocket clients, whose lines arrive inside frames.
+    frames: Option<FrameReader>,
+}
+
+impl Client {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self.frames {
+            Some(_) => self.stream.write_all(&websocket::encode(websocket::OP_TEXT, data)),
+            None => self.stream.write_all(data),
+        }
+    }
+
+    /// Authenticated, and done with the WebSocket upgrade if it needs one.
+    fn ready(&self) -> bool { self.authenticated && self.upgrade.is_none() }
+
+    /// Reads what the client sent and returns the complete lines in it. The error is why the
+    /// connection is over.
+    fn read(&mut self) -> std::result::Result<Vec<String>, String> {
+        let mut chunk = [0u8; 1024];
+        let mut data = match self.stream.read(&mut chunk) {
+            Ok(0) => return Err("disconnected".to_string()),
+            Ok(n) => chunk[..n].to_vec(),
+            Err(e) if is_timeout(e.kind()) => return Ok(Vec::new()),
+            Err(_) => return Err("dropped the connection".to_string()),
+        };
+
+        if let Some(request) = self.upgrade.as_mut() {
+            request.extend_from_slice(&data);
+            let response = match websocket::accept(request) {
+                Ok(Some(response)) => response,
+                Ok(None) => return Ok(Vec::new()),
+                Err(e) => return Err(format!("failed the WebSocket upgrade: {}", e)),
+            };
+            data = std::mem::take(request);
+            self.upgrade = None;
+            if self.stream.write_all(&response).is_err() { return Err("dropped the connection".to_string()); }
+        }
+
+        if let Some(frames) = self.frames.as_mut() {
+            for frame in frames.feed(&data) {
+                match frame {
+                    Frame::Data(payload) => self.buf.extend_from_slice(&payload),
+                    Frame::Ping(payload) => {
+                        let _ = self.stream.write_all(&websocket::encode(websocket::OP_PONG, &payload));
+                    }
+                    Frame::Close => {
+                        let _ = self.stream.write_all(&websocket::encode(websocket::OP_CLOSE, &[]));
+                        return Err("closed the WebSocket".to_string());
+                    }
+                    Frame::Invalid => return Err("sent an invalid WebSocket frame".to_string()),
+                }
+            }
+        } else {
+            self.buf.extend_from_slice(&data);
+        }
+        Ok(take_lines(&mut self.buf))
+    }
+}
+
+/// Listens for one controller at a time over TCP, or over a WebSocket on top of it. A new
+/// connection replaces the current one once it's through the upgrade and the pairing token, so a
+/// controller that rebooted without closing its old socket gets back in right away, and one that
+/// can't pair doesn't get in the way of the one that did.
+pub struct TcpTransport {
+    listener: TcpListener,
+    listen: String,
+    token: Option<String>,
+    websocket: bool,
+    client: Option<Client>,
+    /// Latest connection that isn't ready to take over yet. Read without blocking, so it can't
+    /// hold up `client`.
+    newcomer: Option<Client>,
+    pending: VecDeque<TransportEvent>,
+}
+
+impl TcpTransport {
+    pub fn listen(listen: &str, token: Option<String>, websocket: bool) -> Result<Self> {
+        let listener = TcpListener::bind(listen).with_context(|| format!("Failed to listen on {}", listen))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, listen: listen.to_string(), token, websocket, client: None, newcomer: None, pending: VecDeque::new() })
+    }
+
+    fn kind(&self) -> &'static str { if self.websocket { "WebSocket" } else { "TCP" } }
+
+    fn accept(&mut self) -> Result<()> {
+        let (stream, peer) = match self.listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if is_timeout(e.kind()) => return Ok(()),
+            Err(e) => return Err(e).context("Failed to accept connection"),
+        };
+        stream.set_nonblocking(true)?;
+        let _ = stream.set_nodelay(true);
+        if let Some(previous) = self.newcomer.take() {
+            println!("DEBUG: {} client {} was replaced by {} before it was ready.", self.kind(), previous.peer, peer);
+        }
+        self.newcomer = Some(Client {
+            stream,
+            peer,
+            connected_at: Instant::now(),
+            authenticated: self.token.is_none(),
+            buf: Vec::new(),
+            upgrade: if self.websocket { Some(Vec::new()) } else { None },
+            frames: if self.websocket { Some(FrameReader::default()) } else { None },
+        });
+        Ok(())
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        if let Some(client) = self.client.take() {
+            println!("DEBUG: {} client {} {}, waiting for it to come back.", self.kind(), client.peer, reason);
+        }
+    }
+
+    /// Moves the newcomer through the upgrade and the pairing token, and lets it take over from
+    /// the current client once it's through.
+    fn check_newcomer(&mut self) -> Result<()> {
+        let kind = self.kind();
+        let newcomer = match self.newcomer.as_mut() {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        let mut lines = if newcomer.ready() { Vec::new() } else {
+            match newcomer.read() {
+                Ok(lines) => lines,
+                Err(reason) => {
+                    println!("ERROR: {} client {} {} before it was ready.", kind, newcomer.peer, reason);
+                    self.newcomer = None;
+                    return Ok(());
+                }
+            }
+        };
+        if newcomer.upgrade.is_some() {
+            if newcomer.connected_at.elapsed() >= HANDSHAKE_TIMEOUT {
+                println!("ERROR: {} rejected WebSocket client {}: no upgrade request", self.listen, newcomer.peer);
+                self.newcomer = None;
+            }
+            return Ok(());
+        }
+        if !newcomer.authenticated {
+            if lines.is_empty() { return Ok(()); }
+            let token = self.token.as_deref().unwrap_or_default();
+            if !check_auth(&lines.remove(0), token) {
+                println!("ERROR: {} client {} did not send the right pairing token, closing.", kind, newcomer.peer);
+                let _ = newcomer.write(b"AUTH FAILED\n");
+                self.newcomer = None;
+                return Ok(());
+            }
+            newcomer.authenticated = true;
+            let _ = newcomer.write(b"AUTH OK\n");
+        }
+
+        let client = self.newcomer.take().expect("newcomer checked above");
+        client.stream.set_nonblocking(false)?;
+        client.stream.set_read_timeout(Some(NETWORK_POLL))?;
+        self.disconnect("was replaced by a new connection");
+        self.pending.clear();
+        self.pending.push_back(TransportEvent::Connected(format!("{} client {}", kind, client.peer)));
+        self.pending.extend(lines.into_iter().map(TransportEvent::Line));
+        self.client = Some(client);
+        Ok(())
+    }
+
+    /// Reads what the client sent and queues the lines in it.
+    fn receive(&mut self) {
+        let client = match self.client.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+        match client.read() {
+            Ok(lines) => self.pending.extend(lines.into_iter().map(TransportEvent::Line)),
+            Err(reason) => self.disconnect(&reason),
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn next_event(&mut self) -> Result<TransportEvent> {
+        if let Some(event) = self.pending.pop_front() { return Ok(event); }
+        self.accept()?;
+        self.check_newcomer()?;
+        if self.pending.is_empty() {
+            match self.client {
+                Some(_) => self.receive(),
+                None => std::thread::sleep(NETWORK_POLL),
+            }
+        }
+        Ok(self.pending.pop_front().unwrap_or(TransportEvent::Idle))
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(client) = self.client.as_mut() {
+            client.write(data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Takes lines from UDP datagrams. The controller is whoever last sent something (or, with a
+/// token, whoever last sent the right `AUTH` line), and replies go back to that address.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    token: Option<String>,
+    peer: Option<SocketAddr>,
+    pending: VecDeque<TransportEvent>,
+}
+
+impl UdpTransport {
+    pub fn listen(listen: &str, token: Option<String>) -> Result<Self> {
+        let socket = UdpSocket::bind(listen).with_context(|| format!("Failed to listen on {}", listen))?;
+        socket.set_read_timeout(Some(NETWORK_POLL))?;
+        Ok(Self { socket, token, peer: None, pending: VecDeque::new() })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn next_event(&mut self) -> Result<TransportEvent> {
+        if let Some(event) = self.pending.pop_front() { return Ok(event); }
+        let mut datagram = [0u8; 2048];
+        let (n, from) = match self.socket.recv_from(&mut datagram) {
+            Ok(received) => received,
+            Err(e) if is_timeout(e.kind()) => return Ok(TransportEvent::Idle),
+            // Windows reports an earlier send to a closed port on the next receive
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => return Ok(TransportEvent::Idle),
+            Err(e) => return Err(e).context("Failed to receive datagram"),
+        };
+
+        // Every datagram holds whole lines, the newline after the last one is optional
+        let mut buf = datagram[..n].to_vec();
+        if buf.last() != Some(&b'\n') { buf.push(b'\n'); }
+        for line in take_lines(&mut buf) {
+            if self.peer == Some(from) {
+                self.pending.push_back(TransportEvent::Line(line));
+                continue;
+            }
+            match self.token.as_deref() {
+                None => {
+                    self.peer = Some(from);
+                    self.pending.push_back(TransportEvent::Connected(format!("UDP sender {}", from)));
+                    self.pending.push_back(TransportEvent::Line(line));
+                }
+                Some(token) if check_auth(&line, token) => {
+                    self.peer = Some(from);
+                    let _ = self.socket.send_to(b"AUTH OK\n", from);
+                    self.pending.push_back(TransportEvent::Connected(format!("UDP sender {}", from)));
+                }
+                Some(_) if line.starts_with("AUTH ") => {
+                    println!("ERROR: UDP sender {} did not send the right pairing token, ignoring it.", from);
+                    let _ = self.socket.send_to(b"AUTH FAILED\n", from);
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(self.pending.pop_front().unwrap_or(TransportEvent::Idle))
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(peer) = self.peer {
+            self.socket.send_to(data, peer)?;
+        }
+        Ok(())
+    }
+}
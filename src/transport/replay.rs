@@ -0,0 +1,129 @@
+//! Recording controller lines to a file and playing them back. A recording is plain text with
+//! one controller line per line, prefixed by the seconds since recording started and the
+//! controller number: `12.3450 1 1023|512|0|300|100`. Lines starting with `#` are comments.
+
+use super::{Transport, TransportEvent, NETWORK_POLL};
+use anyhow::{anyhow, Context, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "# RVCI recording v1: seconds controller line";
+
+/// Appends the lines of every controller to one file. Shared by the controller threads.
+pub struct Recorder {
+    start: Instant,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+        writeln!(file, "{}", HEADER)?;
+        file.flush()?;
+        Ok(Self { start: Instant::now(), file: Mutex::new(file) })
+    }
+
+    /// `controller` is 0-based. Written through right away so a crash doesn't lose the end.
+    pub fn record(&self, controller: usize, line: &str) {
+        let time = self.start.elapsed().as_secs_f64();
+        let mut file = self.file.lock().unwrap();
+        let _ = writeln!(file, "{:.4} {} {}", time, controller + 1, line.trim_end_matches(['\r', '\n']));
+        let _ = file.flush();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedLine {
+    pub time: Duration,
+    /// 0-based.
+    pub controller: usize,
+    pub line: String,
+}
+
+pub fn parse_recording(text: &str) -> Result<Vec<RecordedLine>> {
+    let mut lines = Vec::new();
+    for (number, entry) in text.lines().enumerate() {
+        if entry.trim().is_empty() || entry.starts_with('#') { continue; }
+        let mut fields = entry.splitn(3, ' ');
+        let time = fields.next().and_then(|t| t.parse::<f64>().ok()).filter(|t| *t >= 0.0);
+        let controller = fields.next().and_then(|c| c.parse::<usize>().ok()).filter(|c| *c > 0);
+        match (time, controller) {
+            (Some(time), Some(controller)) => lines.push(RecordedLine {
+                time: Duration::from_secs_f64(time),
+                controller: controller - 1,
+                line: fields.next().unwrap_or_default().to_string(),
+            }),
+            _ => return Err(anyhow!("line {}: expected \"<seconds> <controller> <line>\", got \"{}\"", number + 1, entry)),
+        }
+    }
+    Ok(lines)
+}
+
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedLine>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_recording(&text).with_context(|| format!("{} is not a recording", path.display()))
+}
+
+/// Plays back recorded lines, of every controller in the recording, in the order they arrived.
+pub struct ReplayTransport {
+    lines: VecDeque<RecordedLine>,
+    speed: f32,
+    start: Option<Instant>,
+    /// Time of the first line, playback starts right away instead of after the recording's lead-in.
+    offset: Duration,
+    position: Duration,
+    controller: usize,
+}
+
+impl ReplayTransport {
+    /// Plays `lines` `speed` times as fast as they were recorded. 0 plays them back to back.
+    pub fn new(mut lines: Vec<RecordedLine>, speed: f32) -> Self {
+        // Controller threads write their lines in turns, so a line can land after a later one
+        lines.sort_by_key(|l| l.time);
+        let offset = lines.first().map(|l| l.time).unwrap_or_default();
+        Self { lines: lines.into(), speed, start: None, offset, position: offset, controller: 0 }
+    }
+
+    /// Recording time of the last line handed out.
+    pub fn position(&self) -> Duration { self.position }
+
+    /// 0-based controller of the last line handed out.
+    pub fn controller(&self) -> usize { self.controller }
+}
+
+impl Transport for ReplayTransport {
+    fn next_event(&mut self) -> Result<TransportEvent> {
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                self.start = Some(Instant::now());
+                return Ok(TransportEvent::Connected("recording".to_string()));
+            }
+        };
+        let next = match self.lines.pop_front() {
+            Some(next) => next,
+            None => return Ok(TransportEvent::Finished),
+        };
+        if self.speed > 0.0 {
+            let due = start + next.time.saturating_sub(self.offset).div_f32(self.speed);
+            let now = Instant::now();
+            if now < due {
+                std::thread::sleep((due - now).min(NETWORK_POLL));
+                if Instant::now() < due {
+                    self.lines.push_front(next);
+                    return Ok(TransportEvent::Idle);
+                }
+            }
+        }
+        self.position = next.time;
+        self.controller = next.controller;
+        Ok(TransportEvent::Line(next.line))
+    }
+
+    /// A recording can't answer, the handshake request goes nowhere.
+    fn send(&mut self, _data: &[u8]) -> Result<()> { Ok(()) }
+}
@@ -0,0 +1,56 @@
+use super::{Transport, TransportEvent};
+use crate::config::SerialConfig;
+use crate::detect::{self, PortClaim, PortClaims};
+use anyhow::{Context, Result};
+use serialport::{ClearBuffer, SerialPort};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct SerialTransport {
+    reader: BufReader<Box<dyn SerialPort>>,
+    /// Keeps other controllers off the port until this transport is dropped.
+    port: PortClaim,
+    announced: bool,
+    line_buf: String,
+}
+
+impl SerialTransport {
+    pub fn open(serial: &SerialConfig, claims: &Arc<PortClaims>) -> Result<Self> {
+        let claim = detect::resolve_port(serial, claims)?;
+        let port = serialport::new(claim.port(), serial.baud)
+            .timeout(Duration::from_millis(serial.timeout))
+            .open()
+            .context("Failed to open serial port")?;
+        // Whatever queued up while nobody was listening is stale, like a simulator's pseudo-terminal
+        let _ = port.clear(ClearBuffer::Input);
+        Ok(Self { reader: BufReader::new(port), port: claim, announced: false, line_buf: String::new() })
+    }
+}
+
+impl Transport for SerialTransport {
+    fn next_event(&mut self) -> Result<TransportEvent> {
+        if !self.announced {
+            self.announced = true;
+            return Ok(TransportEvent::Connected(format!("serial port {}", self.port.port())));
+        }
+        self.line_buf.clear();
+        match self.reader.read_line(&mut self.line_buf) {
+            Ok(0) => Ok(TransportEvent::Idle),
+            Ok(_) => Ok(TransportEvent::Line(self.line_buf.clone())),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted | ErrorKind::InvalidData) => {
+                Ok(TransportEvent::Idle)
+            }
+            Err(e) => Err(e).context("Serial port read failed"),
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        self.reader.get_mut().write_all(data)?;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        let _ = self.reader.get_mut().set_timeout(timeout);
+    }
+}
@@ -0,0 +1,176 @@
+//! Just enough of RFC 6455 for a controller sending short text frames: the opening handshake,
+//! masked client frames, ping/pong and close. No extensions or subprotocols.
+
+use anyhow::{anyhow, Result};
+
+pub const OP_TEXT: u8 = 0x1;
+pub const OP_CLOSE: u8 = 0x8;
+pub const OP_PING: u8 = 0x9;
+pub const OP_PONG: u8 = 0xA;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_REQUEST: usize = 8192;
+/// Controller lines are tiny; anything bigger than this is not a controller.
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Takes the HTTP upgrade request off the front of `buf` and returns the answer to it, or `None`
+/// while the request is still incomplete. Whatever follows the request stays in `buf` for the
+/// frame reader.
+pub fn accept(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    let end = match buf.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end + 4,
+        None if buf.len() >= MAX_REQUEST => return Err(anyhow!("upgrade request too long")),
+        None => return Ok(None),
+    };
+    let request: Vec<u8> = buf.drain(..end).collect();
+    let request = String::from_utf8_lossy(&request);
+    if !request.starts_with("GET ") { return Err(anyhow!("not a WebSocket upgrade request")); }
+    let key = request.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim().to_string())
+        .ok_or_else(|| anyhow!("missing Sec-WebSocket-Key"))?;
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    );
+    Ok(Some(response.into_bytes()))
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's key.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// Payload of a text, binary or continuation frame. Lines can span frames.
+    Data(Vec<u8>),
+    Ping(Vec<u8>),
+    Close,
+    /// Unmasked, oversized or unknown frame. The connection can't be trusted after this.
+    Invalid,
+}
+
+/// Collects bytes from the socket and splits them into frames.
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Frame> {
+        self.buf.extend_from_slice(data);
+        let mut frames = Vec::new();
+        while let Some((frame, used)) = parse_frame(&self.buf) {
+            self.buf.drain(..used);
+            let invalid = frame == Frame::Invalid;
+            frames.push(frame);
+            if invalid { break; }
+        }
+        frames
+    }
+}
+
+/// Returns the first frame in `buf` and how many bytes it took, or `None` if it is incomplete.
+fn parse_frame(buf: &[u8]) -> Option<(Frame, usize)> {
+    if buf.len() < 2 { return None; }
+    let opcode = buf[0] & 0x0f;
+    let masked = buf[1] & 0x80 != 0;
+    let (len, mut pos) = match buf[1] & 0x7f {
+        126 => (u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?) as usize, 4),
+        127 => (u64::from_be_bytes(buf.get(2..10)?.try_into().ok()?) as usize, 10),
+        len => (len as usize, 2),
+    };
+    // Clients have to mask every frame
+    if !masked || len > MAX_FRAME { return Some((Frame::Invalid, buf.len())); }
+    let mask = buf.get(pos..pos + 4)?;
+    pos += 4;
+    let payload: Vec<u8> = buf.get(pos..pos + len)?.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+
+    let frame = match opcode {
+        0x0 | OP_TEXT | 0x2 => Frame::Data(payload),
+        OP_CLOSE => Frame::Close,
+        OP_PING => Frame::Ping(payload),
+        OP_PONG => Frame::Data(Vec::new()),
+        _ => Frame::Invalid,
+    };
+    Some((frame, pos + len))
+}
+
+/// A single unmasked server frame.
+pub fn encode(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 { msg.push(0); }
+    msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (chunk, v) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
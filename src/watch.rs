@@ -0,0 +1,86 @@
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Editors that don't save atomically fire several events per save. A change is reported once
+/// none arrived for this long.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Tells the engine when `mapping.json` changed on disk.
+///
+/// Watches the containing directory rather than the file, because saves replace the file through a
+/// rename. Falls back to polling the modification time once a second if no watcher can be created.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    rx: Receiver<()>,
+    watcher: Option<RecommendedWatcher>,
+    last_mod: Option<SystemTime>,
+    last_poll: Instant,
+    /// When the latest event of a burst that hasn't been reported yet arrived.
+    pending: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Self {
+        let (tx, rx) = channel();
+        let file_name = path.file_name().map(|n| n.to_os_string());
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                if event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name) {
+                    let _ = tx.send(());
+                }
+            }
+        })
+        .and_then(|mut w| {
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            w.watch(dir, RecursiveMode::NonRecursive)?;
+            Ok(w)
+        });
+
+        let watcher = match watcher {
+            Ok(w) => Some(w),
+            Err(e) => {
+                println!("DEBUG: File watcher unavailable ({}), polling mapping.json instead.", e);
+                None
+            }
+        };
+
+        Self {
+            path: path.to_path_buf(),
+            rx,
+            watcher,
+            last_mod: Self::modified(path),
+            last_poll: Instant::now(),
+            pending: None,
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Returns true once for every burst of changes, after it settled. Never blocks, so callers
+    /// should keep polling.
+    pub fn changed(&mut self) -> bool {
+        if self.watcher.is_some() {
+            while self.rx.try_recv().is_ok() { self.pending = Some(Instant::now()); }
+            return match self.pending {
+                Some(at) if at.elapsed() >= SETTLE => {
+                    self.pending = None;
+                    true
+                }
+                _ => false,
+            };
+        }
+
+        if self.last_poll.elapsed() < Duration::from_secs(1) { return false; }
+        self.last_poll = Instant::now();
+        let mod_time = Self::modified(&self.path);
+        if mod_time != self.last_mod {
+            self.last_mod = mod_time;
+            return true;
+        }
+        false
+    }
+}
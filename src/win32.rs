@@ -0,0 +1,89 @@
+use std::ffi::c_void;
+
+// ==========================================
+// DIRECT WINDOWS API HOOKS
+// ==========================================
+
+#[link(name = "dwmapi")]
+extern "system" {
+    fn DwmSetWindowAttribute(
+        hwnd: *mut c_void,
+        dwAttribute: u32,
+        pvAttribute: *const c_void,
+        cbAttribute: u32,
+    ) -> i32;
+}
+
+const DWMWA_USE_IMMERSIVE_DARK_MODE_WIN11: u32 = 20;
+const DWMWA_USE_IMMERSIVE_DARK_MODE_WIN10: u32 = 19;
+const DWMWA_WINDOW_CORNER_PREFERENCE: u32 = 33;
+const DWMWCP_ROUND: u32 = 2; 
+
+#[link(name = "user32")]
+extern "system" {
+    fn SetWindowPos(hWnd: *mut c_void, hWndInsertAfter: *mut c_void, X: i32, Y: i32, cx: i32, cy: i32, uFlags: u32) -> i32;
+    fn SetLayeredWindowAttributes(hwnd: *mut c_void, crKey: u32, bAlpha: u8, dwFlags: u32) -> i32;
+
+    #[cfg(target_arch = "x86_64")]
+    fn GetWindowLongPtrW(hWnd: *mut c_void, nIndex: i32) -> isize;
+    #[cfg(target_arch = "x86_64")]
+    fn SetWindowLongPtrW(hWnd: *mut c_void, nIndex: i32, dwNewLong: isize) -> isize;
+
+    #[cfg(target_arch = "x86")]
+    fn GetWindowLongW(hWnd: *mut c_void, nIndex: i32) -> isize;
+    #[cfg(target_arch = "x86")]
+    fn SetWindowLongW(hWnd: *mut c_void, nIndex: i32, dwNewLong: isize) -> isize;
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn get_win_long(hwnd: *mut c_void, idx: i32) -> isize { GetWindowLongPtrW(hwnd, idx) }
+#[cfg(target_arch = "x86_64")]
+unsafe fn set_win_long(hwnd: *mut c_void, idx: i32, val: isize) -> isize { SetWindowLongPtrW(hwnd, idx, val) }
+
+#[cfg(target_arch = "x86")]
+unsafe fn get_win_long(hwnd: *mut c_void, idx: i32) -> isize { GetWindowLongW(hwnd, idx) }
+#[cfg(target_arch = "x86")]
+unsafe fn set_win_long(hwnd: *mut c_void, idx: i32, val: i32) -> isize { SetWindowLongW(hwnd, idx, val as isize) }
+
+const HWND_TOPMOST: isize = -1;
+const SWP_NOSIZE: u32 = 0x0001;
+const SWP_NOMOVE: u32 = 0x0002;
+const GWL_EXSTYLE: i32 = -20;
+const WS_EX_LAYERED: isize = 0x00080000;
+const WS_EX_TRANSPARENT: isize = 0x00000020;
+const LWA_COLORKEY: u32 = 0x00000001;
+const LWA_ALPHA: u32 = 0x00000002;
+
+pub unsafe fn apply_main_window_theme(hwnd: *mut c_void) {
+    let preference: u32 = DWMWCP_ROUND;
+    DwmSetWindowAttribute(hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
+    
+    let dark_mode: u32 = 1;
+    DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE_WIN11, &dark_mode as *const u32 as *const c_void, 4);
+    DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE_WIN10, &dark_mode as *const u32 as *const c_void, 4);
+}
+
+pub unsafe fn apply_osd_window_style(bg_hwnd: *mut c_void, fg_hwnd: *mut c_void) {
+    let preference: u32 = DWMWCP_ROUND;
+
+    // --- LAYER 1: The Tinted Box ---
+    DwmSetWindowAttribute(bg_hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
+
+    let mut ex_style = get_win_long(bg_hwnd, GWL_EXSTYLE);
+    ex_style |= WS_EX_LAYERED | WS_EX_TRANSPARENT; 
+    set_win_long(bg_hwnd, GWL_EXSTYLE, ex_style);
+    
+    SetLayeredWindowAttributes(bg_hwnd as _, 0, 180, LWA_ALPHA); 
+    SetWindowPos(bg_hwnd, HWND_TOPMOST as *mut c_void, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE);
+
+    // --- LAYER 2: The Text ---
+    DwmSetWindowAttribute(fg_hwnd, DWMWA_WINDOW_CORNER_PREFERENCE, &preference as *const u32 as *const c_void, 4);
+
+    let mut ex_style_fg = get_win_long(fg_hwnd, GWL_EXSTYLE);
+    ex_style_fg |= WS_EX_LAYERED | WS_EX_TRANSPARENT; 
+    set_win_long(fg_hwnd, GWL_EXSTYLE, ex_style_fg);
+
+    SetLayeredWindowAttributes(fg_hwnd as _, 0x00000000, 0, LWA_COLORKEY);
+
+    SetWindowPos(fg_hwnd, HWND_TOPMOST as *mut c_void, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE);
+}
@@ -0,0 +1,107 @@
+#[allow(dead_code)]
+#[path = "../src/buttons.rs"]
+mod buttons;
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+
+use buttons::{Gesture, GestureDetector, DOUBLE_PRESS_GAP, LONG_PRESS};
+use config::{ButtonAction, ButtonConfig};
+use std::time::{Duration, Instant};
+
+/// A button with actions for the gestures named in `gestures`.
+fn button(gestures: &[Gesture]) -> ButtonConfig {
+    let actions = |gesture| if gestures.contains(&gesture) { vec![ButtonAction::CycleProfile] } else { vec![] };
+    ButtonConfig {
+        press: actions(Gesture::Press),
+        long_press: actions(Gesture::LongPress),
+        double_press: actions(Gesture::DoublePress),
+        midi: None,
+    }
+}
+
+fn ms(n: u64) -> Duration { Duration::from_millis(n) }
+
+#[test]
+fn plain_buttons_fire_on_release() {
+    let config = button(&[Gesture::Press]);
+    let mut detector = GestureDetector::new();
+    let t0 = Instant::now();
+    assert_eq!(detector.button(0, true, &config, t0), None);
+    assert_eq!(detector.button(0, false, &config, t0 + ms(80)), Some(Gesture::Press));
+    // Held past the long press time, it's still a press since there's nothing else to do
+    assert_eq!(detector.button(0, true, &config, t0 + ms(200)), None);
+    assert!(detector.poll(std::slice::from_ref(&config), t0 + ms(200) + LONG_PRESS).is_empty());
+    assert_eq!(detector.button(0, false, &config, t0 + ms(1000)), Some(Gesture::Press));
+    // An UP without a DOWN, e.g. right after connecting, is ignored
+    assert_eq!(detector.button(0, false, &config, t0 + ms(1100)), None);
+}
+
+#[test]
+fn holding_fires_a_long_press_without_a_press() {
+    let config = button(&[Gesture::Press, Gesture::LongPress]);
+    let configs = [config.clone()];
+    let mut detector = GestureDetector::new();
+    let t0 = Instant::now();
+    detector.button(0, true, &config, t0);
+    assert!(detector.poll(&configs, t0 + LONG_PRESS - ms(1)).is_empty());
+    assert_eq!(detector.poll(&configs, t0 + LONG_PRESS), vec![(0, Gesture::LongPress)]);
+    assert!(detector.poll(&configs, t0 + LONG_PRESS * 2).is_empty());
+    assert_eq!(detector.button(0, false, &config, t0 + LONG_PRESS * 2), None);
+
+    // Let go before the long press time, it's a press
+    let t1 = t0 + ms(2000);
+    detector.button(0, true, &config, t1);
+    assert_eq!(detector.button(0, false, &config, t1 + LONG_PRESS - ms(1)), Some(Gesture::Press));
+}
+
+#[test]
+fn two_quick_presses_are_a_double_press() {
+    let config = button(&[Gesture::Press, Gesture::DoublePress]);
+    let configs = [config.clone()];
+    let mut detector = GestureDetector::new();
+    let t0 = Instant::now();
+    detector.button(0, true, &config, t0);
+    assert_eq!(detector.button(0, false, &config, t0 + ms(50)), None);
+    assert!(detector.poll(&configs, t0 + ms(50) + DOUBLE_PRESS_GAP).is_empty());
+    detector.button(0, true, &config, t0 + ms(50) + DOUBLE_PRESS_GAP);
+    assert_eq!(detector.button(0, false, &config, t0 + ms(400)), Some(Gesture::DoublePress));
+    assert!(detector.poll(&configs, t0 + ms(2000)).is_empty());
+}
+
+#[test]
+fn a_single_press_waits_out_the_double_press_window() {
+    let config = button(&[Gesture::Press, Gesture::DoublePress]);
+    let configs = [config.clone()];
+    let mut detector = GestureDetector::new();
+    let t0 = Instant::now();
+    detector.button(0, true, &config, t0);
+    assert_eq!(detector.button(0, false, &config, t0 + ms(50)), None);
+    assert!(detector.poll(&configs, t0 + ms(50) + DOUBLE_PRESS_GAP).is_empty());
+    assert_eq!(detector.poll(&configs, t0 + ms(51) + DOUBLE_PRESS_GAP), vec![(0, Gesture::Press)]);
+
+    // Too late for a double press, so the next one starts over
+    let t1 = t0 + ms(1000);
+    detector.button(0, true, &config, t1);
+    detector.button(0, false, &config, t1 + ms(50));
+    // Pressed again before `poll` noticed, the first press still fires, once
+    assert_eq!(detector.button(0, true, &config, t1 + ms(51) + DOUBLE_PRESS_GAP), Some(Gesture::Press));
+    assert!(detector.poll(&configs, t1 + ms(51) + DOUBLE_PRESS_GAP).is_empty());
+    assert_eq!(detector.button(0, false, &config, t1 + ms(500)), None);
+}
+
+#[test]
+fn buttons_are_tracked_separately() {
+    let configs = [button(&[Gesture::Press, Gesture::DoublePress]), button(&[Gesture::Press])];
+    let mut detector = GestureDetector::new();
+    let t0 = Instant::now();
+    detector.button(0, true, &configs[0], t0);
+    detector.button(1, true, &configs[1], t0);
+    assert_eq!(detector.button(1, false, &configs[1], t0 + ms(50)), Some(Gesture::Press));
+    assert_eq!(detector.button(0, false, &configs[0], t0 + ms(50)), None);
+    detector.reset();
+    assert!(detector.poll(&configs, t0 + ms(1000)).is_empty());
+}
@@ -0,0 +1,344 @@
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+
+use config::{backup_path, parse_config, save_config, AppConfig, ConfigError, DialCurve, DialType, SelectorPosition, BACKUP_COUNT, CONFIG_VERSION};
+use std::path::PathBuf;
+
+/// An empty directory of its own for each test, so they can run side by side.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rvci-config-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const V2: &str = r#"{
+    "version": 2,
+    "serial": { "port": "COM3", "baud": 9600, "timeout": 100 },
+    "value_max": 1023,
+    "dials": [
+        { "type": "system", "process_name": null },
+        { "type": "process", "process_name": "spotify.exe" }
+    ],
+    "debug_mode": true
+}"#;
+
+#[test]
+fn v2_serial_and_dials_become_the_first_controller() {
+    let (config, version) = parse_config(V2).unwrap();
+    assert_eq!(version, 2);
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert!(config.debug_mode);
+    assert_eq!(config.controllers.len(), 1);
+
+    let controller = &config.controllers[0];
+    assert_eq!(controller.serial.port, "COM3");
+    assert_eq!(controller.serial.baud, 9600);
+    assert_eq!(controller.serial.timeout, 100);
+    assert_eq!(controller.serial.protocol, "deej");
+    assert!(controller.name.is_empty());
+    assert!(controller.selector.is_empty());
+    let types: Vec<DialType> = controller.dials.iter().map(|d| d.dial_type).collect();
+    assert_eq!(types, vec![DialType::System, DialType::Process]);
+    assert_eq!(controller.dials[1].process_name.as_deref(), Some("spotify.exe"));
+    // `value_max` went along with the controller and became the knobs' range in v5
+    assert!(controller.dials.iter().all(|d| d.raw_min == 0.0 && d.raw_max == 1023.0));
+}
+
+#[test]
+fn v2_without_value_max_keeps_the_old_default_range() {
+    let text = V2.replace(r#""value_max": 1023,"#, "");
+    let (config, _) = parse_config(&text).unwrap();
+    assert!(config.controllers[0].dials.iter().all(|d| d.raw_max == 720.0));
+}
+
+#[test]
+fn migrating_a_v2_file_rewrites_it_and_keeps_the_original() {
+    let dir = temp_dir("v2");
+    let path = dir.join("mapping.json");
+    std::fs::write(&path, V2).unwrap();
+
+    config::migrate_config_file(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("mapping.json.v2")).unwrap(), V2);
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["version"], CONFIG_VERSION);
+    assert_eq!(written["controllers"][0]["serial"]["port"], "COM3");
+    assert_eq!(written["controllers"][0]["dials"].as_array().unwrap().len(), 2);
+    assert!(written.get("serial").is_none() && written.get("dials").is_none() && written.get("value_max").is_none());
+
+    // Already current, so a second run leaves it alone
+    let migrated = std::fs::read_to_string(&path).unwrap();
+    config::migrate_config_file(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), migrated);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A mapping.json from before the schema had a version, with the deej-style type names.
+const V1: &str = r#"{
+  "serial": { "port": "COM11", "baud": 115200, "timeout": 1 },
+  "value_max": 1024.0,
+  "work_device_1": "7- AIR 192 4",
+  "work_device_2": "None",
+  "use_logarithmic_scale": true,
+  "enable_osd": false,
+  "dials": [
+    { "type": "Master", "process_name": null, "inverted": false },
+    { "type": "process", "process_name": "brave.exe", "inverted": true },
+    { "type": "deej.unmapped", "process_name": null },
+    { "type": "MIC", "process_name": "Yeti" },
+    { "type": " others ", "process_name": null }
+  ]
+}"#;
+
+#[test]
+fn v1_config_migrates_to_the_current_schema() {
+    let (config, version) = parse_config(V1).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert!(!config.enable_osd);
+    assert_eq!(config.controllers.len(), 1);
+
+    let controller = &config.controllers[0];
+    assert_eq!(controller.serial.port, "COM11");
+    assert_eq!(controller.serial.baud, 115200);
+    let types: Vec<DialType> = controller.dials.iter().map(|d| d.dial_type).collect();
+    assert_eq!(types, vec![DialType::System, DialType::Process, DialType::AllOthers, DialType::Microphone, DialType::AllOthers]);
+    assert!(controller.dials[1].inverted);
+    assert_eq!(controller.dials[3].process_name.as_deref(), Some("Yeti"));
+    assert!(controller.dials.iter().all(|d| d.raw_max == 1024.0));
+    assert!(controller.dials.iter().all(|d| d.curve == DialCurve::Power { exponent: 3.0 }));
+    // "None" was how v1 said there's no second device
+    assert_eq!(controller.selector, vec![
+        SelectorPosition { outputs: vec!["7- AIR 192 4".to_string()], input: None },
+        SelectorPosition { outputs: vec![], input: None },
+    ]);
+
+    // Saved and read back, it stays the same
+    let written = serde_json::to_string(&config).unwrap();
+    let (reread, version) = parse_config(&written).unwrap();
+    assert_eq!(version, CONFIG_VERSION);
+    assert_eq!(reread.controllers, config.controllers);
+}
+
+#[test]
+fn unknown_dial_types_are_named_in_the_error() {
+    let text = V1.replace(r#""type": "MIC""#, r#""type": "foo""#);
+    let err = parse_config(&text).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Knob 4: unknown type \"foo\" (expected one of: system, process, all_others, microphone, crossfade)"
+    );
+
+    // A current file keeps the position of the mistake
+    let text = r#"{"version": 6, "controllers": [
+        {"serial": {"port": "auto", "baud": 9600, "timeout": 100}, "dials": [{"type": "system"}]},
+        {"serial": {"port": "auto", "baud": 9600, "timeout": 100}, "dials": [{"type": "system"}, {"type": "foo"}]}
+    ]}"#;
+    match parse_config(text).unwrap_err() {
+        ConfigError::Invalid { message, line, .. } => {
+            assert_eq!(message, "Controller 2, knob 2: unknown type \"foo\" (expected one of: system, process, all_others, microphone, crossfade)");
+            assert_eq!(line, Some(3));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    // Profiles and mute buttons pick their targets the same way
+    let text = r#"{"version": 6, "controllers": [{
+        "serial": {"port": "auto", "baud": 9600, "timeout": 100},
+        "dials": [{"type": "system"}],
+        "profiles": [
+            {"name": "Music", "dials": [{"type": "system"}]},
+            {"name": "Games", "dials": [{"type": "system"}, {"type": "proccess", "process_name": "game.exe"}]}
+        ]
+    }]}"#;
+    match parse_config(text).unwrap_err() {
+        ConfigError::Invalid { message, line, .. } => {
+            assert_eq!(message, "Profile 2, knob 2: unknown type \"proccess\" (expected one of: system, process, all_others, microphone, crossfade)");
+            assert_eq!(line, Some(6));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+    let text = r#"{"version": 6, "controllers": [{
+        "serial": {"port": "auto", "baud": 9600, "timeout": 100},
+        "dials": [{"type": "system"}],
+        "buttons": [{"press": [{"action": "cycle_profile"}], "long_press": [{"action": "toggle_mute", "type": "mic"}]}]
+    }]}"#;
+    let err = parse_config(text).unwrap_err().to_string();
+    assert!(err.ends_with("Button 1, long press action 1: unknown type \"mic\" (expected one of: system, process, all_others, microphone, crossfade)"), "{}", err);
+}
+
+const BROKEN: &str = "{\n  \"version\": 6,\n  \"controllers\": []\n  \"debug_mode\": true\n}\n";
+
+#[test]
+fn syntax_errors_report_line_and_column() {
+    let err = parse_config(BROKEN).unwrap_err();
+    assert_eq!(err, ConfigError::Invalid { message: "expected `,` or `}`".to_string(), line: Some(4), column: Some(3) });
+    assert_eq!(err.to_string(), "line 4, column 3: expected `,` or `}`");
+}
+
+#[test]
+fn a_broken_file_is_never_overwritten() {
+    let dir = temp_dir("broken");
+    let path = dir.join("mapping.json");
+    std::fs::write(&path, BROKEN).unwrap();
+
+    assert!(matches!(config::load_config(&path), Err(ConfigError::Invalid { .. })));
+    assert!(config::migrate_config_file(&path).is_err());
+    let err = save_config(&path, &AppConfig::default()).unwrap_err();
+    assert!(err.to_string().contains("was left as it is"), "{}", err);
+    assert_eq!(std::fs::read(&path).unwrap(), BROKEN.as_bytes());
+    assert!(!backup_path(&path, 1).exists());
+
+    // Once it's set aside, saving starts a fresh file and the broken one is kept as it was
+    let broken = config::set_aside_broken_config(&path).unwrap();
+    assert_eq!(broken, dir.join("mapping.json.broken"));
+    assert!(!path.exists());
+    assert_eq!(std::fs::read(&broken).unwrap(), BROKEN.as_bytes());
+    save_config(&path, &AppConfig::default()).unwrap();
+    assert!(config::load_config(&path).is_ok());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_missing_file_is_not_an_error_to_save_over() {
+    let dir = temp_dir("missing");
+    let path = dir.join("mapping.json");
+    assert!(matches!(config::load_config(&path), Err(ConfigError::Missing)));
+    save_config(&path, &AppConfig::default()).unwrap();
+    assert!(config::load_config(&path).is_ok());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// The v2 example with its controller named `name`, so each save can be told apart.
+fn named(name: &str) -> AppConfig {
+    let mut config = parse_config(V2).unwrap().0;
+    config.controllers[0].name = name.to_string();
+    config
+}
+
+fn saved_name(path: &std::path::Path) -> String {
+    config::load_config(path).unwrap().controllers[0].name.clone()
+}
+
+#[test]
+fn saving_keeps_the_newest_backups_in_order() {
+    let dir = temp_dir("backups");
+    let path = dir.join("mapping.json");
+    // The first save has nothing to back up, the last one pushes the oldest backup out
+    for i in 0..BACKUP_COUNT + 2 {
+        save_config(&path, &named(&format!("save {}", i))).unwrap();
+    }
+    assert_eq!(saved_name(&path), format!("save {}", BACKUP_COUNT + 1));
+    for n in 1..=BACKUP_COUNT {
+        assert_eq!(saved_name(&backup_path(&path, n)), format!("save {}", BACKUP_COUNT + 1 - n));
+    }
+    assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+    assert!(!dir.join("mapping.json.tmp").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn restoring_swaps_the_file_and_the_newest_backup() {
+    let dir = temp_dir("restore");
+    let path = dir.join("mapping.json");
+    assert!(config::restore_previous_config(&path).is_err());
+    save_config(&path, &named("old")).unwrap();
+    save_config(&path, &named("new")).unwrap();
+
+    config::restore_previous_config(&path).unwrap();
+    assert_eq!(saved_name(&path), "old");
+    assert_eq!(saved_name(&backup_path(&path, 1)), "new");
+    // And a second restore undoes the first
+    config::restore_previous_config(&path).unwrap();
+    assert_eq!(saved_name(&path), "new");
+    assert_eq!(saved_name(&backup_path(&path, 1)), "old");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_failed_save_leaves_the_file_and_backups_alone() {
+    let dir = temp_dir("failed-save");
+    let path = dir.join("mapping.json");
+    save_config(&path, &named("old")).unwrap();
+    save_config(&path, &named("current")).unwrap();
+    let before = std::fs::read(&path).unwrap();
+
+    // A directory where the temp file should go makes the write fail before anything is replaced
+    std::fs::create_dir(dir.join("mapping.json.tmp")).unwrap();
+    assert!(save_config(&path, &named("lost")).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+    assert_eq!(saved_name(&backup_path(&path, 1)), "old");
+    assert!(!backup_path(&path, 2).exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn only_connection_settings_need_a_reconnect() {
+    let old = parse_config(V2).unwrap().0.controllers.remove(0);
+    let changed = |change: &dyn Fn(&mut config::ControllerConfig)| {
+        let mut new = old.clone();
+        change(&mut new);
+        old.needs_reconnect(&new)
+    };
+    assert!(!changed(&|_| {}));
+    assert!(changed(&|c| c.serial.port = "COM4".to_string()));
+    assert!(changed(&|c| c.serial.baud = 115200));
+    assert!(changed(&|c| c.serial.usb = Some(config::UsbId { vid: 0x2341, pid: 0x0043, serial_number: None })));
+    assert!(changed(&|c| c.transport = config::TransportConfig::Tcp { listen: "0.0.0.0:5000".to_string(), token: None }));
+    // Applied to the open connection
+    assert!(!changed(&|c| c.serial.timeout = 500));
+    assert!(!changed(&|c| c.name = "Desk".to_string()));
+    assert!(!changed(&|c| c.dials[0].inverted = true));
+    assert!(!changed(&|c| c.selector.push(SelectorPosition { outputs: vec!["Speakers".to_string()], input: None })));
+}
+
+/// A v4 file: controllers already, but one `value_max` per controller instead of a range per knob.
+const V4: &str = r#"{
+    "version": 4,
+    "controllers": [
+        {
+            "serial": { "port": "COM3", "baud": 9600, "timeout": 100 },
+            "value_max": 4095,
+            "dials": [{ "type": "system" }, { "type": "process", "process_name": "spotify.exe" }],
+            "profiles": [{ "name": "Games", "dials": [{ "type": "process", "process_name": "game.exe" }] }]
+        },
+        {
+            "serial": { "port": "COM4", "baud": 9600, "timeout": 100 },
+            "dials": [{ "type": "system" }]
+        }
+    ]
+}"#;
+
+#[test]
+fn v4_value_max_becomes_the_range_of_every_knob() {
+    let (config, version) = parse_config(V4).unwrap();
+    assert_eq!(version, 4);
+    let ranges = |dials: &[config::DialConfig]| dials.iter().map(|d| (d.raw_min, d.raw_max)).collect::<Vec<_>>();
+    assert_eq!(ranges(&config.controllers[0].dials), vec![(0.0, 4095.0), (0.0, 4095.0)]);
+    assert_eq!(ranges(&config.controllers[0].profiles[0].dials), vec![(0.0, 4095.0)]);
+    // Without `value_max` a controller had the old default
+    assert_eq!(ranges(&config.controllers[1].dials), vec![(0.0, 720.0)]);
+    let written = serde_json::to_value(&config).unwrap();
+    assert!(written["controllers"][0].get("value_max").is_none());
+}
+
+#[test]
+fn calibration_skips_knobs_that_were_not_turned() {
+    let mut controller = parse_config(V4).unwrap().0.controllers.remove(0);
+    let span = config::MIN_CALIBRATED_SPAN;
+    let changed = controller.apply_calibration(&[Some((12.0, 1010.0)), Some((500.0, 500.0 + span - 1.0)), Some((0.0, 800.0))]);
+    assert_eq!(changed, 2);
+    assert_eq!((controller.dials[0].raw_min, controller.dials[0].raw_max), (12.0, 1010.0));
+    assert_eq!((controller.dials[1].raw_min, controller.dials[1].raw_max), (0.0, 4095.0));
+    // Profiles get the range of the knob in the same place
+    assert_eq!((controller.profiles[0].dials[0].raw_min, controller.profiles[0].dials[0].raw_max), (12.0, 1010.0));
+
+    // Just wide enough counts, knobs without readings are left alone
+    assert_eq!(controller.apply_calibration(&[None, Some((100.0, 100.0 + span))]), 1);
+    assert_eq!((controller.dials[0].raw_min, controller.dials[0].raw_max), (12.0, 1010.0));
+    assert_eq!((controller.dials[1].raw_min, controller.dials[1].raw_max), (100.0, 100.0 + span));
+}
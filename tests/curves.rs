@@ -0,0 +1,72 @@
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+
+use config::DialCurve;
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn curves_map_positions_to_volumes() {
+    assert_eq!(DialCurve::Linear.apply(0.3), 0.3);
+    assert!(close(DialCurve::Power { exponent: 3.0 }.apply(0.5), 0.125));
+
+    let db = DialCurve::Db { min_db: -60.0 };
+    assert_eq!(db.apply(0.0), 0.0);
+    assert_eq!(db.apply(1.0), 1.0);
+    // Halfway is 30 dB down
+    assert!(close(db.apply(0.5), 0.0316));
+
+    let custom = DialCurve::Points { points: vec![[0.0, 0.1], [0.5, 0.2], [1.0, 1.0]] };
+    assert!(close(custom.apply(0.25), 0.15));
+    assert!(close(custom.apply(0.75), 0.6));
+    assert_eq!(custom.apply(-1.0), 0.1);
+}
+
+#[test]
+fn curves_find_the_position_of_a_volume() {
+    for curve in [DialCurve::Linear, DialCurve::Power { exponent: 3.0 }, DialCurve::Db { min_db: -40.0 }] {
+        for volume in [0.0, 0.05, 0.5, 1.0] {
+            assert!(close(curve.apply(curve.position_of(volume)), volume), "{:?} at {}", curve, volume);
+        }
+    }
+}
+
+#[test]
+fn custom_curves_start_from_the_current_one() {
+    let custom = DialCurve::Power { exponent: 2.0 }.with_name("Custom");
+    assert_eq!(custom, DialCurve::Points { points: vec![[0.0, 0.0], [0.25, 0.063], [0.5, 0.25], [0.75, 0.563], [1.0, 1.0]] });
+    assert_eq!(custom.with_name("Custom"), custom);
+}
+
+#[test]
+fn logarithmic_scale_becomes_a_power_curve() {
+    let v5 = r#"{"version": 5, "use_logarithmic_scale": true, "controllers": [{"serial": {"port": "auto", "baud": 115200, "timeout": 50},
+        "dials": [{"type": "system", "process_name": null}],
+        "profiles": [{"name": "Games", "dials": [{"type": "all_others", "process_name": null}]}]}]}"#;
+    let (config, version) = config::parse_config(v5).unwrap();
+    assert_eq!(version, 5);
+    let controller = &config.controllers[0];
+    assert_eq!(controller.dials[0].curve, DialCurve::Power { exponent: 3.0 });
+    assert_eq!(controller.profiles[0].dials[0].curve, DialCurve::Power { exponent: 3.0 });
+
+    let linear = v5.replace("\"use_logarithmic_scale\": true", "\"use_logarithmic_scale\": false");
+    assert_eq!(config::parse_config(&linear).unwrap().0.controllers[0].dials[0].curve, DialCurve::Linear);
+}
+
+#[test]
+fn output_range_and_snapping() {
+    let dial = config::DialConfig { min_output: 0.05, max_output: 0.6, snap_to: vec![0.0, 0.5, 1.0], ..Default::default() };
+    assert!(close(dial.to_output(0.0), 0.05));
+    assert!(close(dial.to_output(1.0), 0.6));
+    assert!(close(dial.output_position(dial.to_output(0.3)), 0.3));
+    // Only 0.5 is inside the range, and only close by
+    assert_eq!(dial.snap(0.51), 0.5);
+    assert_eq!(dial.snap(0.53), 0.53);
+    assert_eq!(dial.snap(0.06), 0.06);
+}
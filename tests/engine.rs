@@ -0,0 +1,380 @@
+#[allow(dead_code)]
+#[path = "../src/audio/mod.rs"]
+mod audio;
+#[allow(dead_code)]
+#[path = "../src/buttons.rs"]
+mod buttons;
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/engine.rs"]
+mod engine;
+#[allow(dead_code)]
+#[path = "../src/matching.rs"]
+mod matching;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+#[allow(dead_code)]
+#[path = "../src/smoothing.rs"]
+mod smoothing;
+
+use audio::{DataFlow, Endpoint, MockBackend};
+use config::{parse_config, AppConfig};
+use engine::{DialPipeline, EngineEvent};
+use std::time::{Duration, Instant};
+
+/// A single controller with `dials` (JSON objects) and `extra` fields such as `"selector": [...]`.
+/// Knobs read 0-100 and aren't smoothed, so the volumes come out exactly.
+fn controller(dials: &[&str], extra: &str) -> AppConfig {
+    let dials: Vec<String> = dials.iter()
+        .map(|d| format!(r#"{{"raw_max": 100, "filter": {{"kind": "none"}}, {}}}"#, d))
+        .collect();
+    let extra = if extra.is_empty() { String::new() } else { format!(", {}", extra) };
+    let text = format!(
+        r#"{{"version": 6, "controllers": [{{"serial": {{"port": "auto", "baud": 9600, "timeout": 100}}, "dials": [{}]{}}}]}}"#,
+        dials.join(", "), extra
+    );
+    parse_config(&text).unwrap().0
+}
+
+/// Feeds `lines` 50 ms apart, so none of them is dropped as too soon after the last frame, and
+/// returns the trace of backend calls and the events sent to the GUI.
+fn run(config: AppConfig, backend: &mut MockBackend, lines: &[&str]) -> (Vec<String>, Vec<EngineEvent>) {
+    let timed: Vec<(u64, &str)> = lines.iter().enumerate().map(|(i, line)| (50 * i as u64, *line)).collect();
+    run_timed(config, backend, &timed)
+}
+
+/// Like `run`, with each line's time in milliseconds.
+fn run_timed(config: AppConfig, backend: &mut MockBackend, lines: &[(u64, &str)]) -> (Vec<String>, Vec<EngineEvent>) {
+    let start = backend.calls.len();
+    let mut events = Vec::new();
+    {
+        let mut send = |event| events.push(event);
+        let mut pipeline = DialPipeline::new(config, 0, backend, &mut send);
+        let t0 = Instant::now();
+        for (ms, line) in lines {
+            pipeline.set_clock(t0 + Duration::from_millis(*ms));
+            pipeline.handle_line(line);
+        }
+    }
+    (backend.calls[start..].iter().map(|c| c.to_string()).collect(), events)
+}
+
+fn desktop() -> MockBackend {
+    MockBackend::new()
+        .with_session(100, "spotify")
+        .with_session(200, "discord")
+        .with_session(300, "game")
+        .with_device(DataFlow::Render, "Speakers (Realtek Audio)")
+        .with_device(DataFlow::Render, "Headset (USB Audio)")
+        .with_device(DataFlow::Capture, "Desk Mic")
+}
+
+#[test]
+fn every_knob_type_sets_its_target() {
+    let config = controller(&[
+        r#""type": "system""#,
+        r#""type": "process", "process_name": "spotify.exe""#,
+        r#""type": "all_others""#,
+        r#""type": "microphone", "process_name": "Desk Mic""#,
+    ], "");
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["100|50|25|80"]);
+    assert_eq!(calls, vec![
+        "volume output 1.000",
+        "volume spotify 0.500",
+        "volume discord 0.250",
+        "volume game 0.250",
+        "volume capture Desk Mic 0.800",
+    ]);
+}
+
+#[test]
+fn only_knobs_that_moved_are_applied_again() {
+    let config = controller(&[r#""type": "system""#, r#""type": "process", "process_name": "spotify.exe""#], "");
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["100|50", "100|50", "100|20"]);
+    assert_eq!(calls, vec!["volume output 1.000", "volume spotify 0.500", "volume spotify 0.200"]);
+}
+
+#[test]
+fn all_others_skips_apps_mapped_on_other_knobs() {
+    let config = controller(&[r#""type": "process", "process_name": "Discord.exe""#, r#""type": "all_others""#], "");
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["0|60"]);
+    assert_eq!(calls, vec!["volume discord 0.000", "volume spotify 0.600", "volume game 0.600"]);
+}
+
+#[test]
+fn missing_targets_are_skipped() {
+    let config = controller(&[r#""type": "process", "process_name": "closed.exe""#, r#""type": "microphone", "process_name": "Unplugged Mic""#], "");
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["50|50"]);
+    assert!(calls.is_empty(), "{:?}", calls);
+}
+
+#[test]
+fn switch_positions_change_the_default_devices() {
+    let selector = r#""selector": [
+        {"outputs": ["speakers"]},
+        {"outputs": ["Bluetooth", "headset"], "input": "desk mic"}
+    ]"#;
+    let config = controller(&[r#""type": "system""#], selector);
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["WORKS 2", "WORKS 1", "WORKS 3"]);
+    assert_eq!(calls, vec![
+        "default Render Headset (USB Audio)",
+        "default Capture Desk Mic",
+        "default Render Speakers (Realtek Audio)",
+    ]);
+}
+
+#[test]
+fn mute_buttons_and_all_others_follow_the_match_rule() {
+    let buttons = r#""buttons": [{"press": [{"action": "toggle_mute", "type": "process", "process_name": "discord*", "match": "glob"}]}]"#;
+    let config = controller(&[r#""type": "process", "process_name": "DISCORD*", "match": "glob""#, r#""type": "all_others""#], buttons);
+    let mut backend = desktop().with_session(400, "discordptb");
+    let (calls, _) = run(config, &mut backend, &["20|60", "BUTTON 1 DOWN", "BUTTON 1 UP"]);
+    assert_eq!(calls, vec![
+        "volume discord 0.200",
+        "volume discordptb 0.200",
+        "volume spotify 0.600",
+        "volume game 0.600",
+        "mute discord true",
+        "mute discordptb true",
+    ]);
+}
+
+#[test]
+fn v3_work_devices_become_switch_positions() {
+    let text = r#"{
+        "version": 3,
+        "work_device_1": "realtek",
+        "work_device_2": "USB Audio",
+        "controllers": [{"serial": {"port": "auto", "baud": 9600, "timeout": 100}, "dials": [{"type": "system"}]}]
+    }"#;
+    let config = parse_config(text).unwrap().0;
+    let mut backend = desktop();
+    let (calls, _) = run(config, &mut backend, &["WORKS 2", "WORKS 1"]);
+    assert_eq!(calls, vec!["default Render Headset (USB Audio)", "default Render Speakers (Realtek Audio)"]);
+
+    // "None" left the position without a device
+    let config = parse_config(&text.replace("USB Audio", "None")).unwrap().0;
+    let (calls, _) = run(config, &mut backend, &["WORKS 2", "WORKS 1"]);
+    assert_eq!(calls, vec!["default Render Speakers (Realtek Audio)"]);
+}
+
+#[test]
+fn switch_positions_fall_back_in_order() {
+    let selector = r#""selector": [
+        {"outputs": ["Bluetooth", "HEADSET", "speakers"], "input": "Webcam Mic"},
+        {"outputs": ["", "None", "realtek"], "input": "desk"},
+        {"outputs": ["Bluetooth"]}
+    ]"#;
+    let config = controller(&[r#""type": "system""#], selector);
+    let mut backend = desktop();
+    // The first connected device wins, names match part of the device name ignoring case, and a
+    // missing input doesn't keep the output from switching
+    let (calls, _) = run(config, &mut backend, &["WORKS 1", "WORKS 2", "WORKS 3", "WORKS 4"]);
+    assert_eq!(calls, vec![
+        "default Render Headset (USB Audio)",
+        "default Render Speakers (Realtek Audio)",
+        "default Capture Desk Mic",
+    ]);
+}
+
+#[test]
+fn the_switch_is_applied_again_after_reconnecting() {
+    let selector = r#""selector": [{"outputs": ["speakers"]}, {"outputs": ["headset"]}]"#;
+    let config = controller(&[r#""type": "system""#], selector);
+    let mut backend = desktop();
+    let mut events = |_| {};
+    let mut pipeline = DialPipeline::new(config.clone(), 0, &mut backend, &mut events);
+    pipeline.handle_line("WORKS 2");
+    pipeline.reset_connection();
+    // While disconnected the old position is stale, so changing its devices switches nothing
+    let mut changed = config.clone();
+    changed.controllers[0].selector[1].outputs = vec!["realtek".to_string()];
+    pipeline.update_config(changed);
+    // The firmware reports the switch again once it's connected
+    pipeline.handle_line("WORKS 2");
+    drop(pipeline);
+    let calls: Vec<String> = backend.calls.iter().map(|c| c.to_string()).collect();
+    assert_eq!(calls, vec!["default Render Headset (USB Audio)", "default Render Speakers (Realtek Audio)"]);
+}
+
+/// The system volume at `volume`, with a pickup knob on it.
+fn pickup(volume: f32) -> (AppConfig, MockBackend) {
+    let mut backend = desktop();
+    backend.endpoint_volumes.push((Endpoint::DefaultOutput, volume));
+    (controller(&[r#""type": "system", "pickup": true"#], ""), backend)
+}
+
+#[test]
+fn pickup_knobs_wait_until_they_cross_the_volume() {
+    let (config, mut backend) = pickup(0.5);
+    let (calls, _) = run(config.clone(), &mut backend, &["10", "30", "60", "70"]);
+    assert_eq!(calls, vec!["volume output 0.600", "volume output 0.700"]);
+
+    // Coming down from above works the same
+    let (_, mut backend) = pickup(0.5);
+    let (calls, _) = run(config, &mut backend, &["90", "80", "40"]);
+    assert_eq!(calls, vec!["volume output 0.400"]);
+}
+
+#[test]
+fn pickup_knobs_close_enough_take_over_right_away() {
+    let (config, mut backend) = pickup(0.5);
+    let (calls, _) = run(config.clone(), &mut backend, &["52", "60"]);
+    assert_eq!(calls, vec!["volume output 0.520", "volume output 0.600"]);
+
+    let (_, mut backend) = pickup(0.5);
+    let (calls, _) = run(config, &mut backend, &["47", "45"]);
+    assert!(calls.is_empty(), "{:?}", calls);
+}
+
+#[test]
+fn pickup_knobs_let_go_after_something_else_changes_the_volume() {
+    // The second knob stands in for another app turning the volume
+    let config = controller(&[r#""type": "system", "pickup": true"#, r#""type": "system""#], "");
+    let (_, mut backend) = pickup(0.5);
+    let (calls, _) = run_timed(config, &mut backend, &[
+        (0, "50|50"),
+        (50, "70|50"),
+        (300, "70|30"),
+        // Still going by what it set itself, until it reads the volume again
+        (350, "72|30"),
+        (600, "72|20"),
+        (650, "75|20"),
+        (700, "40|20"),
+        (750, "15|20"),
+        (800, "30|20"),
+    ]);
+    assert_eq!(calls, vec![
+        "volume output 0.500",
+        "volume output 0.500",
+        "volume output 0.700",
+        "volume output 0.300",
+        "volume output 0.720",
+        "volume output 0.200",
+        "volume output 0.150",
+        "volume output 0.300",
+    ]);
+}
+
+const ENCODER: &str = r#""input": {"kind": "encoder", "step": 0.02, "acceleration": 2.0}"#;
+
+/// The mock starting with spotify (session 0) at `volume`.
+fn spotify_at(volume: f32) -> MockBackend {
+    let mut backend = desktop();
+    backend.session_volumes.push((0, volume));
+    backend
+}
+
+#[test]
+fn encoders_step_from_the_current_volume() {
+    let encoder = format!(r#""type": "process", "process_name": "spotify.exe", {}"#, ENCODER);
+    let config = controller(&[r#""type": "process", "process_name": "spotify.exe""#, &encoder], "");
+    let mut backend = spotify_at(0.5);
+    // The pot moves it first, the encoder carries on from there
+    let (calls, _) = run_timed(config, &mut backend, &[
+        (0, "ENCODER 1 +1"),
+        (1000, "30"),
+        (2000, "ENCODER 1 +1"),
+        (3000, "ENCODER 1 -2"),
+        (4000, "ENCODER 2 +1"),
+    ]);
+    assert_eq!(calls, vec!["volume spotify 0.520", "volume spotify 0.300", "volume spotify 0.320", "volume spotify 0.280"]);
+
+    let inverted = format!(r#""type": "process", "process_name": "spotify.exe", "inverted": true, {}"#, ENCODER);
+    let (calls, _) = run(controller(&[&inverted], ""), &mut spotify_at(0.5), &["ENCODER 1 +1"]);
+    assert_eq!(calls, vec!["volume spotify 0.480"]);
+}
+
+#[test]
+fn encoders_take_bigger_steps_when_turned_fast() {
+    let encoder = format!(r#""type": "process", "process_name": "spotify.exe", {}"#, ENCODER);
+    let config = controller(&[&encoder], "");
+    let (calls, _) = run_timed(config, &mut spotify_at(0.2), &[
+        (0, "ENCODER 1 +1"),
+        // 1 step a second is below ENCODER_SLOW
+        (1000, "ENCODER 1 +1"),
+        // 40 a second is ENCODER_FAST, three times the step
+        (1050, "ENCODER 1 +2"),
+        // 22.5 a second is halfway, twice the step
+        (1450, "ENCODER 1 +9"),
+        // Faster than that still triples, and the volume stops at 0
+        (1500, "ENCODER 1 -10"),
+        (1550, "ENCODER 1 -5"),
+    ]);
+    assert_eq!(calls, vec![
+        "volume spotify 0.220",
+        "volume spotify 0.240",
+        "volume spotify 0.360",
+        "volume spotify 0.720",
+        "volume spotify 0.120",
+        "volume spotify 0.000",
+    ]);
+
+    let steady = r#""type": "process", "process_name": "spotify.exe", "input": {"kind": "encoder", "step": 0.05, "acceleration": 0.0}"#;
+    let (calls, _) = run_timed(controller(&[steady], ""), &mut spotify_at(0.2), &[(0, "ENCODER 1 +1"), (50, "ENCODER 1 +2")]);
+    assert_eq!(calls, vec!["volume spotify 0.250", "volume spotify 0.350"]);
+}
+
+/// Five knobs, one per target of `desktop()`.
+fn five_knobs() -> AppConfig {
+    controller(&[
+        r#""type": "system""#,
+        r#""type": "process", "process_name": "spotify.exe""#,
+        r#""type": "process", "process_name": "discord.exe""#,
+        r#""type": "process", "process_name": "game.exe""#,
+        r#""type": "microphone", "process_name": "Desk Mic""#,
+    ], "")
+}
+
+fn mismatches(events: &[EngineEvent]) -> Vec<(String, usize, usize)> {
+    events.iter().filter_map(|e| match e {
+        EngineEvent::DialCountMismatch { controller_index: 0, controller, sliders, dials } => Some((controller.clone(), *sliders, *dials)),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn a_handshake_with_more_sliders_than_knobs_is_reported_once() {
+    let mut backend = desktop();
+    let (calls, events) = run(five_knobs(), &mut backend, &[
+        "HELLO version=1 sliders=6 buttons=0 name=Desk Deck",
+        // The hardware said six, so five values are a cut-off line
+        "10|20|30|40|50",
+        "10|20|30|40|50|60",
+        "HELLO version=1 sliders=6 buttons=0 name=Desk Deck",
+    ]);
+    assert_eq!(mismatches(&events), vec![("Desk Deck".to_string(), 6, 5)]);
+    // The sixth slider has no knob and is left out
+    assert_eq!(calls, vec![
+        "volume output 0.100",
+        "volume spotify 0.200",
+        "volume discord 0.300",
+        "volume game 0.400",
+        "volume capture Desk Mic 0.500",
+    ]);
+}
+
+#[test]
+fn plain_deej_frames_work_without_a_handshake() {
+    let mut backend = desktop();
+    let (calls, events) = run(five_knobs(), &mut backend, &["10|20|30|40|50", "10|20|30|40|60"]);
+    assert!(mismatches(&events).is_empty());
+    assert_eq!(calls.len(), 6, "{:?}", calls);
+    assert_eq!(calls[5], "volume capture Desk Mic 0.600");
+
+    // Without a handshake, a different length has to keep coming before it's believed
+    let mut lines = vec!["10|20|30|40|50|60"; 9];
+    let (calls, events) = run(five_knobs(), &mut backend, &lines);
+    assert!(calls.is_empty() && mismatches(&events).is_empty());
+    lines.push("10|20|30|40|50|60");
+    let (_, events) = run(five_knobs(), &mut backend, &lines);
+    assert_eq!(mismatches(&events), vec![("Controller 1".to_string(), 6, 5)]);
+}
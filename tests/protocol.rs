@@ -0,0 +1,92 @@
+#[path = "../src/protocol.rs"]
+mod protocol;
+
+use protocol::{create_protocol, ControllerEvent, ControllerInfo, DeejProtocol, LineProtocol, ParseError, HANDSHAKE_VERSION, PROTOCOL_NAMES};
+
+fn parse(line: &str) -> Result<Option<ControllerEvent>, ParseError> {
+    DeejProtocol.parse_line(line)
+}
+
+#[test]
+fn parses_dial_values() {
+    assert_eq!(parse("1023|512|0|300|100\r\n"), Ok(Some(ControllerEvent::Dials(vec![1023.0, 512.0, 0.0, 300.0, 100.0]))));
+    assert_eq!(parse("42"), Ok(Some(ControllerEvent::Dials(vec![42.0]))));
+    assert_eq!(parse(" 1.5 | 2 "), Ok(Some(ControllerEvent::Dials(vec![1.5, 2.0]))));
+}
+
+#[test]
+fn blank_lines_are_not_events() {
+    assert_eq!(parse(""), Ok(None));
+    assert_eq!(parse("  \r\n"), Ok(None));
+}
+
+#[test]
+fn parses_switch_positions() {
+    assert_eq!(parse("WORKS 1\r\n"), Ok(Some(ControllerEvent::Switch(1))));
+    assert_eq!(parse("WORKS 2"), Ok(Some(ControllerEvent::Switch(2))));
+    assert_eq!(parse("WORKS 7"), Ok(Some(ControllerEvent::Switch(7))));
+}
+
+#[test]
+fn parses_buttons() {
+    assert_eq!(parse("BUTTON 3 DOWN"), Ok(Some(ControllerEvent::Button { index: 3, pressed: true })));
+    assert_eq!(parse("BUTTON 3 UP\r\n"), Ok(Some(ControllerEvent::Button { index: 3, pressed: false })));
+    assert_eq!(parse("BUTTON 3 SIDEWAYS"), Err(ParseError::Unrecognized("BUTTON 3 SIDEWAYS".to_string())));
+    assert_eq!(parse("BUTTON 3"), Err(ParseError::Unrecognized("BUTTON 3".to_string())));
+}
+
+#[test]
+fn parses_encoder_steps() {
+    assert_eq!(parse("ENCODER 1 +2"), Ok(Some(ControllerEvent::Encoder { index: 1, delta: 2 })));
+    assert_eq!(parse("ENCODER 2 -1\r\n"), Ok(Some(ControllerEvent::Encoder { index: 2, delta: -1 })));
+    assert_eq!(parse("ENCODER 2 3"), Ok(Some(ControllerEvent::Encoder { index: 2, delta: 3 })));
+    assert_eq!(parse("ENCODER 2 fast"), Err(ParseError::InvalidValue { field: 3, text: "fast".to_string() }));
+    assert_eq!(parse("ENCODER 2 +1 +1"), Err(ParseError::Unrecognized("ENCODER 2 +1 +1".to_string())));
+}
+
+#[test]
+fn parses_handshake() {
+    let expected = ControllerInfo { version: 1, sliders: 6, switches: 1, buttons: 2, encoders: 1, name: "RVCI Nano v2".to_string() };
+    assert_eq!(
+        parse("HELLO version=1 sliders=6 switches=1 buttons=2 encoders=1 name=RVCI Nano v2\r\n"),
+        Ok(Some(ControllerEvent::Hello(expected)))
+    );
+}
+
+#[test]
+fn handshake_fills_in_defaults_and_skips_unknown_keys() {
+    let expected = ControllerInfo { version: HANDSHAKE_VERSION + 1, sliders: 3, switches: 0, buttons: 0, encoders: 0, name: "Controller".to_string() };
+    assert_eq!(parse("HELLO sliders=3 version=2 leds=8"), Ok(Some(ControllerEvent::Hello(expected))));
+}
+
+#[test]
+fn rejects_incomplete_handshakes() {
+    assert_eq!(parse("HELLO version=1 name=Nano"), Err(ParseError::Unrecognized("HELLO version=1 name=Nano".to_string())));
+    assert_eq!(parse("HELLO sliders"), Err(ParseError::Unrecognized("HELLO sliders".to_string())));
+    assert_eq!(parse("HELLO sliders=five"), Err(ParseError::InvalidValue { field: 2, text: "five".to_string() }));
+}
+
+#[test]
+fn reports_malformed_dial_values() {
+    assert_eq!(parse("1023|abc|0"), Err(ParseError::InvalidValue { field: 2, text: "abc".to_string() }));
+    assert_eq!(parse("1023||0"), Err(ParseError::InvalidValue { field: 2, text: String::new() }));
+    assert_eq!(parse("1023|NaN"), Err(ParseError::InvalidValue { field: 2, text: "NaN".to_string() }));
+    assert_eq!(parse("WORKS x"), Err(ParseError::InvalidValue { field: 2, text: "x".to_string() }));
+}
+
+#[test]
+fn reports_unknown_lines() {
+    assert_eq!(parse("hello"), Err(ParseError::Unrecognized("hello".to_string())));
+    assert_eq!(parse("|1|2"), Err(ParseError::Unrecognized("|1|2".to_string())));
+    assert_eq!(parse("hello").unwrap_err().to_string(), "unrecognized line \"hello\"");
+}
+
+#[test]
+fn every_listed_protocol_can_be_created() {
+    for name in PROTOCOL_NAMES {
+        let parser = create_protocol(name);
+        assert!(parser.is_some(), "{}", name);
+        assert!(parser.unwrap().handshake_request().is_some());
+    }
+    assert!(create_protocol("nope").is_none());
+}
@@ -0,0 +1,113 @@
+//! Runs the PulseAudio backend against the sound server on this machine, with a null sink, a
+//! null source and a `pacat` stream of its own so nothing audible is touched. Skipped where
+//! there is no server, like most CI machines.
+
+#[allow(dead_code)]
+#[path = "../src/audio/mod.rs"]
+mod audio;
+
+use audio::{AudioBackend, DataFlow, Endpoint, PulseBackend};
+use std::fs::File;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn pactl(args: &[&str]) -> Option<String> {
+    let output = Command::new("pactl").args(args).output().ok()?;
+    if !output.status.success() { return None; }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The test's sink, source and stream, removed again however the test ends.
+struct Fixture {
+    modules: Vec<String>,
+    stream: Option<Child>,
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            let _ = stream.kill();
+            let _ = stream.wait();
+        }
+        for module in &self.modules {
+            let _ = pactl(&["unload-module", module]);
+        }
+    }
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.01
+}
+
+#[test]
+fn controls_a_null_sink_stream_and_source() {
+    if pactl(&["info"]).is_none() {
+        println!("No PulseAudio or PipeWire server to test against, skipping.");
+        return;
+    }
+    let mut fixture = Fixture { modules: Vec::new(), stream: None };
+    fixture.modules.push(pactl(&["load-module", "module-null-sink", "sink_name=rvci_test",
+        "sink_properties=device.description=RVCI_Test"]).expect("null sink"));
+    fixture.modules.push(pactl(&["load-module", "module-null-source", "source_name=rvci_test_mic",
+        "source_properties=device.description=RVCI_Test_Mic"]).expect("null source"));
+    fixture.stream = Some(Command::new("pacat")
+        .args(["--playback", "--device=rvci_test"])
+        .stdin(File::open("/dev/zero").unwrap())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("pacat"));
+
+    let mut backend = PulseBackend::new();
+    let deadline = Instant::now() + Duration::from_secs(3);
+    let stream = loop {
+        backend.invalidate_caches();
+        let sessions = backend.list_sessions().unwrap();
+        if let Some(sess) = sessions.into_iter().find(|s| s.process_name == "pacat") { break sess; }
+        assert!(Instant::now() < deadline, "pacat's stream never showed up");
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(backend.list_devices(DataFlow::Render).unwrap().iter().any(|d| d.name == "RVCI_Test"));
+    let mics = backend.list_devices(DataFlow::Capture).unwrap();
+    assert!(mics.iter().any(|d| d.name == "RVCI_Test_Mic"));
+    assert!(!mics.iter().any(|d| d.id.ends_with(".monitor")), "{:?}", mics.iter().map(|d| &d.id).collect::<Vec<_>>());
+
+    // What we set is read back from the cache, and from the server once the cache is dropped
+    backend.set_session_volume(&stream, 0.5).unwrap();
+    assert!(close(backend.session_volume(&stream).unwrap(), 0.5));
+    backend.invalidate_caches();
+    assert!(close(backend.session_volume(&stream).unwrap(), 0.5));
+
+    backend.set_session_mute(&stream, true).unwrap();
+    backend.invalidate_caches();
+    assert!(backend.session_muted(&stream).unwrap());
+    backend.set_session_mute(&stream, false).unwrap();
+
+    let mic = Endpoint::Capture("RVCI_Test_Mic".to_string());
+    backend.set_endpoint_volume(&mic, 0.25).unwrap();
+    backend.invalidate_caches();
+    assert!(close(backend.endpoint_volume(&mic).unwrap(), 0.25));
+    backend.set_endpoint_mute(&mic, true).unwrap();
+    assert!(backend.endpoint_muted(&mic).unwrap());
+    backend.set_endpoint_mute(&mic, false).unwrap();
+    assert!(!backend.endpoint_muted(&mic).unwrap());
+
+    // Read from the JSON listing, so the answer doesn't depend on pactl's language
+    std::env::set_var("LANG", "de_DE.UTF-8");
+    std::env::set_var("LC_ALL", "de_DE.UTF-8");
+    backend.set_endpoint_mute(&mic, true).unwrap();
+    assert!(backend.endpoint_muted(&mic).unwrap());
+    backend.set_endpoint_mute(&mic, false).unwrap();
+    assert!(!backend.endpoint_muted(&mic).unwrap());
+
+    // A stream that went away is an error, not a stale volume
+    let mut pacat = fixture.stream.take().unwrap();
+    pacat.kill().unwrap();
+    pacat.wait().unwrap();
+    backend.invalidate_caches();
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while backend.session_volume(&stream).is_ok() {
+        assert!(Instant::now() < deadline, "the stream outlived pacat");
+        std::thread::sleep(Duration::from_millis(100));
+        backend.invalidate_caches();
+    }
+}
@@ -0,0 +1,65 @@
+#[allow(dead_code)]
+#[path = "../src/audio/mod.rs"]
+mod audio;
+#[allow(dead_code)]
+#[path = "../src/buttons.rs"]
+mod buttons;
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/detect.rs"]
+mod detect;
+#[allow(dead_code)]
+#[path = "../src/engine.rs"]
+mod engine;
+#[allow(dead_code)]
+#[path = "../src/matching.rs"]
+mod matching;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+#[allow(dead_code)]
+#[path = "../src/replay.rs"]
+mod replay;
+#[allow(dead_code)]
+#[path = "../src/smoothing.rs"]
+mod smoothing;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/transport/mod.rs"]
+mod transport;
+
+use replay::ReplayOptions;
+use std::path::PathBuf;
+
+fn data(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/replay").join(name)
+}
+
+fn options(expect: Option<PathBuf>, write_expected: Option<PathBuf>) -> ReplayOptions {
+    ReplayOptions { recording: data("session.txt"), speed: 0.0, config: data("mapping.json"), expect, write_expected }
+}
+
+/// Both controllers, a glob knob, "Others", a mute button and the selector switch, with a line
+/// recorded out of order.
+#[test]
+fn recording_matches_its_trace() {
+    assert!(replay::run(&options(Some(data("trace.txt")), None)).unwrap());
+}
+
+#[test]
+fn a_changed_trace_is_reported() {
+    let dir = std::env::temp_dir().join(format!("rvci-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let written = dir.join("trace.txt");
+    assert!(replay::run(&options(None, Some(written.clone()))).unwrap());
+    let expected = std::fs::read_to_string(data("trace.txt")).unwrap();
+    let lines: Vec<&str> = expected.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(std::fs::read_to_string(&written).unwrap(), lines.join("\n") + "\n");
+
+    std::fs::write(&written, expected.replace("volume chrom 0.300", "volume chrom 0.350")).unwrap();
+    assert!(!replay::run(&options(Some(written.clone()), None)).unwrap());
+    std::fs::write(&written, expected.replace("mute discord true\n", "")).unwrap();
+    assert!(!replay::run(&options(Some(written), None)).unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,80 @@
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+#[path = "../src/smoothing.rs"]
+mod smoothing;
+
+use config::{DialConfig, DialFilter};
+use smoothing::{auto_tune, Smoother, TUNE_READINGS};
+use std::time::{Duration, Instant};
+
+/// Runs `values` through `filter`, one reading every 25 ms like the controller loop.
+fn filtered(filter: DialFilter, values: &[f32]) -> Vec<f32> {
+    let mut smoother = Smoother::default();
+    let start = Instant::now();
+    values.iter().enumerate().map(|(i, v)| smoother.process(&filter, *v, start + Duration::from_millis(25 * i as u64))).collect()
+}
+
+#[test]
+fn filters_smooth_readings() {
+    // The default keeps the old behaviour: jumps are taken, small moves eased in
+    let ema = filtered(DialFilter::default(), &[0.5, 0.52, 0.52]);
+    assert_eq!(ema[0], 0.5);
+    assert!((ema[1] - 0.507).abs() < 1e-6);
+    assert_eq!(filtered(DialFilter::None, &[0.5, 0.52]), vec![0.5, 0.52]);
+
+    let median = filtered(DialFilter::Median { samples: 3 }, &[0.5, 0.5, 0.9, 0.5, 0.5]);
+    assert!(median.iter().all(|v| *v == 0.5), "{:?}", median);
+
+    // At rest the one-euro filter cuts wobble down, a fast turn gets through almost at once
+    let one_euro = DialFilter::OneEuro { min_cutoff: 1.0, beta: 5.0 };
+    let wobble: Vec<f32> = (0..40).map(|i| if i % 2 == 0 { 0.5 } else { 0.51 }).collect();
+    let rest = filtered(one_euro, &wobble);
+    assert!(smoothing::spread(&rest[20..]) < 0.003, "{:?}", rest);
+    let turn: Vec<f32> = (0..10).map(|i| i as f32 / 9.0).collect();
+    assert!(filtered(one_euro, &turn)[9] > 0.8);
+}
+
+#[test]
+fn deadband_and_hysteresis() {
+    let dial = DialConfig { deadband: 0.01, hysteresis: 0.03, ..DialConfig::default() };
+    let mut smoother = Smoother::default();
+    assert!(smoother.should_apply(&dial, 0.5, -1.0));
+    assert!(!smoother.should_apply(&dial, 0.505, 0.5));
+    assert!(smoother.should_apply(&dial, 0.52, 0.5));
+    // Turning back has to go further than the hysteresis
+    assert!(!smoother.should_apply(&dial, 0.5, 0.52));
+    assert!(smoother.should_apply(&dial, 0.48, 0.52));
+    // The ends always get through
+    assert!(smoother.should_apply(&dial, 0.0, 0.005));
+}
+
+#[test]
+fn auto_tune_reads_the_wobble() {
+    let steady = vec![0.5; TUNE_READINGS];
+    assert_eq!(auto_tune(&steady[1..]), None);
+    assert_eq!(auto_tune(&steady).unwrap().filter, DialFilter::None);
+
+    let mut spiky = steady.clone();
+    spiky[7] = 0.56;
+    assert_eq!(auto_tune(&spiky).unwrap().filter, DialFilter::Median { samples: 5 });
+
+    let noisy: Vec<f32> = (0..TUNE_READINGS).map(|i| 0.5 + [0.0, 0.01, -0.01, 0.005][i % 4]).collect();
+    let tuning = auto_tune(&noisy).unwrap();
+    assert!(matches!(tuning.filter, DialFilter::OneEuro { .. }), "{:?}", tuning);
+    assert_eq!(tuning.deadband, 0.01);
+    assert_eq!(tuning.hysteresis, 0.02);
+}
+
+#[test]
+fn filters_in_mapping_json() {
+    let dial: DialConfig = serde_json::from_str(r#"{"type": "system", "process_name": null, "filter": {"kind": "median", "samples": 7}, "hysteresis": 0.02}"#).unwrap();
+    assert_eq!(dial.filter, DialFilter::Median { samples: 7 });
+    assert_eq!(dial.deadband, config::DEFAULT_DEADBAND);
+    // Defaults stay out of the file
+    let written = serde_json::to_string(&DialConfig::default()).unwrap();
+    assert!(!written.contains("filter") && !written.contains("deadband") && !written.contains("hysteresis"), "{}", written);
+}
@@ -0,0 +1,92 @@
+#[allow(dead_code)]
+#[path = "../src/audio/mod.rs"]
+mod audio;
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/matching.rs"]
+mod matching;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+
+use audio::{AudioBackend, MockBackend};
+use config::{DialConfig, DialType, FadeLaw, MatchRule, ProcessTarget};
+use matching::{glob_matches, SessionMatcher};
+
+#[test]
+fn a_knob_turns_several_processes() {
+    let dial: DialConfig = serde_json::from_str(r#"{"type": "process", "process_name": "game.exe",
+        "targets": [{"process_name": "steam.exe", "gain": 0.5}, {"process_name": "launcher.exe"}, {"process_name": ""}]}"#).unwrap();
+    assert_eq!(dial.dial_type, DialType::Process);
+    assert_eq!(dial.process_targets(), vec![("game.exe", 1.0), ("steam.exe", 0.5), ("launcher.exe", 1.0)]);
+
+    // A knob without its own process still turns the extra ones
+    let extra_only = DialConfig { targets: vec![ProcessTarget { process_name: "steam.exe".to_string(), gain: 1.0 }], ..DialConfig::default() };
+    assert_eq!(extra_only.process_targets(), vec![("steam.exe", 1.0)]);
+
+    // Full gain and an empty list stay out of the file
+    let written = serde_json::to_string(&dial).unwrap();
+    assert!(written.contains(r#"{"process_name":"launcher.exe"}"#), "{}", written);
+    assert!(!serde_json::to_string(&DialConfig::default()).unwrap().contains("targets"));
+}
+
+#[test]
+fn globs() {
+    assert!(glob_matches("discord*", "DiscordPTB"));
+    assert!(glob_matches("*game?", "MyGame2"));
+    assert!(glob_matches("*", ""));
+    assert!(!glob_matches("discord", "DiscordPTB"));
+    assert!(!glob_matches("game?", "game"));
+    assert!(glob_matches("a*b*c", "aXbYbZc"));
+}
+
+#[test]
+fn match_rules_pick_sessions() {
+    let sessions = MockBackend::new()
+        .with_app(1, "Discord", "C:\\Users\\me\\AppData\\Local\\Discord\\app-1.0\\Discord.exe", "Discord")
+        .with_app(2, "DiscordPTB", "C:\\Users\\me\\AppData\\Local\\DiscordPTB\\DiscordPTB.exe", "")
+        .with_app(3, "game-v1.2", "D:/Games/Game/game-v1.2.exe", "Cool Game")
+        .list_sessions().unwrap();
+    let matched = |rule: MatchRule, pattern: &str| -> Vec<u32> {
+        let matcher = SessionMatcher::new(rule, pattern).unwrap();
+        sessions.iter().filter(|s| matcher.matches(s)).map(|s| s.pid).collect()
+    };
+    assert_eq!(matched(MatchRule::Name, "discord.exe"), vec![1]);
+    assert_eq!(matched(MatchRule::Glob, "Discord*.exe"), vec![1, 2]);
+    assert_eq!(matched(MatchRule::Regex, r"^game-v[\d.]+$"), vec![3]);
+    assert_eq!(matched(MatchRule::Path, "D:\\Games\\*"), vec![3]);
+    assert_eq!(matched(MatchRule::DisplayName, "cool game"), vec![3]);
+    assert!(SessionMatcher::new(MatchRule::Regex, "game(").is_err());
+
+    let preview = matching::preview(MatchRule::Glob, &["discord*", "spotify"], &sessions);
+    assert_eq!(preview[0].1.as_ref().unwrap(), &vec!["Discord".to_string(), "DiscordPTB".to_string()]);
+    assert!(preview[1].1.as_ref().unwrap().is_empty());
+
+    let dial: DialConfig = serde_json::from_str(r#"{"type": "process", "process_name": "discord*", "match": "glob"}"#).unwrap();
+    assert_eq!(dial.match_rule, MatchRule::Glob);
+}
+
+#[test]
+fn crossfade_laws() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+    assert_eq!(FadeLaw::Linear.volumes(0.25), (0.75, 0.25));
+    let (a, b) = FadeLaw::EqualPower.volumes(0.5);
+    assert!(close(a, b) && close(a * a + b * b, 1.0));
+    assert_eq!(FadeLaw::EqualPower.volumes(0.0), (1.0, 0.0));
+
+    // Reading the volumes back gives the position, also with one side not playing
+    for law in FadeLaw::ALL {
+        for position in [0.0, 0.3, 0.5, 1.0] {
+            let (a, b) = law.volumes(position);
+            assert!(close(law.position_of(Some(a), Some(b)).unwrap(), position), "{:?} at {}", law, position);
+            assert!(close(law.position_of(Some(a), None).unwrap(), position), "{:?} at {}", law, position);
+            assert!(close(law.position_of(None, Some(b)).unwrap(), position), "{:?} at {}", law, position);
+        }
+    }
+    assert_eq!(FadeLaw::Linear.position_of(None, None), None);
+
+    let dial: DialConfig = serde_json::from_str(r#"{"type": "crossfade", "process_name": "game", "fade_to": "discord", "fade_law": "equal_power"}"#).unwrap();
+    assert_eq!(dial.fade_law, FadeLaw::EqualPower);
+    assert_eq!(dial.process_targets(), vec![("game", 1.0), ("discord", 1.0)]);
+}
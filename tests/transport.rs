@@ -0,0 +1,326 @@
+#[allow(dead_code)]
+#[path = "../src/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../src/detect.rs"]
+mod detect;
+#[allow(dead_code)]
+#[path = "../src/protocol.rs"]
+mod protocol;
+#[allow(dead_code)]
+#[path = "../src/simulator.rs"]
+mod simulator;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/transport/mod.rs"]
+mod transport;
+
+use config::{ControllerConfig, MidiControl, SerialConfig, TransportConfig};
+use detect::{resolve_port, PortClaims};
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use transport::{Transport, TransportEvent};
+
+fn listen(transport: TransportConfig) -> Box<dyn Transport> {
+    let ports = Arc::new(PortClaims::default());
+    transport::open(&ControllerConfig { transport, ..ControllerConfig::default() }, &ports).expect("listen")
+}
+
+/// The next event that isn't `Idle`, giving up after two seconds.
+fn next(transport: &mut Box<dyn Transport>) -> TransportEvent {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        match transport.next_event().expect("transport failed") {
+            TransportEvent::Idle => continue,
+            event => return event,
+        }
+    }
+    panic!("nothing arrived");
+}
+
+fn line(text: &str) -> TransportEvent {
+    TransportEvent::Line(text.to_string())
+}
+
+fn is_connected(event: &TransportEvent) -> bool {
+    matches!(event, TransportEvent::Connected(_))
+}
+
+fn read_reply(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+    stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let mut reply = vec![0u8; len];
+    stream.read_exact(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn tcp_lines_arrive_in_pieces() {
+    let mut transport = listen(TransportConfig::Tcp { listen: "127.0.0.1:47811".into(), token: None });
+    let mut client = TcpStream::connect("127.0.0.1:47811").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+
+    client.write_all(b"HELLO version=1 sliders=3\n1|2").unwrap();
+    assert_eq!(next(&mut transport), line("HELLO version=1 sliders=3\n"));
+    client.write_all(b"|3\n").unwrap();
+    assert_eq!(next(&mut transport), line("1|2|3\n"));
+
+    transport.send(b"HELLO?\n").unwrap();
+    assert_eq!(read_reply(&mut client, 7), b"HELLO?\n");
+}
+
+#[test]
+fn tcp_token_is_checked_first() {
+    let token = Some("secret".to_string());
+    let mut transport = listen(TransportConfig::Tcp { listen: "127.0.0.1:47812".into(), token });
+
+    let mut intruder = TcpStream::connect("127.0.0.1:47812").unwrap();
+    intruder.write_all(b"AUTH guess\n512|512\n").unwrap();
+    let deadline = Instant::now() + Duration::from_millis(300);
+    while Instant::now() < deadline {
+        assert_eq!(transport.next_event().unwrap(), TransportEvent::Idle);
+    }
+    assert_eq!(read_reply(&mut intruder, 12), b"AUTH FAILED\n");
+
+    let mut client = TcpStream::connect("127.0.0.1:47812").unwrap();
+    client.write_all(b"AUTH secret\n512|512\n").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("512|512\n"));
+    assert_eq!(read_reply(&mut client, 8), b"AUTH OK\n");
+}
+
+#[test]
+fn tcp_keeps_the_paired_client_while_a_newcomer_pairs() {
+    let token = Some("secret".to_string());
+    let mut transport = listen(TransportConfig::Tcp { listen: "127.0.0.1:47816".into(), token });
+    let mut paired = TcpStream::connect("127.0.0.1:47816").unwrap();
+    paired.write_all(b"AUTH secret\n1|1\n").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("1|1\n"));
+    assert_eq!(read_reply(&mut paired, 8), b"AUTH OK\n");
+
+    // Someone connects but doesn't pair yet: the paired client keeps streaming
+    let mut stranger = TcpStream::connect("127.0.0.1:47816").unwrap();
+    paired.write_all(b"2|2\n").unwrap();
+    assert_eq!(next(&mut transport), line("2|2\n"));
+    transport.send(b"HELLO?\n").unwrap();
+    assert_eq!(read_reply(&mut paired, 7), b"HELLO?\n");
+
+    stranger.write_all(b"AUTH guess\n9|9\n").unwrap();
+    paired.write_all(b"3|3\n").unwrap();
+    assert_eq!(next(&mut transport), line("3|3\n"));
+    assert_eq!(read_reply(&mut stranger, 12), b"AUTH FAILED\n");
+
+    // A newcomer with the token takes over, e.g. the controller after a reboot
+    let mut rebooted = TcpStream::connect("127.0.0.1:47816").unwrap();
+    rebooted.write_all(b"AUTH secret\n4|4\n").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("4|4\n"));
+    let _ = paired.write_all(b"5|5\n");
+    rebooted.write_all(b"6|6\n").unwrap();
+    assert_eq!(next(&mut transport), line("6|6\n"));
+}
+
+#[test]
+fn udp_datagrams_are_lines() {
+    let token = Some("secret".to_string());
+    let mut transport = listen(TransportConfig::Udp { listen: "127.0.0.1:47813".into(), token });
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+    client.send_to(b"1|2|3", "127.0.0.1:47813").unwrap();
+    client.send_to(b"AUTH secret\n4|5|6", "127.0.0.1:47813").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("4|5|6\n"));
+
+    let mut reply = [0u8; 64];
+    let n = client.recv(&mut reply).unwrap();
+    assert_eq!(&reply[..n], b"AUTH OK\n");
+    transport.send(b"HELLO?\n").unwrap();
+    let n = client.recv(&mut reply).unwrap();
+    assert_eq!(&reply[..n], b"HELLO?\n");
+}
+
+/// A masked client text frame.
+fn client_frame(payload: &[u8]) -> Vec<u8> {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![0x81, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+#[test]
+fn websocket_handshake_and_frames() {
+    let mut transport = listen(TransportConfig::Websocket { listen: "127.0.0.1:47814".into(), token: None });
+    let mut client = TcpStream::connect("127.0.0.1:47814").unwrap();
+    // Example key from RFC 6455
+    client.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+    assert!(is_connected(&next(&mut transport)));
+
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.extend(read_reply(&mut client, 1));
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"));
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+    client.write_all(&client_frame(b"WORKS 2\n10|")).unwrap();
+    assert_eq!(next(&mut transport), line("WORKS 2\n"));
+    client.write_all(&client_frame(b"20\n")).unwrap();
+    assert_eq!(next(&mut transport), line("10|20\n"));
+
+    transport.send(b"HELLO?\n").unwrap();
+    assert_eq!(read_reply(&mut client, 9), b"\x81\x07HELLO?\n");
+}
+
+#[test]
+fn websocket_upgrade_does_not_hold_up_the_client() {
+    let mut transport = listen(TransportConfig::Websocket { listen: "127.0.0.1:47817".into(), token: None });
+    let request: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+    let mut first = TcpStream::connect("127.0.0.1:47817").unwrap();
+    first.write_all(request).unwrap();
+    first.write_all(&client_frame(b"1|1\n")).unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("1|1\n"));
+
+    // Half an upgrade request from a second client doesn't stall the first one
+    let mut second = TcpStream::connect("127.0.0.1:47817").unwrap();
+    second.write_all(&request[..20]).unwrap();
+    let started = Instant::now();
+    first.write_all(&client_frame(b"2|2\n")).unwrap();
+    assert_eq!(next(&mut transport), line("2|2\n"));
+    assert!(started.elapsed() < Duration::from_millis(500), "took {:?}", started.elapsed());
+
+    second.write_all(&request[20..]).unwrap();
+    second.write_all(&client_frame(b"3|3\n")).unwrap();
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("3|3\n"));
+    let response = read_reply(&mut second, 12);
+    assert!(response.starts_with(b"HTTP/1.1 101"));
+}
+
+#[test]
+fn decodes_midi_controls() {
+    use transport::midi::decode;
+    assert_eq!(decode(&[0xb0, 7, 100]), Some((MidiControl::Cc { channel: 1, number: 7 }, 100.0)));
+    assert_eq!(decode(&[0xbf, 1, 0]), Some((MidiControl::Cc { channel: 16, number: 1 }, 0.0)));
+    // Pitch bend keeps its 14 bits: centre is 64, full up just under 128
+    assert_eq!(decode(&[0xe2, 0x00, 0x40]), Some((MidiControl::PitchBend { channel: 3 }, 64.0)));
+    assert_eq!(decode(&[0xe0, 0x7f, 0x7f]), Some((MidiControl::PitchBend { channel: 1 }, 16383.0 / 128.0)));
+    // Notes, program changes and cut-off messages are not controls
+    assert_eq!(decode(&[0x90, 60, 127]), None);
+    assert_eq!(decode(&[0xc0, 5]), None);
+    assert_eq!(decode(&[0xb0, 7]), None);
+    assert_eq!(decode(&[]), None);
+}
+
+#[test]
+fn recordings_replay_in_order() {
+    use transport::replay::{parse_recording, RecordedLine, ReplayTransport};
+    let text = "# RVCI recording v1: seconds controller line\n2.0000 1 HELLO version=1 sliders=2\n2.0500 2 1|2\n2.1000 1 1023|0\n";
+    let lines = parse_recording(text).unwrap();
+    assert_eq!(lines[1], RecordedLine { time: Duration::from_millis(2050), controller: 1, line: "1|2".into() });
+    assert!(parse_recording("soon 1 1|2").is_err());
+    assert!(parse_recording("1.0 0 1|2").is_err());
+
+    // The lead-in before the first line is skipped, the gaps are kept at 10x
+    let started = Instant::now();
+    let mut replay: Box<dyn Transport> = Box::new(ReplayTransport::new(lines, 10.0));
+    assert!(is_connected(&next(&mut replay)));
+    assert_eq!(next(&mut replay), line("HELLO version=1 sliders=2"));
+    assert_eq!(next(&mut replay), line("1|2"));
+    assert_eq!(next(&mut replay), line("1023|0"));
+    assert_eq!(next(&mut replay), TransportEvent::Finished);
+    let took = started.elapsed();
+    assert!(took >= Duration::from_millis(10) && took < Duration::from_millis(500), "took {:?}", took);
+}
+
+#[test]
+fn simulator_commands() {
+    use simulator::{SimulatedController, Step};
+    let mut controller = SimulatedController::new(2, 1, 1);
+    assert_eq!(controller.hello()[0], "HELLO version=1 sliders=2 switches=1 buttons=1 encoders=1 name=RVCI Simulator");
+    assert_eq!(controller.command("# comment").unwrap(), vec![]);
+    assert_eq!(controller.command("slider 2 1023").unwrap(), vec![Step::Slider { index: 1, value: 1023 }]);
+    // A gradual move is one step however long it takes
+    assert_eq!(controller.command("slider 2 0 0.1").unwrap(), vec![
+        Step::Ramp { index: 1, from: 1023, to: 0, duration: Duration::from_millis(100) },
+    ]);
+    assert_eq!(controller.command("slider 1 500 1e9").unwrap(), vec![
+        Step::Ramp { index: 0, from: 0, to: 500, duration: Duration::from_secs(1_000_000_000) },
+    ]);
+    assert_eq!(controller.command("hold 1 0.8").unwrap(), vec![
+        Step::Send("BUTTON 1 DOWN".into()),
+        Step::Wait(Duration::from_millis(800)),
+        Step::Send("BUTTON 1 UP".into()),
+    ]);
+    assert_eq!(controller.command("turn 1 -2").unwrap(), vec![Step::Send("ENCODER 1 -2".into())]);
+    assert_eq!(controller.command("switch 2").unwrap(), vec![Step::Send("WORKS 2".into())]);
+    assert!(controller.command("press 2").is_err());
+    assert!(controller.command("slider 1 2000").is_err());
+    assert!(controller.command("dance").is_err());
+    // Longer than a Duration holds, or than the clock can count from now
+    for command in ["wait 1e20", "wait 1e19", "hold 1 1e20", "slider 1 5 1e20", "wait -1", "wait inf", "wait NaN"] {
+        assert_eq!(controller.command(command).unwrap_err().to_string(), "expected a number of seconds", "{}", command);
+    }
+}
+
+#[test]
+fn simulator_drives_a_tcp_controller() {
+    let mut transport = listen(TransportConfig::Tcp { listen: "127.0.0.1:47815".into(), token: Some("secret".into()) });
+    let script = std::env::temp_dir().join("rvci-simulator-script.txt");
+    std::fs::write(&script, "slider 1 800\nwait 0.05\npress 1\nswitch 2\n").unwrap();
+    let options = simulator::SimulatorOptions {
+        target: simulator::Target::Tcp { address: "127.0.0.1:47815".into(), token: Some("secret".into()) },
+        script: Some(script),
+        sliders: 2,
+        buttons: 1,
+        encoders: 0,
+    };
+    let simulated = std::thread::spawn(move || simulator::run(&options));
+
+    assert!(is_connected(&next(&mut transport)));
+    assert_eq!(next(&mut transport), line("HELLO version=1 sliders=2 switches=1 buttons=1 encoders=0 name=RVCI Simulator\r\n"));
+    assert_eq!(next(&mut transport), line("WORKS 1\r\n"));
+    let mut lines = Vec::new();
+    while let TransportEvent::Line(line) = next(&mut transport) {
+        lines.push(line.trim().to_string());
+        if line.starts_with("WORKS 2") { break; }
+    }
+    let press = lines.iter().position(|l| l == "BUTTON 1 DOWN").expect("no press");
+    assert_eq!(lines[press + 1], "BUTTON 1 UP");
+    assert!(lines[..press].contains(&"800|0".to_string()));
+    simulated.join().unwrap().unwrap();
+}
+
+#[test]
+fn a_port_belongs_to_one_controller_at_a_time() {
+    let claims = Arc::new(PortClaims::default());
+    let first = claims.claim("/dev/ttyUSB0").expect("free port");
+    assert!(claims.claim("/dev/ttyUSB0").is_none());
+    let other = claims.claim("/dev/ttyUSB1").expect("free port");
+    assert_eq!(other.port(), "/dev/ttyUSB1");
+
+    // Closing the connection hands the port back
+    drop(first);
+    assert!(claims.claim("/dev/ttyUSB0").is_some());
+
+    // A fixed port a controller already holds is an error, not a second connection
+    let serial = SerialConfig { port: "/dev/ttyUSB1".into(), ..ControllerConfig::default().serial };
+    let err = resolve_port(&serial, &claims).unwrap_err();
+    assert_eq!(err.to_string(), "/dev/ttyUSB1 is used by another controller");
+    drop(other);
+    assert_eq!(resolve_port(&serial, &claims).unwrap().port(), "/dev/ttyUSB1");
+}
+
+#[test]
+fn pairing_tokens_are_random_hex() {
+    let token = transport::generate_token().unwrap();
+    assert_eq!(token.len(), 32);
+    assert!(token.chars().all(|c| c.is_ascii_hexdigit()), "{}", token);
+    assert_ne!(token, transport::generate_token().unwrap());
+}